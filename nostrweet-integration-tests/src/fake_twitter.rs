@@ -0,0 +1,474 @@
+use anyhow::{Context, Result, bail};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode, header};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Bearer token the fake server hands to nostrweet, any non-empty token is accepted
+pub const FAKE_BEARER_TOKEN: &str = "fake-twitter-bearer-token";
+
+/// Fixtures bundled with the integration tests
+pub fn default_fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/twitter")
+}
+
+/// Contents of `rate_limit.json` in the fixtures directory
+#[derive(Debug, Clone, Deserialize)]
+struct RateLimitConfig {
+    /// Requests allowed per window
+    limit: u64,
+    /// Window length in seconds
+    window_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        // Twitter's app-level limit for the endpoints we use
+        Self {
+            limit: 900,
+            window_secs: 900,
+        }
+    }
+}
+
+struct RateLimitWindow {
+    remaining: u64,
+    reset: u64,
+}
+
+/// Users and tweets served by the fake API
+///
+/// Fixture layout:
+/// - `users/<username>.json`: a user object as in `data` of `/2/users/by/username/:u`
+/// - `tweets/<id>.json`: a tweet object as in `data` of `/2/tweets/:id`
+/// - `rate_limit.json` (optional): `{ "limit": 900, "window_secs": 900 }`
+struct Fixtures {
+    users: Vec<Value>,
+    tweets: HashMap<String, Value>,
+    rate_limit: RateLimitConfig,
+}
+
+impl Fixtures {
+    fn load(dir: &Path) -> Result<Self> {
+        let users = read_json_dir(&dir.join("users"))?.into_values().collect();
+        let tweets = read_json_dir(&dir.join("tweets"))?;
+
+        let rate_limit_path = dir.join("rate_limit.json");
+        let rate_limit = if rate_limit_path.exists() {
+            let json = std::fs::read_to_string(&rate_limit_path)
+                .with_context(|| format!("Failed to read {}", rate_limit_path.display()))?;
+            serde_json::from_str(&json)
+                .with_context(|| format!("Failed to parse {}", rate_limit_path.display()))?
+        } else {
+            RateLimitConfig::default()
+        };
+
+        Ok(Self {
+            users,
+            tweets,
+            rate_limit,
+        })
+    }
+
+    fn user_by_username(&self, username: &str) -> Option<&Value> {
+        self.users.iter().find(|user| {
+            user["username"]
+                .as_str()
+                .is_some_and(|u| u.eq_ignore_ascii_case(username))
+        })
+    }
+
+    fn user_by_id(&self, user_id: &str) -> Option<&Value> {
+        self.users
+            .iter()
+            .find(|user| user["id"].as_str() == Some(user_id))
+    }
+
+    /// Tweets of a user, newest first like the timeline endpoint
+    fn timeline(&self, user_id: &str) -> Vec<&Value> {
+        let mut tweets: Vec<&Value> = self
+            .tweets
+            .values()
+            .filter(|tweet| tweet["author_id"].as_str() == Some(user_id))
+            .collect();
+        tweets.sort_by_key(|tweet| std::cmp::Reverse(tweet_id_number(tweet)));
+        tweets
+    }
+}
+
+fn read_json_dir(dir: &Path) -> Result<HashMap<String, Value>> {
+    let mut entries = HashMap::new();
+    if !dir.exists() {
+        return Ok(entries);
+    }
+
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let value = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        entries.insert(name.to_string(), value);
+    }
+
+    Ok(entries)
+}
+
+fn tweet_id_number(tweet: &Value) -> u128 {
+    tweet["id"]
+        .as_str()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or_default()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+struct FakeTwitterState {
+    fixtures: Fixtures,
+    window: Mutex<RateLimitWindow>,
+}
+
+impl FakeTwitterState {
+    /// Counts a request against the current window, false once it is exhausted
+    fn take_request(&self) -> (bool, u64, u64) {
+        let now = unix_now();
+        let mut window = match self.window.lock() {
+            Ok(window) => window,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if now >= window.reset {
+            window.remaining = self.fixtures.rate_limit.limit;
+            window.reset = now + self.fixtures.rate_limit.window_secs;
+        }
+
+        let allowed = window.remaining > 0;
+        window.remaining = window.remaining.saturating_sub(1);
+        (allowed, window.remaining, window.reset)
+    }
+
+    fn handle(
+        &self,
+        method: &Method,
+        path: &str,
+        query: Option<&str>,
+        authorized: bool,
+    ) -> Response<Full<Bytes>> {
+        if !authorized {
+            return json_response(
+                StatusCode::UNAUTHORIZED,
+                json!({ "title": "Unauthorized", "status": 401, "detail": "Unauthorized" }),
+            );
+        }
+
+        let (allowed, remaining, reset) = self.take_request();
+        let mut response = if !allowed {
+            json_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                json!({ "title": "Too Many Requests", "status": 429, "detail": "Too Many Requests" }),
+            )
+        } else if method != Method::GET {
+            json_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                json!({ "title": "Method Not Allowed" }),
+            )
+        } else {
+            let params: HashMap<String, String> =
+                url::form_urlencoded::parse(query.unwrap_or("").as_bytes())
+                    .into_owned()
+                    .collect();
+            let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+            match segments.as_slice() {
+                ["2", "tweets"] => self.tweets_lookup(&params),
+                ["2", "tweets", id] => self.tweet(id),
+                ["2", "users", "by", "username", username] => self.user(username),
+                ["2", "users", user_id, "tweets"] => self.user_tweets(user_id, &params),
+                _ => json_response(StatusCode::NOT_FOUND, json!({ "title": "Not Found" })),
+            }
+        };
+
+        let headers = response.headers_mut();
+        for (name, value) in [
+            ("x-rate-limit-limit", self.fixtures.rate_limit.limit),
+            ("x-rate-limit-remaining", remaining),
+            ("x-rate-limit-reset", reset),
+        ] {
+            headers.insert(name, header::HeaderValue::from(value));
+        }
+        response
+    }
+
+    fn tweet(&self, tweet_id: &str) -> Response<Full<Bytes>> {
+        let Some(tweet) = self.fixtures.tweets.get(tweet_id) else {
+            // The API reports missing tweets with a 200 and an errors array
+            return json_response(
+                StatusCode::OK,
+                json!({
+                    "errors": [{
+                        "value": tweet_id,
+                        "detail": format!("Could not find tweet with id: [{tweet_id}]."),
+                        "title": "Not Found Error",
+                        "type": "https://api.twitter.com/2/problems/resource-not-found"
+                    }]
+                }),
+            );
+        };
+
+        let mut body = json!({ "data": tweet });
+        if let Some(author) = tweet["author_id"]
+            .as_str()
+            .and_then(|id| self.fixtures.user_by_id(id))
+        {
+            body["includes"] = json!({ "users": [author] });
+        }
+        json_response(StatusCode::OK, body)
+    }
+
+    /// Batch lookup, unknown IDs end up in the errors array like deleted tweets
+    fn tweets_lookup(&self, params: &HashMap<String, String>) -> Response<Full<Bytes>> {
+        let ids = params.get("ids").map(String::as_str).unwrap_or("");
+        let mut data = Vec::new();
+        let mut errors = Vec::new();
+        for id in ids.split(',').filter(|id| !id.is_empty()) {
+            match self.fixtures.tweets.get(id) {
+                Some(tweet) => data.push(tweet.clone()),
+                None => errors.push(json!({
+                    "value": id,
+                    "resource_id": id,
+                    "detail": format!("Could not find tweet with ids: [{id}]."),
+                    "title": "Not Found Error",
+                    "type": "https://api.twitter.com/2/problems/resource-not-found"
+                })),
+            }
+        }
+
+        let mut body = json!({});
+        if !data.is_empty() {
+            body["data"] = json!(data);
+        }
+        if !errors.is_empty() {
+            body["errors"] = json!(errors);
+        }
+        json_response(StatusCode::OK, body)
+    }
+
+    fn user(&self, username: &str) -> Response<Full<Bytes>> {
+        match self.fixtures.user_by_username(username) {
+            Some(user) => json_response(StatusCode::OK, json!({ "data": user })),
+            None => json_response(
+                StatusCode::OK,
+                json!({
+                    "errors": [{
+                        "value": username,
+                        "detail": format!("Could not find user with username: [{username}]."),
+                        "title": "Not Found Error",
+                        "type": "https://api.twitter.com/2/problems/resource-not-found"
+                    }]
+                }),
+            ),
+        }
+    }
+
+    fn user_tweets(
+        &self,
+        user_id: &str,
+        params: &HashMap<String, String>,
+    ) -> Response<Full<Bytes>> {
+        let max_results = params
+            .get("max_results")
+            .and_then(|m| m.parse::<usize>().ok())
+            .unwrap_or(10)
+            .clamp(5, 100);
+        let since_id = params
+            .get("since_id")
+            .and_then(|id| id.parse::<u128>().ok());
+        let until_id = params
+            .get("until_id")
+            .and_then(|id| id.parse::<u128>().ok());
+        // Pagination tokens are opaque to clients, here they are just an offset
+        let offset = params
+            .get("pagination_token")
+            .and_then(|token| token.parse::<usize>().ok())
+            .unwrap_or(0);
+
+        let timeline: Vec<&Value> = self
+            .fixtures
+            .timeline(user_id)
+            .into_iter()
+            .filter(|tweet| since_id.is_none_or(|since| tweet_id_number(tweet) > since))
+            .filter(|tweet| until_id.is_none_or(|until| tweet_id_number(tweet) < until))
+            .collect();
+        let page: Vec<&Value> = timeline
+            .iter()
+            .skip(offset)
+            .take(max_results)
+            .copied()
+            .collect();
+
+        if page.is_empty() {
+            return json_response(StatusCode::OK, json!({ "meta": { "result_count": 0 } }));
+        }
+
+        let mut meta = json!({
+            "result_count": page.len(),
+            "newest_id": page.first().map(|t| &t["id"]),
+            "oldest_id": page.last().map(|t| &t["id"]),
+        });
+        if offset + page.len() < timeline.len() {
+            meta["next_token"] = json!((offset + page.len()).to_string());
+        }
+
+        let mut body = json!({ "data": page, "meta": meta });
+        if let Some(author) = self.fixtures.user_by_id(user_id) {
+            body["includes"] = json!({ "users": [author] });
+        }
+        json_response(StatusCode::OK, body)
+    }
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body.to_string())));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// In-process stand-in for the Twitter API v2, serving tweets and users from fixtures
+pub struct FakeTwitter {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl FakeTwitter {
+    /// Start serving the fixtures on 127.0.0.1 (port 0 picks a free port)
+    pub async fn start(fixtures_dir: &Path, port: u16) -> Result<Self> {
+        let fixtures = Fixtures::load(fixtures_dir)?;
+        if fixtures.users.is_empty() {
+            bail!("No user fixtures found in {}", fixtures_dir.display());
+        }
+        info!(
+            "Starting fake Twitter API with {} user(s) and {} tweet(s)",
+            fixtures.users.len(),
+            fixtures.tweets.len()
+        );
+
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .with_context(|| format!("Failed to bind fake Twitter API to port {port}"))?;
+        let addr = listener.local_addr()?;
+
+        let state = Arc::new(FakeTwitterState {
+            window: Mutex::new(RateLimitWindow {
+                remaining: fixtures.rate_limit.limit,
+                reset: unix_now() + fixtures.rate_limit.window_secs,
+            }),
+            fixtures,
+        });
+
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, peer) = tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("Fake Twitter API failed to accept connection: {e}");
+                            continue;
+                        }
+                    },
+                };
+
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request: Request<Incoming>| {
+                        let state = state.clone();
+                        async move {
+                            debug!("Fake Twitter API: {} {}", request.method(), request.uri());
+                            let authorized = request
+                                .headers()
+                                .get(header::AUTHORIZATION)
+                                .and_then(|v| v.to_str().ok())
+                                .and_then(|v| v.strip_prefix("Bearer "))
+                                .is_some_and(|token| !token.is_empty());
+                            Ok::<_, Infallible>(state.handle(
+                                request.method(),
+                                request.uri().path(),
+                                request.uri().query(),
+                                authorized,
+                            ))
+                        }
+                    });
+
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        debug!("Fake Twitter API connection from {peer} failed: {e}");
+                    }
+                });
+            }
+        });
+
+        info!("Fake Twitter API listening on http://{addr}");
+        Ok(Self {
+            addr,
+            shutdown: Some(shutdown_tx),
+            task: Some(task),
+        })
+    }
+
+    /// Base URL to pass to nostrweet as TWITTER_API_BASE
+    pub fn api_base(&self) -> String {
+        format!("http://{}/2", self.addr)
+    }
+
+    /// Stop accepting connections
+    pub async fn stop(&mut self) -> Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(task) = self.task.take() {
+            task.await.context("Fake Twitter API task panicked")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FakeTwitter {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
+mod fake_twitter;
 mod relay;
 mod test_runner;
 mod tests;
@@ -30,9 +32,17 @@ struct Cli {
     #[arg(short, long)]
     verbose: bool,
 
-    /// Twitter Bearer Token for API access
+    /// Twitter Bearer Token for API access (the fake Twitter API is used without one)
     #[arg(long, env = "TWITTER_BEARER_TOKEN")]
-    twitter_token: String,
+    twitter_token: Option<String>,
+
+    /// Serve Twitter API requests from fixtures instead of the real API
+    #[arg(long)]
+    fake_twitter: bool,
+
+    /// Fixtures directory for the fake Twitter API
+    #[arg(long)]
+    twitter_fixtures: Option<PathBuf>,
 
     /// BIP39 mnemonic for deterministic key derivation
     #[arg(
@@ -57,6 +67,13 @@ enum Commands {
 
     /// Clean up test artifacts
     Cleanup,
+
+    /// Run only the fake Twitter API, e.g. for manual testing with TWITTER_API_BASE
+    FakeTwitter {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8081)]
+        port: u16,
+    },
 }
 
 #[tokio::main]
@@ -71,32 +88,70 @@ async fn main() -> Result<()> {
         .with_env_filter(filter)
         .init();
 
+    let fixtures_dir = cli
+        .twitter_fixtures
+        .clone()
+        .unwrap_or_else(fake_twitter::default_fixtures_dir);
+
     match cli.command {
-        Commands::RunAll => {
-            info!("Running all integration tests");
-            test_runner::run_all_tests(
-                cli.relay_port,
-                cli.keep_relay,
-                &cli.twitter_token,
-                &cli.mnemonic,
-            )
-            .await?;
-        }
-        Commands::Run { test } => {
-            info!("Running test: {test}");
-            test_runner::run_single_test(
-                &test,
-                cli.relay_port,
-                cli.keep_relay,
-                &cli.twitter_token,
-                &cli.mnemonic,
-            )
-            .await?;
-        }
         Commands::Cleanup => {
             info!("Cleaning up test artifacts");
             test_runner::cleanup().await?;
         }
+        Commands::FakeTwitter { port } => {
+            let mut server = fake_twitter::FakeTwitter::start(&fixtures_dir, port).await?;
+            println!("TWITTER_API_BASE={}", server.api_base());
+            tokio::signal::ctrl_c().await?;
+            server.stop().await?;
+        }
+        command => {
+            // Without a real token, point nostrweet at the fake Twitter API
+            let mut fake = None;
+            let twitter = match cli.twitter_token {
+                Some(token) if !cli.fake_twitter => test_runner::TwitterApi {
+                    token,
+                    api_base: None,
+                },
+                _ => {
+                    let server = fake_twitter::FakeTwitter::start(&fixtures_dir, 0).await?;
+                    let api_base = server.api_base();
+                    fake = Some(server);
+                    test_runner::TwitterApi {
+                        token: fake_twitter::FAKE_BEARER_TOKEN.to_string(),
+                        api_base: Some(api_base),
+                    }
+                }
+            };
+
+            let result = match command {
+                Commands::Run { test } => {
+                    info!("Running test: {test}");
+                    test_runner::run_single_test(
+                        &test,
+                        cli.relay_port,
+                        cli.keep_relay,
+                        &twitter,
+                        &cli.mnemonic,
+                    )
+                    .await
+                }
+                _ => {
+                    info!("Running all integration tests");
+                    test_runner::run_all_tests(
+                        cli.relay_port,
+                        cli.keep_relay,
+                        &twitter,
+                        &cli.mnemonic,
+                    )
+                    .await
+                }
+            };
+
+            if let Some(mut server) = fake {
+                server.stop().await?;
+            }
+            result?;
+        }
     }
 
     Ok(())
@@ -22,6 +22,13 @@ pub struct TestInfo {
     pub run_fn: TestFn,
 }
 
+/// Twitter API the tests run against
+pub struct TwitterApi {
+    pub token: String,
+    /// Base URL of the fake Twitter API, None for the real one
+    pub api_base: Option<String>,
+}
+
 /// Context provided to each test
 pub struct TestContext {
     pub relay_url: String,
@@ -29,6 +36,7 @@ pub struct TestContext {
     pub private_key: String,
     pub nostrweet_binary: PathBuf,
     pub twitter_token: String,
+    pub twitter_api_base: Option<String>,
     pub mnemonic: String,
 }
 
@@ -43,6 +51,9 @@ impl TestContext {
             .env("NOSTRWEET_RELAYS", &self.relay_url)
             .env("TWITTER_BEARER_TOKEN", &self.twitter_token)
             .env("NOSTRWEET_MNEMONIC", &self.mnemonic);
+        if let Some(api_base) = &self.twitter_api_base {
+            cmd.env("TWITTER_API_BASE", api_base);
+        }
 
         // Add arguments
         for arg in args {
@@ -102,6 +113,9 @@ impl TestContext {
             .env("NOSTRWEET_RELAYS", &self.relay_url)
             .env("TWITTER_BEARER_TOKEN", &self.twitter_token)
             .env("NOSTRWEET_MNEMONIC", &self.mnemonic);
+        if let Some(api_base) = &self.twitter_api_base {
+            cmd.env("TWITTER_API_BASE", api_base);
+        }
 
         // Add arguments
         for arg in args {
@@ -182,7 +196,7 @@ fn get_tests() -> Vec<TestInfo> {
 pub async fn run_all_tests(
     relay_port: u16,
     keep_relay: bool,
-    twitter_token: &str,
+    twitter: &TwitterApi,
     mnemonic: &str,
 ) -> Result<()> {
     let tests = get_tests();
@@ -219,7 +233,8 @@ pub async fn run_all_tests(
             output_dir: temp_dir.path().to_path_buf(),
             private_key: hex::encode(rand::random::<[u8; 32]>()),
             nostrweet_binary: find_nostrweet_binary()?,
-            twitter_token: twitter_token.to_string(),
+            twitter_token: twitter.token.clone(),
+            twitter_api_base: twitter.api_base.clone(),
             mnemonic: mnemonic.to_string(),
         };
 
@@ -275,7 +290,7 @@ pub async fn run_single_test(
     test_name: &str,
     relay_port: u16,
     keep_relay: bool,
-    twitter_token: &str,
+    twitter: &TwitterApi,
     mnemonic: &str,
 ) -> Result<()> {
     let tests = get_tests();
@@ -297,7 +312,8 @@ pub async fn run_single_test(
         output_dir: temp_dir.path().to_path_buf(),
         private_key: hex::encode(rand::random::<[u8; 32]>()),
         nostrweet_binary: find_nostrweet_binary()?,
-        twitter_token: twitter_token.to_string(),
+        twitter_token: twitter.token.clone(),
+        twitter_api_base: twitter.api_base.clone(),
         mnemonic: mnemonic.to_string(),
     };
 
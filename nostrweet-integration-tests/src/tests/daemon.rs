@@ -70,7 +70,11 @@ async fn start_daemon(ctx: &TestContext, username: &str) -> Result<Child> {
     cmd.env("NOSTRWEET_OUTPUT_DIR", &ctx.output_dir)
         .env("NOSTRWEET_PRIVATE_KEY", &ctx.private_key)
         .env("NOSTRWEET_MNEMONIC", &ctx.mnemonic)
-        .arg("daemon")
+        .env("TWITTER_BEARER_TOKEN", &ctx.twitter_token);
+    if let Some(api_base) = &ctx.twitter_api_base {
+        cmd.env("TWITTER_API_BASE", api_base);
+    }
+    cmd.arg("daemon")
         .arg("--user")
         .arg(username)
         .arg("--relay")
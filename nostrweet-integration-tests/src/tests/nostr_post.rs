@@ -111,6 +111,8 @@ pub async fn run(ctx: &TestContext) -> Result<()> {
         },
         "author_id": "987654321",
         "created_at": "2024-01-01T00:05:00Z",
+        "conversation_id": "123456789",
+        "in_reply_to_user_id": "987654321",
         "referenced_tweets": [
             {
                 "type": "replied_to",
@@ -137,7 +139,7 @@ pub async fn run(ctx: &TestContext) -> Result<()> {
     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
     let reply_events = client
-        .fetch_events(filter, std::time::Duration::from_secs(5))
+        .fetch_events(filter.clone(), std::time::Duration::from_secs(5))
         .await?;
 
     // Find the reply event
@@ -152,9 +154,17 @@ pub async fn run(ctx: &TestContext) -> Result<()> {
         content = reply_event.content
     );
 
-    // Check for reply marker (using ↩️ emoji or "Reply to" text)
-    if !reply_event.content.contains("Reply to") && !reply_event.content.contains("↩️") {
-        anyhow::bail!("Reply marker not found in event");
+    // Replying to yourself continues a thread, linked with a NIP-10 root marker
+    let root_id = event.id.to_hex();
+    let links_root = reply_event.tags.iter().any(|tag| {
+        let tag = tag.as_slice();
+        tag.len() >= 4 && tag[0] == "e" && tag[1] == root_id && tag[3] == "root"
+    });
+    if !links_root {
+        anyhow::bail!("Thread reply does not reference the root event {root_id}");
+    }
+    if reply_event.content.contains("Reply to") {
+        anyhow::bail!("Thread reply should not quote its parent");
     }
 
     info!("✅ Reply tweet posted successfully");
@@ -199,6 +209,27 @@ pub async fn run(ctx: &TestContext) -> Result<()> {
         .await
         .context("Failed to post quote tweet")?;
 
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+    let quote_events = client
+        .fetch_events(filter, std::time::Duration::from_secs(5))
+        .await?;
+    let quote_event = quote_events
+        .into_iter()
+        .find(|e| e.content.contains("Check out this tweet!"))
+        .context("Quote event not found")?;
+
+    // The quoted tweet was mirrored already, so it is referenced instead of copied
+    let quotes_root = quote_event.tags.iter().any(|tag| {
+        let tag = tag.as_slice();
+        tag.len() >= 2 && tag[0] == "q" && tag[1] == root_id
+    });
+    if !quotes_root || !quote_event.content.contains("nostr:nevent1") {
+        anyhow::bail!("Quote does not reference the quoted event {root_id}");
+    }
+
+    info!("✅ Quote tweet posted successfully");
+
     info!("✅ All Nostr posting tests completed successfully");
 
     Ok(())
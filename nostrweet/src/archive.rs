@@ -0,0 +1,935 @@
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::datetime_utils;
+use crate::twitter::{
+    Attachments, Cashtag, Entities, Hashtag, Includes, Media, MediaVariant, Mention, NoteTweet,
+    ReferencedTweet, Tweet, UrlEntity, User,
+};
+
+/// Folders media attachments live in, depending on the archive generation
+const MEDIA_DIRS: [&str; 2] = ["data/tweets_media", "data/tweet_media"];
+
+/// A long tweet and its note are created within this many seconds of each other
+const NOTE_TWEET_MAX_SKEW_SECS: i64 = 5;
+
+/// Where the archive files are read from
+enum ArchiveSource {
+    Directory(PathBuf),
+    Zip {
+        archive: zip::ZipArchive<File>,
+        /// Prefix of every entry, for zips that wrap the export in a folder
+        root: String,
+    },
+}
+
+/// A Twitter data export ("Download an archive of your data"), unpacked or as zip
+pub struct TwitterArchive {
+    source: ArchiveSource,
+    /// Archive paths of all data files, relative to the archive root
+    files: Vec<String>,
+    /// Folder holding media attachments, relative to the archive root
+    media_dir: Option<String>,
+    /// File names inside `media_dir`
+    media_files: HashSet<String>,
+}
+
+impl TwitterArchive {
+    /// Opens an unpacked archive directory or the zip file Twitter sends
+    pub fn open(path: &Path) -> Result<Self> {
+        let (source, files) = if path.is_dir() {
+            let files = list_directory_files(path)?;
+            (ArchiveSource::Directory(path.to_path_buf()), files)
+        } else {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open {path}", path = path.display()))?;
+            let archive = zip::ZipArchive::new(file).with_context(|| {
+                format!(
+                    "{path} is neither a directory nor a zip file",
+                    path = path.display()
+                )
+            })?;
+            let root = archive
+                .file_names()
+                .find_map(|name| name.strip_suffix("data/account.js"))
+                .unwrap_or_default()
+                .to_string();
+            let files = archive
+                .file_names()
+                .filter_map(|name| name.strip_prefix(root.as_str()))
+                .filter(|name| !name.ends_with('/'))
+                .map(str::to_string)
+                .collect();
+            (ArchiveSource::Zip { archive, root }, files)
+        };
+
+        if !files.iter().any(|f| f == "data/account.js") {
+            bail!(
+                "No data/account.js in {path}, is this a Twitter data archive?",
+                path = path.display()
+            );
+        }
+
+        let media_dir = MEDIA_DIRS
+            .iter()
+            .find(|dir| files.iter().any(|f| f.starts_with(&format!("{dir}/"))))
+            .map(|dir| dir.to_string());
+        let media_files = match &media_dir {
+            Some(dir) => files
+                .iter()
+                .filter_map(|f| f.strip_prefix(&format!("{dir}/")))
+                .map(str::to_string)
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        debug!(
+            "Opened archive with {count} file(s), {media} media file(s)",
+            count = files.len(),
+            media = media_files.len()
+        );
+
+        Ok(Self {
+            source,
+            files,
+            media_dir,
+            media_files,
+        })
+    }
+
+    fn read_file(&mut self, name: &str) -> Result<Option<String>> {
+        if !self.files.iter().any(|f| f == name) {
+            return Ok(None);
+        }
+
+        let contents = match &mut self.source {
+            ArchiveSource::Directory(dir) => fs::read_to_string(dir.join(name))
+                .with_context(|| format!("Failed to read {name} from archive"))?,
+            ArchiveSource::Zip { archive, root } => {
+                let mut entry = archive
+                    .by_name(&format!("{root}{name}"))
+                    .with_context(|| format!("Failed to open {name} in archive"))?;
+                let mut contents = String::new();
+                entry
+                    .read_to_string(&mut contents)
+                    .with_context(|| format!("Failed to read {name} from archive"))?;
+                contents
+            }
+        };
+        Ok(Some(contents))
+    }
+
+    /// The archived account, with bio and avatar from `profile.js` when present
+    pub fn account(&mut self) -> Result<User> {
+        let contents = self
+            .read_file("data/account.js")?
+            .context("Archive has no data/account.js")?;
+        let account = parse_ytd_file::<AccountEntry>(&contents)
+            .context("Failed to parse data/account.js")?
+            .into_iter()
+            .next()
+            .context("data/account.js contains no account")?
+            .account;
+
+        let profile = match self.read_file("data/profile.js")? {
+            Some(contents) => parse_ytd_file::<ProfileEntry>(&contents)
+                .context("Failed to parse data/profile.js")?
+                .into_iter()
+                .next()
+                .map(|entry| entry.profile),
+            None => None,
+        };
+        let (description, website, avatar) = match profile {
+            Some(profile) => {
+                let (bio, website) = profile
+                    .description
+                    .map(|d| (d.bio, d.website))
+                    .unwrap_or_default();
+                (bio, website, profile.avatar_media_url)
+            }
+            None => (None, None, None),
+        };
+
+        Ok(User {
+            id: account.account_id,
+            name: account.account_display_name,
+            username: account.username,
+            profile_image_url: avatar,
+            description: description.filter(|d| !d.is_empty()),
+            url: website.filter(|w| !w.is_empty()),
+            entities: None,
+        })
+    }
+
+    /// All archived tweets of `author`, converted to the API v2 shape, oldest first
+    pub fn tweets(&mut self, author: &User) -> Result<Vec<Tweet>> {
+        let part_pattern =
+            Regex::new(r"^data/tweets?(-part\d+)?\.js$").context("Invalid tweet file pattern")?;
+        let mut parts: Vec<String> = self
+            .files
+            .iter()
+            .filter(|f| part_pattern.is_match(f))
+            .cloned()
+            .collect();
+        parts.sort();
+
+        let mut tweets = Vec::new();
+        for part in parts {
+            let Some(contents) = self.read_file(&part)? else {
+                continue;
+            };
+            let entries = parse_ytd_file::<TweetEntry>(&contents)
+                .with_context(|| format!("Failed to parse {part}"))?;
+            debug!("Read {count} tweet(s) from {part}", count = entries.len());
+
+            for entry in entries {
+                let archived = entry.into_tweet();
+                match self.convert_tweet(archived, author) {
+                    Ok(tweet) => tweets.push(tweet),
+                    Err(e) => warn!("Skipping archived tweet: {e:#}"),
+                }
+            }
+        }
+
+        if let Some(contents) = self.read_file("data/note-tweet.js")? {
+            let notes = parse_ytd_file::<NoteEntry>(&contents)
+                .context("Failed to parse data/note-tweet.js")?;
+            attach_note_tweets(&mut tweets, notes.into_iter().map(|n| n.note_tweet));
+        }
+
+        tweets.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        Ok(tweets)
+    }
+
+    /// Archive file name of a media URL attached to a tweet, if it was exported
+    fn media_file_name(&self, tweet_id: &str, url: &str) -> Option<String> {
+        let name = format!("{tweet_id}-{basename}", basename = url_basename(url));
+        self.media_files.contains(&name).then_some(name)
+    }
+
+    fn convert_tweet(&self, archived: ArchivedTweet, author: &User) -> Result<Tweet> {
+        let created_at = datetime_utils::parse_twitter_legacy(&archived.created_at)
+            .with_context(|| format!("Tweet {id} has an invalid date", id = archived.id_str))?;
+
+        let mut referenced_tweets = Vec::new();
+        if let Some(reply_to) = archived.in_reply_to_status_id_str {
+            referenced_tweets.push(ReferencedTweet {
+                id: reply_to,
+                type_field: "replied_to".to_string(),
+                data: None,
+            });
+        }
+
+        // Media links show up as t.co URLs in the text, like in API responses
+        let archived_media = archived
+            .extended_entities
+            .map(|e| e.media)
+            .filter(|media| !media.is_empty())
+            .unwrap_or(archived.entities.media);
+
+        let mut urls: Vec<UrlEntity> = archived
+            .entities
+            .urls
+            .into_iter()
+            .map(|u| UrlEntity {
+                display_url: u.display_url.unwrap_or_else(|| u.url.clone()),
+                url: u.url,
+                expanded_url: u.expanded_url,
+            })
+            .collect();
+        for url in &urls {
+            if let Some(expanded) = &url.expanded_url
+                && let Some(quoted_id) = quoted_status_id(expanded)
+                && quoted_id != archived.id_str
+                && !referenced_tweets.iter().any(|r| r.id == quoted_id)
+            {
+                referenced_tweets.push(ReferencedTweet {
+                    id: quoted_id,
+                    type_field: "quoted".to_string(),
+                    data: None,
+                });
+            }
+        }
+
+        let mut media = Vec::new();
+        for item in archived_media {
+            if !urls.iter().any(|u| u.url == item.url) {
+                urls.push(UrlEntity {
+                    url: item.url.clone(),
+                    expanded_url: item.expanded_url.clone(),
+                    display_url: item.display_url.clone().unwrap_or_default(),
+                });
+            }
+            media.push(self.convert_media(&archived.id_str, item));
+        }
+
+        let media_keys: Vec<String> = media.iter().map(|m| m.media_key.clone()).collect();
+        let entities = Entities {
+            urls: (!urls.is_empty()).then_some(urls),
+            mentions: (!archived.entities.user_mentions.is_empty()).then(|| {
+                archived
+                    .entities
+                    .user_mentions
+                    .into_iter()
+                    .map(|m| Mention {
+                        username: m.screen_name,
+                    })
+                    .collect()
+            }),
+            hashtags: (!archived.entities.hashtags.is_empty()).then(|| {
+                archived
+                    .entities
+                    .hashtags
+                    .into_iter()
+                    .map(|h| Hashtag { tag: h.text })
+                    .collect()
+            }),
+            cashtags: (!archived.entities.symbols.is_empty()).then(|| {
+                archived
+                    .entities
+                    .symbols
+                    .into_iter()
+                    .map(|s| Cashtag { tag: s.text })
+                    .collect()
+            }),
+        };
+
+        Ok(Tweet {
+            id: archived.id_str,
+            text: archived.full_text,
+            author: author.clone(),
+            referenced_tweets: (!referenced_tweets.is_empty()).then_some(referenced_tweets),
+            attachments: (!media_keys.is_empty()).then(|| Attachments {
+                media_keys: Some(media_keys),
+            }),
+            created_at: datetime_utils::format_rfc3339(&created_at),
+            entities: Some(entities),
+            includes: (!media.is_empty()).then(|| Includes {
+                media: Some(media),
+                users: None,
+                tweets: None,
+                places: None,
+            }),
+            author_id: Some(author.id.clone()),
+            note_tweet: None,
+            // Archives don't record the conversation, threads are resolved by walking replies
+            conversation_id: None,
+            in_reply_to_user_id: archived.in_reply_to_user_id_str,
+            edit_history_tweet_ids: archived
+                .edit_info
+                .and_then(ArchivedEditInfo::edit_tweet_ids),
+            edit_controls: None,
+            lang: archived.lang,
+            possibly_sensitive: archived.possibly_sensitive,
+            // Archives record coordinates as strings and places without IDs
+            geo: None,
+        })
+    }
+
+    fn convert_media(&self, tweet_id: &str, item: ArchivedMedia) -> Media {
+        // Media keys in the API are prefixed with the media category
+        let prefix = match item.media_type.as_str() {
+            "video" => "7",
+            "animated_gif" => "16",
+            _ => "3",
+        };
+        let media_key = format!("{prefix}_{id}", id = item.id_str);
+
+        let Some(video_info) = item.video_info else {
+            return Media {
+                media_key,
+                type_field: item.media_type,
+                url: Some(item.media_url_https),
+                preview_image_url: None,
+                alt_text: None,
+                variants: None,
+            };
+        };
+
+        let mut variants: Vec<MediaVariant> = video_info
+            .variants
+            .into_iter()
+            .map(|v| MediaVariant {
+                bit_rate: v.bitrate.and_then(|b| b.parse().ok()),
+                content_type: v.content_type,
+                // Query strings would end up in the local file name
+                url: strip_query(&v.url).to_string(),
+            })
+            .collect();
+        // Archives only contain one rendition, prefer it so nothing is downloaded
+        if variants
+            .iter()
+            .any(|v| self.media_file_name(tweet_id, &v.url).is_some())
+        {
+            variants.retain(|v| self.media_file_name(tweet_id, &v.url).is_some());
+        }
+
+        Media {
+            media_key,
+            type_field: item.media_type,
+            url: None,
+            preview_image_url: Some(item.media_url_https),
+            alt_text: None,
+            variants: Some(variants),
+        }
+    }
+
+    /// Copies the archived media of a tweet to where posting looks for it
+    ///
+    /// Files go to `<data_dir>/tweets/<tweet_id>/<name>`, named after the last
+    /// segment of the media URL. Directory archives are hard-linked when possible.
+    /// Returns the number of files written.
+    pub fn copy_media(&mut self, tweet: &Tweet, data_dir: &Path) -> Result<usize> {
+        let Some(media_dir) = self.media_dir.clone() else {
+            return Ok(0);
+        };
+        let media = tweet
+            .includes
+            .as_ref()
+            .and_then(|i| i.media.as_ref())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let mut urls = Vec::new();
+        for item in media {
+            match (&item.url, &item.variants) {
+                (Some(url), _) => urls.push(url.clone()),
+                (None, Some(variants)) => urls.extend(variants.iter().map(|v| v.url.clone())),
+                (None, None) => {}
+            }
+        }
+
+        let mut copied = 0;
+        for url in urls {
+            let Some(name) = self.media_file_name(&tweet.id, &url) else {
+                debug!(
+                    "Media {url} of tweet {id} is not in the archive",
+                    id = tweet.id
+                );
+                continue;
+            };
+            let tweet_dir = data_dir.join("tweets").join(&tweet.id);
+            let dest = tweet_dir.join(url_basename(&url));
+            if dest.exists() {
+                continue;
+            }
+            fs::create_dir_all(&tweet_dir).with_context(|| {
+                format!(
+                    "Failed to create media directory {path}",
+                    path = tweet_dir.display()
+                )
+            })?;
+
+            let archive_path = format!("{media_dir}/{name}");
+            match &mut self.source {
+                ArchiveSource::Directory(dir) => {
+                    let src = dir.join(&archive_path);
+                    if fs::hard_link(&src, &dest).is_err() {
+                        fs::copy(&src, &dest).with_context(|| {
+                            format!(
+                                "Failed to copy {src} to {dest}",
+                                src = src.display(),
+                                dest = dest.display()
+                            )
+                        })?;
+                    }
+                }
+                ArchiveSource::Zip { archive, root } => {
+                    let mut entry = archive
+                        .by_name(&format!("{root}{archive_path}"))
+                        .with_context(|| format!("Failed to open {archive_path} in archive"))?;
+                    let mut file = File::create(&dest).with_context(|| {
+                        format!("Failed to create {dest}", dest = dest.display())
+                    })?;
+                    std::io::copy(&mut entry, &mut file)
+                        .with_context(|| format!("Failed to extract {archive_path}"))?;
+                }
+            }
+            copied += 1;
+        }
+
+        Ok(copied)
+    }
+}
+
+/// Parses a `window.YTD.<name>.part0 = [...]` data file
+pub fn parse_ytd_file<T: DeserializeOwned>(contents: &str) -> Result<Vec<T>> {
+    let (_, json) = contents
+        .split_once('=')
+        .context("Not a Twitter archive data file")?;
+    serde_json::from_str(json.trim().trim_end_matches(';')).context("Failed to parse archive JSON")
+}
+
+fn list_directory_files(root: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read {path}", path = dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn strip_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+fn url_basename(url: &str) -> &str {
+    strip_query(url).rsplit('/').next().unwrap_or(url)
+}
+
+/// ID of the tweet a status link points to, which is how archives record quotes
+fn quoted_status_id(expanded_url: &str) -> Option<String> {
+    let url = url::Url::parse(expanded_url).ok()?;
+    let host = url.host_str()?;
+    if !(host.ends_with("twitter.com") || host == "x.com" || host.ends_with(".x.com")) {
+        return None;
+    }
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    match segments.as_slice() {
+        // Media links (/status/<id>/photo/1) point at the tweet itself
+        [_, "status", id] if id.chars().all(|c| c.is_ascii_digit()) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Attaches full note texts to the truncated long tweets they belong to
+///
+/// `note-tweet.js` doesn't reference tweet IDs, so notes are matched by
+/// creation time and the text both start with.
+fn attach_note_tweets(tweets: &mut [Tweet], notes: impl IntoIterator<Item = ArchivedNote>) {
+    for note in notes {
+        let Ok(note_time) = datetime_utils::parse_rfc3339(&note.created_at) else {
+            debug!(
+                "Skipping note with invalid date {date}",
+                date = note.created_at
+            );
+            continue;
+        };
+
+        let tweet = tweets.iter_mut().find(|tweet| {
+            tweet.note_tweet.is_none()
+                && datetime_utils::parse_rfc3339(&tweet.created_at).is_ok_and(|time| {
+                    (time - note_time).num_seconds().abs() <= NOTE_TWEET_MAX_SKEW_SECS
+                })
+                && note_starts_like(&note.core.text, &tweet.text)
+        });
+
+        match tweet {
+            Some(tweet) => {
+                tweet.note_tweet = Some(NoteTweet {
+                    text: note.core.text,
+                })
+            }
+            None => debug!(
+                "No tweet found for note created at {date}",
+                date = note.created_at
+            ),
+        }
+    }
+}
+
+/// Whether a note starts with the visible part of its truncated tweet
+fn note_starts_like(note_text: &str, tweet_text: &str) -> bool {
+    let tweet_text = html_escape::decode_html_entities(tweet_text);
+    // The truncated text ends with an ellipsis and a t.co link to the full post
+    let visible = tweet_text
+        .split(['…', '\n'])
+        .next()
+        .unwrap_or_default()
+        .split("http")
+        .next()
+        .unwrap_or_default();
+    let prefix: String = visible.chars().take(40).collect();
+    note_text.starts_with(prefix.trim_end())
+}
+
+#[derive(Deserialize)]
+struct AccountEntry {
+    account: ArchivedAccount,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedAccount {
+    account_id: String,
+    username: String,
+    account_display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProfileEntry {
+    profile: ArchivedProfile,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedProfile {
+    description: Option<ArchivedBio>,
+    avatar_media_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedBio {
+    bio: Option<String>,
+    website: Option<String>,
+}
+
+/// Current exports wrap each tweet in `{"tweet": ...}`, older ones don't
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TweetEntry {
+    Wrapped { tweet: Box<ArchivedTweet> },
+    Bare(Box<ArchivedTweet>),
+}
+
+impl TweetEntry {
+    fn into_tweet(self) -> ArchivedTweet {
+        match self {
+            TweetEntry::Wrapped { tweet } | TweetEntry::Bare(tweet) => *tweet,
+        }
+    }
+}
+
+/// Tweet in the legacy (API v1.1) shape archives use
+#[derive(Deserialize)]
+struct ArchivedTweet {
+    id_str: String,
+    full_text: String,
+    created_at: String,
+    in_reply_to_status_id_str: Option<String>,
+    in_reply_to_user_id_str: Option<String>,
+    #[serde(default)]
+    entities: ArchivedEntities,
+    extended_entities: Option<ArchivedExtendedEntities>,
+    edit_info: Option<ArchivedEditInfo>,
+    lang: Option<String>,
+    possibly_sensitive: Option<bool>,
+}
+
+/// `initial` on the first version of a tweet, `edit` on later ones
+#[derive(Deserialize)]
+struct ArchivedEditInfo {
+    initial: Option<ArchivedEditControl>,
+    edit: Option<ArchivedEdit>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedEdit {
+    edit_control_initial: Option<ArchivedEditControl>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedEditControl {
+    #[serde(default)]
+    edit_tweet_ids: Vec<String>,
+}
+
+impl ArchivedEditInfo {
+    fn edit_tweet_ids(self) -> Option<Vec<String>> {
+        let control = self
+            .initial
+            .or_else(|| self.edit.and_then(|edit| edit.edit_control_initial))?;
+        (!control.edit_tweet_ids.is_empty()).then_some(control.edit_tweet_ids)
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct ArchivedEntities {
+    #[serde(default)]
+    hashtags: Vec<ArchivedHashtag>,
+    /// Cashtags, shaped like hashtags
+    #[serde(default)]
+    symbols: Vec<ArchivedHashtag>,
+    #[serde(default)]
+    user_mentions: Vec<ArchivedMention>,
+    #[serde(default)]
+    urls: Vec<ArchivedUrl>,
+    #[serde(default)]
+    media: Vec<ArchivedMedia>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedExtendedEntities {
+    #[serde(default)]
+    media: Vec<ArchivedMedia>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedHashtag {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ArchivedMention {
+    screen_name: String,
+}
+
+#[derive(Deserialize)]
+struct ArchivedUrl {
+    url: String,
+    expanded_url: Option<String>,
+    display_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedMedia {
+    id_str: String,
+    #[serde(rename = "type")]
+    media_type: String,
+    media_url_https: String,
+    url: String,
+    expanded_url: Option<String>,
+    display_url: Option<String>,
+    video_info: Option<ArchivedVideoInfo>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedVideoInfo {
+    #[serde(default)]
+    variants: Vec<ArchivedVariant>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedVariant {
+    bitrate: Option<String>,
+    content_type: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct NoteEntry {
+    #[serde(rename = "noteTweet")]
+    note_tweet: ArchivedNote,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedNote {
+    created_at: String,
+    core: ArchivedNoteCore,
+}
+
+#[derive(Deserialize)]
+struct ArchivedNoteCore {
+    text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT_JS: &str = r#"window.YTD.account.part0 = [
+  {
+    "account" : {
+      "createdVia" : "web",
+      "username" : "TestUser",
+      "accountId" : "123456",
+      "createdAt" : "2010-01-01T00:00:00.000Z",
+      "accountDisplayName" : "Test User"
+    }
+  }
+]"#;
+
+    const TWEETS_JS: &str = r#"window.YTD.tweets.part0 = [
+  {
+    "tweet" : {
+      "id_str" : "1001",
+      "full_text" : "Look at this https://t.co/pic",
+      "created_at" : "Sat Jan 20 15:30:00 +0000 2024",
+      "entities" : {
+        "hashtags" : [ ],
+        "user_mentions" : [ ],
+        "urls" : [ ]
+      },
+      "extended_entities" : {
+        "media" : [
+          {
+            "id_str" : "555",
+            "type" : "photo",
+            "media_url_https" : "https://pbs.twimg.com/media/AbCdEf.jpg",
+            "url" : "https://t.co/pic",
+            "expanded_url" : "https://twitter.com/TestUser/status/1001/photo/1",
+            "display_url" : "pic.twitter.com/pic"
+          }
+        ]
+      }
+    }
+  },
+  {
+    "tweet" : {
+      "id_str" : "1002",
+      "full_text" : "@friend agreed, see https://t.co/q",
+      "created_at" : "Sat Jan 20 16:00:00 +0000 2024",
+      "in_reply_to_status_id_str" : "999",
+      "lang" : "en",
+      "possibly_sensitive" : false,
+      "entities" : {
+        "hashtags" : [ { "text" : "rust" } ],
+        "symbols" : [ { "text" : "BTC" } ],
+        "user_mentions" : [ { "screen_name" : "friend", "id_str" : "42" } ],
+        "urls" : [
+          {
+            "url" : "https://t.co/q",
+            "expanded_url" : "https://twitter.com/friend/status/998",
+            "display_url" : "twitter.com/friend/status/998"
+          }
+        ]
+      }
+    }
+  },
+  {
+    "tweet" : {
+      "id_str" : "1003",
+      "full_text" : "This is a very long post that goes on and on… https://t.co/more",
+      "created_at" : "Sat Jan 20 17:00:00 +0000 2024",
+      "edit_info" : {
+        "edit" : {
+          "initialTweetId" : "1000",
+          "editControlInitial" : {
+            "editTweetIds" : [ "1000", "1003" ],
+            "editableUntil" : "2024-01-20T17:30:00.000Z",
+            "editsRemaining" : "4",
+            "isEditEligible" : true
+          }
+        }
+      },
+      "entities" : { }
+    }
+  }
+]"#;
+
+    const NOTE_TWEET_JS: &str = r#"window.YTD.note_tweet.part0 = [
+  {
+    "noteTweet" : {
+      "noteTweetId" : "77",
+      "createdAt" : "2024-01-20T17:00:01.000Z",
+      "core" : {
+        "text" : "This is a very long post that goes on and on, well past the limit."
+      }
+    }
+  }
+]"#;
+
+    fn write_archive(dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir.join("data/tweets_media"))?;
+        fs::write(dir.join("data/account.js"), ACCOUNT_JS)?;
+        fs::write(dir.join("data/tweets.js"), TWEETS_JS)?;
+        fs::write(dir.join("data/note-tweet.js"), NOTE_TWEET_JS)?;
+        fs::write(dir.join("data/tweets_media/1001-AbCdEf.jpg"), b"jpeg")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ytd_file() -> Result<()> {
+        let entries: Vec<AccountEntry> = parse_ytd_file(ACCOUNT_JS)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].account.username, "TestUser");
+        assert!(parse_ytd_file::<AccountEntry>("[]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_archive_directory() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        write_archive(temp_dir.path())?;
+
+        let mut archive = TwitterArchive::open(temp_dir.path())?;
+        let user = archive.account()?;
+        assert_eq!(user.id, "123456");
+        assert_eq!(user.username, "TestUser");
+        assert_eq!(user.name.as_deref(), Some("Test User"));
+
+        let tweets = archive.tweets(&user)?;
+        assert_eq!(tweets.len(), 3);
+
+        let photo = &tweets[0];
+        assert_eq!(photo.id, "1001");
+        assert_eq!(photo.created_at, "2024-01-20T15:30:00.000Z");
+        assert_eq!(photo.author_id.as_deref(), Some("123456"));
+        let media = photo
+            .includes
+            .as_ref()
+            .and_then(|i| i.media.as_ref())
+            .context("photo tweet has no media")?;
+        assert_eq!(media[0].media_key, "3_555");
+        assert_eq!(
+            media[0].url.as_deref(),
+            Some("https://pbs.twimg.com/media/AbCdEf.jpg")
+        );
+
+        let reply = &tweets[1];
+        let references = reply
+            .referenced_tweets
+            .as_ref()
+            .context("reply has no references")?;
+        assert_eq!(references[0].type_field, "replied_to");
+        assert_eq!(references[0].id, "999");
+        assert_eq!(references[1].type_field, "quoted");
+        assert_eq!(references[1].id, "998");
+        assert_eq!(reply.lang.as_deref(), Some("en"));
+        assert_eq!(reply.possibly_sensitive, Some(false));
+        let cashtags = reply
+            .entities
+            .as_ref()
+            .and_then(|e| e.cashtags.as_ref())
+            .context("reply has no cashtags")?;
+        assert_eq!(cashtags[0].tag, "BTC");
+
+        let long = &tweets[2];
+        assert_eq!(
+            long.note_tweet.as_ref().map(|n| n.text.as_str()),
+            Some("This is a very long post that goes on and on, well past the limit.")
+        );
+        assert_eq!(
+            long.edit_history_tweet_ids,
+            Some(vec!["1000".to_string(), "1003".to_string()])
+        );
+        assert_eq!(photo.edit_history_tweet_ids, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_archived_media() -> Result<()> {
+        let archive_dir = tempfile::tempdir()?;
+        write_archive(archive_dir.path())?;
+        let data_dir = tempfile::tempdir()?;
+
+        let mut archive = TwitterArchive::open(archive_dir.path())?;
+        let user = archive.account()?;
+        let tweets = archive.tweets(&user)?;
+
+        assert_eq!(archive.copy_media(&tweets[0], data_dir.path())?, 1);
+        let copied = data_dir.path().join("tweets/1001/AbCdEf.jpg");
+        assert_eq!(fs::read(copied)?, b"jpeg");
+
+        // Already present, nothing to do
+        assert_eq!(archive.copy_media(&tweets[0], data_dir.path())?, 0);
+        assert_eq!(archive.copy_media(&tweets[1], data_dir.path())?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_rejects_non_archive() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        assert!(TwitterArchive::open(temp_dir.path()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,560 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::storage;
+use crate::twitter::{TwitterClient, TwitterError};
+
+/// The timeline endpoint only reaches back this many tweets
+pub const TIMELINE_HISTORY_LIMIT: u32 = 3200;
+
+/// Directory inside the data directory holding one checkpoint per user
+const BACKFILL_DIR: &str = "backfill";
+
+/// Tweets requested per page (the API maximum)
+const PAGE_SIZE: u32 = 100;
+
+/// Progress of walking a user's timeline backward, persisted after every page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackfillCheckpoint {
+    pub user_id: String,
+    pub username: String,
+    /// Token of the next older page, as returned by the last fetched page
+    pub pagination_token: Option<String>,
+    /// Oldest tweet ID fetched so far, resumed from with `until_id` if the token expired
+    pub oldest_id: Option<String>,
+    /// Tweets returned by the API so far
+    pub fetched: u32,
+    /// Tweets that were not cached yet and got saved
+    pub saved: u32,
+    /// Pages fetched so far
+    pub pages: u32,
+    /// No older tweets left, or the requested number was reached
+    pub completed: bool,
+    /// Requested number of tweets the backfill stopped at, `None` if it ran out of tweets
+    #[serde(default)]
+    pub completed_limit: Option<u32>,
+    /// Last update (UNIX timestamp)
+    pub updated_at: i64,
+}
+
+impl BackfillCheckpoint {
+    pub fn new(user_id: &str, username: &str) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            pagination_token: None,
+            oldest_id: None,
+            fetched: 0,
+            saved: 0,
+            pages: 0,
+            completed: false,
+            completed_limit: None,
+            updated_at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Reopens a backfill that stopped at a smaller number of tweets than `max_tweets`
+    pub fn extend_limit(&mut self, max_tweets: u32) {
+        if let Some(limit) = self.completed_limit
+            && self.completed
+            && limit < max_tweets
+        {
+            info!(
+                "Extending backfill of @{username} from {limit} to {max_tweets} tweet(s)",
+                username = self.username
+            );
+            self.completed = false;
+            self.completed_limit = None;
+        }
+    }
+}
+
+pub fn checkpoint_path(data_dir: &Path, username: &str) -> PathBuf {
+    data_dir.join(BACKFILL_DIR).join(format!(
+        "{username}.json",
+        username = username.to_lowercase()
+    ))
+}
+
+/// Loads the checkpoint of a user, if a backfill was started before
+pub fn load_checkpoint(data_dir: &Path, username: &str) -> Result<Option<BackfillCheckpoint>> {
+    let path = checkpoint_path(data_dir, username);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read backfill checkpoint {path}",
+            path = path.display()
+        )
+    })?;
+    let checkpoint = serde_json::from_str(&json).with_context(|| {
+        format!(
+            "Failed to parse backfill checkpoint {path}",
+            path = path.display()
+        )
+    })?;
+    Ok(Some(checkpoint))
+}
+
+/// Writes the checkpoint atomically, so a crash never leaves a truncated file
+pub fn save_checkpoint(data_dir: &Path, checkpoint: &BackfillCheckpoint) -> Result<()> {
+    let path = checkpoint_path(data_dir, &checkpoint.username);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| {
+            format!(
+                "Failed to create backfill directory {path}",
+                path = dir.display()
+            )
+        })?;
+    }
+
+    let json = serde_json::to_string_pretty(checkpoint)
+        .context("Failed to serialize backfill checkpoint")?;
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, json).with_context(|| {
+        format!(
+            "Failed to write backfill checkpoint {path}",
+            path = temp_path.display()
+        )
+    })?;
+    std::fs::rename(&temp_path, &path).with_context(|| {
+        format!(
+            "Failed to move backfill checkpoint into place at {path}",
+            path = path.display()
+        )
+    })
+}
+
+pub fn remove_checkpoint(data_dir: &Path, username: &str) -> Result<()> {
+    let path = checkpoint_path(data_dir, username);
+    if path.exists() {
+        std::fs::remove_file(&path).with_context(|| {
+            format!(
+                "Failed to remove backfill checkpoint {path}",
+                path = path.display()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Loads the checkpoint of a user, or starts a new one (looking up the user ID)
+pub async fn load_or_start(
+    client: &TwitterClient,
+    data_dir: &Path,
+    username: &str,
+) -> Result<BackfillCheckpoint> {
+    if let Some(checkpoint) = load_checkpoint(data_dir, username)? {
+        return Ok(checkpoint);
+    }
+
+    let user_id = client
+        .get_user_id(username)
+        .await
+        .with_context(|| format!("Failed to look up user ID of @{username}"))?;
+    let checkpoint = BackfillCheckpoint::new(&user_id, username);
+    save_checkpoint(data_dir, &checkpoint)?;
+    Ok(checkpoint)
+}
+
+/// Whether Twitter rejected the request's `pagination_token`, as it does once the token expired
+///
+/// Twitter answers with a 400 `invalid-request` problem naming the parameter:
+/// `{"errors":[{"parameters":{"pagination_token":["..."]},...}],"type":"https://api.twitter.com/2/problems/invalid-request",...}`
+fn is_invalid_pagination_token(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<TwitterError>(),
+        Some(TwitterError::ApiError { status: 400, message })
+            if message.contains("problems/invalid-request")
+                && message.contains("\"pagination_token\"")
+    )
+}
+
+/// Snowflake IDs grow over time, so the shorter or lexically smaller one is older
+fn older_id<'a>(a: &'a str, b: &'a str) -> &'a str {
+    if (a.len(), a) <= (b.len(), b) { a } else { b }
+}
+
+/// Fetches the next older page and saves its tweets, updating the checkpoint
+///
+/// Returns the number of newly saved tweets. The checkpoint is written after
+/// the tweets, so a crash repeats at most one page and never skips one.
+pub async fn backfill_page(
+    client: &TwitterClient,
+    data_dir: &Path,
+    checkpoint: &mut BackfillCheckpoint,
+    max_tweets: u32,
+) -> Result<u32> {
+    checkpoint.extend_limit(max_tweets);
+    if checkpoint.completed {
+        return Ok(0);
+    }
+
+    let remaining = max_tweets.saturating_sub(checkpoint.fetched);
+    let page_size = remaining.min(PAGE_SIZE);
+    let result = match checkpoint.pagination_token.as_deref() {
+        Some(token) => {
+            let result = client
+                .get_user_timeline_page(&checkpoint.user_id, page_size, Some(token), None)
+                .await;
+            match result {
+                // Pagination tokens expire, continue below the oldest tweet instead
+                Err(e) if is_invalid_pagination_token(&e) => {
+                    warn!(
+                        "Pagination token for @{username} was rejected, resuming below tweet {oldest:?}",
+                        username = checkpoint.username,
+                        oldest = checkpoint.oldest_id
+                    );
+                    client
+                        .get_user_timeline_page(
+                            &checkpoint.user_id,
+                            page_size,
+                            None,
+                            checkpoint.oldest_id.as_deref(),
+                        )
+                        .await
+                }
+                result => result,
+            }
+        }
+        None => {
+            client
+                .get_user_timeline_page(
+                    &checkpoint.user_id,
+                    page_size,
+                    None,
+                    checkpoint.oldest_id.as_deref(),
+                )
+                .await
+        }
+    };
+    let page = result.with_context(|| {
+        format!(
+            "Failed to fetch timeline page of @{username}",
+            username = checkpoint.username
+        )
+    })?;
+
+    let tweets = page.data.unwrap_or_default();
+    let mut saved = 0;
+    for tweet in &tweets {
+        if storage::find_existing_tweet_json(&tweet.id, data_dir).is_some() {
+            continue;
+        }
+        storage::save_tweet(tweet, data_dir)?;
+        saved += 1;
+    }
+
+    for tweet in &tweets {
+        checkpoint.oldest_id = Some(match &checkpoint.oldest_id {
+            Some(oldest) => older_id(oldest, &tweet.id).to_string(),
+            None => tweet.id.clone(),
+        });
+    }
+    checkpoint.fetched += tweets.len() as u32;
+    checkpoint.saved += saved;
+    checkpoint.pages += 1;
+    checkpoint.pagination_token = page.meta.and_then(|meta| meta.next_token);
+    let exhausted = tweets.is_empty() || checkpoint.pagination_token.is_none();
+    checkpoint.completed = exhausted || checkpoint.fetched >= max_tweets;
+    checkpoint.completed_limit = (checkpoint.completed && !exhausted).then_some(max_tweets);
+    checkpoint.updated_at = chrono::Utc::now().timestamp();
+    save_checkpoint(data_dir, checkpoint)?;
+
+    debug!(
+        "Backfill page {pages} of @{username}: {count} tweet(s), {saved} new",
+        pages = checkpoint.pages,
+        username = checkpoint.username,
+        count = tweets.len()
+    );
+    if checkpoint.completed {
+        info!(
+            "Backfill of @{username} complete: {fetched} tweet(s) fetched, {total} saved",
+            username = checkpoint.username,
+            fetched = checkpoint.fetched,
+            total = checkpoint.saved
+        );
+    }
+
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_roundtrip() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        assert!(load_checkpoint(temp_dir.path(), "TestUser")?.is_none());
+
+        let mut checkpoint = BackfillCheckpoint::new("123456", "TestUser");
+        checkpoint.pagination_token = Some("next-page".to_string());
+        checkpoint.oldest_id = Some("1700000000000000000".to_string());
+        checkpoint.fetched = 100;
+        save_checkpoint(temp_dir.path(), &checkpoint)?;
+
+        // Usernames are case-insensitive
+        let loaded = load_checkpoint(temp_dir.path(), "testuser")?.context("missing checkpoint")?;
+        assert_eq!(loaded, checkpoint);
+
+        remove_checkpoint(temp_dir.path(), "TestUser")?;
+        assert!(load_checkpoint(temp_dir.path(), "TestUser")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_older_id() {
+        assert_eq!(older_id("999", "1000"), "999");
+        assert_eq!(
+            older_id("1959000000000000002", "1959000000000000001"),
+            "1959000000000000001"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backfill_resumes_from_checkpoint() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let tweet = |id: &str| {
+            serde_json::json!({
+                "id": id,
+                "text": format!("tweet {id}"),
+                "created_at": "2024-01-20T15:30:00.000Z",
+                "author_id": "42"
+            })
+        };
+        let first_page = server
+            .mock("GET", "/2/users/42/tweets")
+            // The first request carries neither a token nor an until_id
+            .match_query(mockito::Matcher::Regex(r"user\.fields=[^&]*$".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "data": [tweet("1003"), tweet("1002")],
+                    "includes": { "users": [{ "id": "42", "username": "testuser" }] },
+                    "meta": { "result_count": 2, "next_token": "page2" }
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("GET", "/2/users/42/tweets")
+            .match_query(mockito::Matcher::Regex(
+                "pagination_token=page2".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "data": [tweet("1001")],
+                    "includes": { "users": [{ "id": "42", "username": "testuser" }] },
+                    "meta": { "result_count": 1 }
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let client = TwitterClient::new(temp_dir.path(), "test-token")?
+            .with_api_base(&format!("{url}/2", url = server.url()));
+
+        let mut checkpoint = BackfillCheckpoint::new("42", "testuser");
+        assert_eq!(
+            backfill_page(&client, temp_dir.path(), &mut checkpoint, 3200).await?,
+            2
+        );
+        assert!(!checkpoint.completed);
+
+        // A restarted run continues with the saved token
+        let mut resumed =
+            load_checkpoint(temp_dir.path(), "testuser")?.context("missing checkpoint")?;
+        assert_eq!(resumed.pagination_token.as_deref(), Some("page2"));
+        assert_eq!(
+            backfill_page(&client, temp_dir.path(), &mut resumed, 3200).await?,
+            1
+        );
+        assert!(resumed.completed);
+        assert_eq!(resumed.fetched, 3);
+        assert_eq!(resumed.oldest_id.as_deref(), Some("1001"));
+        assert!(storage::find_existing_tweet_json("1001", temp_dir.path()).is_some());
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backfill_resumes_with_larger_limit() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let tweet = |id: &str| {
+            serde_json::json!({
+                "id": id,
+                "text": format!("tweet {id}"),
+                "created_at": "2024-01-20T15:30:00.000Z",
+                "author_id": "42"
+            })
+        };
+        let first_page = server
+            .mock("GET", "/2/users/42/tweets")
+            .match_query(mockito::Matcher::Regex(r"user\.fields=[^&]*$".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "data": [tweet("1003"), tweet("1002")],
+                    "includes": { "users": [{ "id": "42", "username": "testuser" }] },
+                    "meta": { "result_count": 2, "next_token": "page2" }
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("GET", "/2/users/42/tweets")
+            .match_query(mockito::Matcher::Regex(
+                "pagination_token=page2".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "data": [tweet("1001")],
+                    "includes": { "users": [{ "id": "42", "username": "testuser" }] },
+                    "meta": { "result_count": 1 }
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let client = TwitterClient::new(temp_dir.path(), "test-token")?
+            .with_api_base(&format!("{url}/2", url = server.url()));
+
+        let mut checkpoint = BackfillCheckpoint::new("42", "testuser");
+        backfill_page(&client, temp_dir.path(), &mut checkpoint, 2).await?;
+        assert!(checkpoint.completed);
+        assert_eq!(checkpoint.completed_limit, Some(2));
+
+        // The same limit has nothing left to fetch
+        let mut resumed =
+            load_checkpoint(temp_dir.path(), "testuser")?.context("missing checkpoint")?;
+        assert_eq!(
+            backfill_page(&client, temp_dir.path(), &mut resumed, 2).await?,
+            0
+        );
+
+        // A larger one continues with the saved token
+        assert_eq!(
+            backfill_page(&client, temp_dir.path(), &mut resumed, 3200).await?,
+            1
+        );
+        assert!(resumed.completed);
+        assert_eq!(resumed.completed_limit, None);
+        assert_eq!(resumed.fetched, 3);
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        Ok(())
+    }
+
+    fn bad_request_body(parameter: &str) -> String {
+        serde_json::json!({
+            "errors": [{
+                "parameters": { parameter: ["stale"] },
+                "message": format!("The `{parameter}` query parameter value [stale] is not valid")
+            }],
+            "title": "Invalid Request",
+            "detail": "One or more parameters to your request was invalid.",
+            "type": "https://api.twitter.com/2/problems/invalid-request"
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_backfill_resumes_below_oldest_on_expired_token() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let rejected = server
+            .mock("GET", "/2/users/42/tweets")
+            .match_query(mockito::Matcher::Regex(
+                "pagination_token=stale".to_string(),
+            ))
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(bad_request_body("pagination_token"))
+            .create_async()
+            .await;
+        let below_oldest = server
+            .mock("GET", "/2/users/42/tweets")
+            .match_query(mockito::Matcher::Regex("until_id=1002$".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "data": [{
+                        "id": "1001",
+                        "text": "tweet 1001",
+                        "created_at": "2024-01-20T15:30:00.000Z",
+                        "author_id": "42"
+                    }],
+                    "includes": { "users": [{ "id": "42", "username": "testuser" }] },
+                    "meta": { "result_count": 1 }
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let client = TwitterClient::new(temp_dir.path(), "test-token")?
+            .with_api_base(&format!("{url}/2", url = server.url()));
+
+        let mut checkpoint = BackfillCheckpoint::new("42", "testuser");
+        checkpoint.pagination_token = Some("stale".to_string());
+        checkpoint.oldest_id = Some("1002".to_string());
+        assert_eq!(
+            backfill_page(&client, temp_dir.path(), &mut checkpoint, 3200).await?,
+            1
+        );
+        assert_eq!(checkpoint.oldest_id.as_deref(), Some("1001"));
+
+        rejected.assert_async().await;
+        below_oldest.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backfill_propagates_other_bad_requests() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let rejected = server
+            .mock("GET", "/2/users/42/tweets")
+            .match_query(mockito::Matcher::Any)
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(bad_request_body("max_results"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let client = TwitterClient::new(temp_dir.path(), "test-token")?
+            .with_api_base(&format!("{url}/2", url = server.url()));
+
+        let mut checkpoint = BackfillCheckpoint::new("42", "testuser");
+        checkpoint.pagination_token = Some("page2".to_string());
+        checkpoint.oldest_id = Some("1002".to_string());
+        assert!(
+            backfill_page(&client, temp_dir.path(), &mut checkpoint, 3200)
+                .await
+                .is_err()
+        );
+        // The token is kept for the next attempt
+        assert_eq!(checkpoint.pagination_token.as_deref(), Some("page2"));
+
+        rejected.assert_async().await;
+        Ok(())
+    }
+}
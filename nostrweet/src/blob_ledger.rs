@@ -0,0 +1,301 @@
+use anyhow::{Context, Result, anyhow};
+use nostr_sdk::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::blossom::{BlobDescriptor, BlossomClient};
+use crate::signer::UserSigner;
+
+/// File inside the data directory recording the blobs uploaded to Blossom servers
+const LEDGER_FILE: &str = "blossom_ledger.json";
+
+/// Serializes read-modify-write cycles of the ledger within the process
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Blobs known to be on Blossom servers, so media is uploaded once per server
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlobLedger {
+    /// Uploaded blobs by SHA-256
+    #[serde(default)]
+    pub blobs: BTreeMap<String, LedgerBlob>,
+    /// Hashes of local media files by file name, so unchanged files aren't read again
+    #[serde(default)]
+    pub files: BTreeMap<String, LedgerFile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LedgerBlob {
+    pub size: u64,
+    pub mime_type: Option<String>,
+    /// Pubkey (hex) that uploaded the blob, whose key signs further mirrors
+    #[serde(default)]
+    pub uploader: Option<String>,
+    /// URL of the blob on each server holding it, by server URL
+    pub servers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerFile {
+    pub sha256: String,
+    /// Size when hashed, a changed size means the file must be hashed again
+    pub size: u64,
+}
+
+pub fn ledger_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(LEDGER_FILE)
+}
+
+impl BlobLedger {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = ledger_path(data_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read blob ledger {path}", path = path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse blob ledger {path}", path = path.display()))
+    }
+
+    /// Writes the ledger atomically, so a crash never leaves a truncated file
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(data_dir).with_context(|| {
+            format!(
+                "Failed to create data directory {path}",
+                path = data_dir.display()
+            )
+        })?;
+        let path = ledger_path(data_dir);
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize blob ledger")?;
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, json).with_context(|| {
+            format!(
+                "Failed to write blob ledger {path}",
+                path = temp_path.display()
+            )
+        })?;
+        std::fs::rename(&temp_path, &path).with_context(|| {
+            format!(
+                "Failed to move blob ledger into place at {path}",
+                path = path.display()
+            )
+        })
+    }
+
+    /// Loads the ledger, applies `change` and saves it, holding the process-wide lock
+    pub fn update<F>(data_dir: &Path, change: F) -> Result<()>
+    where
+        F: FnOnce(&mut BlobLedger),
+    {
+        let _guard = LEDGER_LOCK
+            .lock()
+            .map_err(|e| anyhow!("Blob ledger lock is poisoned: {e}"))?;
+        let mut ledger = Self::load(data_dir)?;
+        change(&mut ledger);
+        ledger.save(data_dir)
+    }
+
+    /// Hash recorded for a media file, if it still has the size it was hashed with
+    pub fn file_sha256(&self, file_name: &str, size: u64) -> Option<&str> {
+        self.files
+            .get(file_name)
+            .filter(|file| file.size == size)
+            .map(|file| file.sha256.as_str())
+    }
+
+    /// URL of a blob on a server, if it was uploaded there
+    pub fn url_on(&self, sha256: &str, server: &str) -> Option<&str> {
+        self.blobs
+            .get(sha256)?
+            .servers
+            .get(server)
+            .map(String::as_str)
+    }
+
+    pub fn record_file(&mut self, file_name: &str, sha256: &str, size: u64) {
+        self.files.insert(
+            file_name.to_string(),
+            LedgerFile {
+                sha256: sha256.to_string(),
+                size,
+            },
+        );
+    }
+
+    /// Records a blob a server holds
+    pub fn record_blob(
+        &mut self,
+        server: &str,
+        descriptor: &BlobDescriptor,
+        uploader: Option<&PublicKey>,
+    ) {
+        let blob = self.blobs.entry(descriptor.sha256.clone()).or_default();
+        blob.size = descriptor.size;
+        if blob.uploader.is_none() {
+            blob.uploader = uploader.map(PublicKey::to_hex);
+        }
+        if descriptor.mime_type.is_some() {
+            blob.mime_type = descriptor.mime_type.clone();
+        }
+        blob.servers
+            .insert(server.to_string(), descriptor.url.clone());
+    }
+
+    /// Forgets a blob on a server that no longer has it
+    pub fn forget_blob(&mut self, sha256: &str, server: &str) {
+        if let Some(blob) = self.blobs.get_mut(sha256) {
+            blob.servers.remove(server);
+            if blob.servers.is_empty() {
+                self.blobs.remove(sha256);
+            }
+        }
+    }
+}
+
+/// Checks a server for a blob with `HEAD /<sha256>`, keeping the ledger in sync
+///
+/// Returns the blob's URL on the server if it holds the blob.
+pub async fn find_on_server(
+    client: &BlossomClient,
+    sha256: &str,
+    size: u64,
+    mime_type: Option<&str>,
+    uploader: Option<&PublicKey>,
+    data_dir: &Path,
+) -> Result<Option<String>> {
+    let server = client.server();
+    let recorded = BlobLedger::load(data_dir)?
+        .url_on(sha256, server)
+        .map(str::to_string);
+
+    if !client.has_blob(sha256).await? {
+        if recorded.is_some() {
+            warn!("{server} no longer has blob {sha256}");
+            BlobLedger::update(data_dir, |ledger| ledger.forget_blob(sha256, server))?;
+        }
+        return Ok(None);
+    }
+    if let Some(url) = recorded {
+        return Ok(Some(url));
+    }
+
+    // Held already, from an earlier run or another uploader
+    let descriptor = BlobDescriptor {
+        url: format!("{server}/{sha256}"),
+        sha256: sha256.to_string(),
+        size,
+        mime_type: mime_type.map(str::to_string),
+        uploaded: None,
+        nip94: None,
+    };
+    BlobLedger::update(data_dir, |ledger| {
+        ledger.record_blob(server, &descriptor, uploader)
+    })?;
+    Ok(Some(descriptor.url))
+}
+
+/// Copies a blob to a server from `source_url` unless the server holds it already
+pub async fn mirror_to_server(
+    client: &BlossomClient,
+    source_url: &str,
+    sha256: &str,
+    size: u64,
+    mime_type: Option<&str>,
+    signer: &UserSigner,
+    data_dir: &Path,
+) -> Result<String> {
+    let uploader = signer.public_key();
+    if let Some(url) =
+        find_on_server(client, sha256, size, mime_type, Some(&uploader), data_dir).await?
+    {
+        return Ok(url);
+    }
+
+    let mut descriptor = client.mirror(source_url, sha256, signer).await?;
+    if descriptor.mime_type.is_none() {
+        descriptor.mime_type = mime_type.map(str::to_string);
+    }
+    BlobLedger::update(data_dir, |ledger| {
+        ledger.record_blob(client.server(), &descriptor, Some(&uploader))
+    })?;
+    Ok(descriptor.url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_uploader() -> PublicKey {
+        nostr_sdk::Keys::generate().public_key()
+    }
+
+    fn descriptor(server: &str, sha256: &str) -> BlobDescriptor {
+        BlobDescriptor {
+            url: format!("{server}/{sha256}.jpg"),
+            sha256: sha256.to_string(),
+            size: 42,
+            mime_type: Some("image/jpeg".to_string()),
+            uploaded: Some(1_700_000_000),
+            nip94: None,
+        }
+    }
+
+    #[test]
+    fn test_ledger_roundtrip() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let uploader = new_uploader();
+        assert_eq!(BlobLedger::load(temp_dir.path())?, BlobLedger::default());
+
+        BlobLedger::update(temp_dir.path(), |ledger| {
+            ledger.record_file("photo.jpg", "abc", 42);
+            ledger.record_blob(
+                "https://one.example",
+                &descriptor("https://one.example", "abc"),
+                Some(&uploader),
+            );
+            ledger.record_blob(
+                "https://two.example",
+                &descriptor("https://two.example", "abc"),
+                Some(&new_uploader()),
+            );
+        })?;
+
+        let ledger = BlobLedger::load(temp_dir.path())?;
+        assert_eq!(ledger.file_sha256("photo.jpg", 42), Some("abc"));
+        assert_eq!(ledger.file_sha256("photo.jpg", 43), None);
+        assert_eq!(
+            ledger.url_on("abc", "https://two.example"),
+            Some("https://two.example/abc.jpg")
+        );
+        // The first uploader signs later mirrors
+        assert_eq!(ledger.blobs["abc"].uploader, Some(uploader.to_hex()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_forget_blob() {
+        let mut ledger = BlobLedger::default();
+        let uploader = new_uploader();
+        ledger.record_blob(
+            "https://one.example",
+            &descriptor("https://one.example", "abc"),
+            Some(&uploader),
+        );
+        ledger.record_blob(
+            "https://two.example",
+            &descriptor("https://two.example", "abc"),
+            Some(&uploader),
+        );
+
+        ledger.forget_blob("abc", "https://one.example");
+        assert_eq!(ledger.url_on("abc", "https://one.example"), None);
+        assert!(ledger.url_on("abc", "https://two.example").is_some());
+
+        ledger.forget_blob("abc", "https://two.example");
+        assert!(ledger.blobs.is_empty());
+    }
+}
@@ -0,0 +1,662 @@
+use anyhow::{Context, Result, bail, ensure};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use nostr_sdk::{EventBuilder, Kind, PublicKey, Tag, Timestamp};
+use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::time::sleep;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, warn};
+
+use crate::nip96::Nip94Event;
+use crate::signer::UserSigner;
+
+/// Kind of the authorization events Blossom servers accept (BUD-01)
+pub const AUTH_KIND: u16 = 24242;
+
+/// Seconds an authorization event stays valid
+const AUTH_EXPIRATION_SECS: u64 = 300;
+
+/// Attempts of a request answered with 429 Too Many Requests
+const MAX_ATTEMPTS: usize = 3;
+
+/// Wait between attempts when the server sends no Retry-After
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Bytes read at a time when hashing files
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Blossom errors callers may want to act on
+#[derive(Debug, Error)]
+pub enum BlossomError {
+    /// The server asks for a payment before accepting the request (BUD-07)
+    #[error(
+        "Blossom server {server} requires payment for {what}: {}",
+        payment_options(.lightning, .cashu)
+    )]
+    PaymentRequired {
+        server: String,
+        what: String,
+        /// BOLT-11 invoice from the `X-Lightning` header
+        lightning: Option<String>,
+        /// Cashu payment request from the `X-Cashu` header
+        cashu: Option<String>,
+    },
+}
+
+fn payment_options(lightning: &Option<String>, cashu: &Option<String>) -> String {
+    match (lightning, cashu) {
+        (Some(invoice), Some(request)) => {
+            format!("pay Lightning invoice {invoice} or Cashu request {request}")
+        }
+        (Some(invoice), None) => format!("pay Lightning invoice {invoice}"),
+        (None, Some(request)) => format!("pay Cashu request {request}"),
+        (None, None) => "no payment method offered".to_string(),
+    }
+}
+
+/// Blob descriptor returned by Blossom servers (BUD-02)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobDescriptor {
+    pub url: String,
+    /// SHA-256 of the blob (hex)
+    pub sha256: String,
+    pub size: u64,
+    /// MIME type, if the server knows it
+    #[serde(rename = "type")]
+    pub mime_type: Option<String>,
+    /// Upload time (UNIX timestamp)
+    pub uploaded: Option<u64>,
+    /// NIP-94 tags describing the blob, returned by some servers (BUD-08)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nip94: Option<Vec<Vec<String>>>,
+}
+
+impl BlobDescriptor {
+    pub fn nip94_event(&self) -> Option<Nip94Event> {
+        self.nip94.as_ref().map(|tags| Nip94Event {
+            tags: tags.clone(),
+            content: String::new(),
+        })
+    }
+}
+
+/// Endpoints an authorization event can be scoped to, the `t` tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlossomAction {
+    Get,
+    Upload,
+    List,
+    Delete,
+}
+
+impl BlossomAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlossomAction::Get => "get",
+            BlossomAction::Upload => "upload",
+            BlossomAction::List => "list",
+            BlossomAction::Delete => "delete",
+        }
+    }
+}
+
+/// Hex SHA-256 of a blob, the name it is stored under
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Builds a kind 24242 authorization event for one action
+///
+/// Upload and delete authorizations are bound to the blob with an `x` tag, so
+/// a leaked header can't be replayed for another blob.
+pub fn auth_builder(
+    action: BlossomAction,
+    description: &str,
+    sha256: Option<&str>,
+) -> Result<EventBuilder> {
+    let expiration = Timestamp::now().as_u64() + AUTH_EXPIRATION_SECS;
+    let mut tags = vec![
+        Tag::parse(vec!["t", action.as_str()])?,
+        Tag::parse(vec!["expiration", expiration.to_string().as_str()])?,
+    ];
+    if let Some(sha256) = sha256 {
+        tags.push(Tag::parse(vec!["x", sha256])?);
+    }
+    Ok(EventBuilder::new(Kind::Custom(AUTH_KIND), description).tags(tags))
+}
+
+/// Hex SHA-256 of a file, read in chunks so large videos are never held in memory
+pub async fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {path}", path = path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; HASH_CHUNK_SIZE];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read {path}", path = path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Signs an authorization event and encodes it as an `Authorization` header value
+pub async fn auth_header(
+    signer: &UserSigner,
+    action: BlossomAction,
+    description: &str,
+    sha256: Option<&str>,
+) -> Result<String> {
+    let event = signer
+        .sign_event(auth_builder(action, description, sha256)?)
+        .await
+        .context("Failed to sign Blossom authorization")?;
+    let json =
+        serde_json::to_string(&event).context("Failed to serialize Blossom authorization")?;
+    Ok(format!("Nostr {token}", token = STANDARD.encode(json)))
+}
+
+/// Authorization for uploading one blob
+async fn upload_auth(signer: &UserSigner, sha256: &str) -> Result<String> {
+    auth_header(
+        signer,
+        BlossomAction::Upload,
+        &format!("Upload {sha256}"),
+        Some(sha256),
+    )
+    .await
+}
+
+/// Client for one Blossom server
+pub struct BlossomClient {
+    http: reqwest::Client,
+    /// Server URL, without trailing slash
+    server: String,
+}
+
+impl BlossomClient {
+    pub fn new(server: &str) -> Result<Self> {
+        let server = server.trim_end_matches('/');
+        ensure!(
+            server.starts_with("http://") || server.starts_with("https://"),
+            "Blossom server URL must start with http:// or https://, got {server}"
+        );
+        Ok(Self {
+            http: reqwest::Client::new(),
+            server: server.to_string(),
+        })
+    }
+
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// Sends a request, retrying while the server answers 429 Too Many Requests
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Response> {
+        let request = &request;
+        self.send_with(move || async move {
+            request
+                .try_clone()
+                .context("Blossom request can't be retried")
+        })
+        .await
+    }
+
+    /// Like `send`, building the request anew for every attempt
+    ///
+    /// Requests with a streamed body can't be cloned, `build` reopens the stream.
+    async fn send_with<F, Fut>(&self, mut build: F) -> Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<reqwest::RequestBuilder>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let response = build().await?.send().await.with_context(|| {
+                format!(
+                    "Failed to reach Blossom server {server}",
+                    server = self.server
+                )
+            })?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= MAX_ATTEMPTS {
+                return Ok(response);
+            }
+
+            let wait = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|s| s.parse::<u64>().ok())
+                .map_or(RETRY_DELAY, Duration::from_secs);
+            warn!(
+                "429 Too Many Requests from {server}, retrying in {wait:?} ({attempt}/{MAX_ATTEMPTS})",
+                server = self.server
+            );
+            sleep(wait).await;
+        }
+    }
+
+    /// Fails with the status and the server's `X-Reason` for unsuccessful responses
+    ///
+    /// 402 Payment Required fails with [`BlossomError::PaymentRequired`] carrying
+    /// the offered invoice, nothing is paid automatically.
+    async fn check(&self, response: Response, what: &str) -> Result<Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if status == StatusCode::PAYMENT_REQUIRED {
+            let header = |name: &str| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            };
+            return Err(BlossomError::PaymentRequired {
+                server: self.server.clone(),
+                what: what.to_string(),
+                lightning: header("X-Lightning"),
+                cashu: header("X-Cashu"),
+            }
+            .into());
+        }
+        let reason = response
+            .headers()
+            .get("X-Reason")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let reason = match reason {
+            Some(reason) => reason,
+            None => response.text().await.with_context(|| {
+                format!(
+                    "Blossom server {server} rejected {what} ({status}) and its response couldn't be read",
+                    server = self.server
+                )
+            })?,
+        };
+        bail!(
+            "Blossom server {server} rejected {what} ({status}): {reason}",
+            server = self.server
+        )
+    }
+
+    /// Uploads a blob held in memory (`PUT /upload`)
+    pub async fn upload(
+        &self,
+        data: Vec<u8>,
+        mime_type: &str,
+        signer: &UserSigner,
+    ) -> Result<BlobDescriptor> {
+        let sha256 = sha256_hex(&data);
+        let auth = upload_auth(signer, &sha256).await?;
+
+        debug!(
+            "Uploading {size} byte blob {sha256} to {server}",
+            size = data.len(),
+            server = self.server
+        );
+        let request = self
+            .http
+            .put(format!("{server}/upload", server = self.server))
+            .header(AUTHORIZATION, auth)
+            .header(CONTENT_TYPE, mime_type)
+            .body(data);
+        let response = self.send(request).await?;
+        self.uploaded_descriptor(response, &sha256).await
+    }
+
+    /// Uploads a file (`PUT /upload`), streaming it from disk
+    ///
+    /// `sha256` must be the hash of the file, see [`sha256_file`]. The file is
+    /// reopened for every attempt instead of being held in memory.
+    pub async fn upload_file(
+        &self,
+        path: &Path,
+        sha256: &str,
+        mime_type: &str,
+        signer: &UserSigner,
+    ) -> Result<BlobDescriptor> {
+        let size = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("Failed to read metadata of {path}", path = path.display()))?
+            .len();
+        let auth = upload_auth(signer, sha256).await?;
+
+        debug!(
+            "Uploading {size} byte file {path} as {sha256} to {server}",
+            path = path.display(),
+            server = self.server
+        );
+        let url = &format!("{server}/upload", server = self.server);
+        let auth = &auth;
+        let response = self
+            .send_with(move || async move {
+                let file = tokio::fs::File::open(path)
+                    .await
+                    .with_context(|| format!("Failed to open {path}", path = path.display()))?;
+                Ok(self
+                    .http
+                    .put(url)
+                    .header(AUTHORIZATION, auth)
+                    .header(CONTENT_TYPE, mime_type)
+                    .header(CONTENT_LENGTH, size)
+                    .body(reqwest::Body::wrap_stream(ReaderStream::new(file))))
+            })
+            .await?;
+        self.uploaded_descriptor(response, sha256).await
+    }
+
+    /// Parses the descriptor answering an upload and checks it names the uploaded blob
+    async fn uploaded_descriptor(
+        &self,
+        response: Response,
+        sha256: &str,
+    ) -> Result<BlobDescriptor> {
+        let response = self.check(response, "the upload").await?;
+        let descriptor: BlobDescriptor = response
+            .json()
+            .await
+            .context("Failed to parse Blossom blob descriptor")?;
+
+        ensure!(
+            descriptor.sha256 == sha256,
+            "Blossom server {server} stored the blob as {stored}, expected {sha256}",
+            server = self.server,
+            stored = descriptor.sha256
+        );
+        Ok(descriptor)
+    }
+
+    /// Has the server copy a blob from another server (`PUT /mirror`, BUD-04)
+    pub async fn mirror(
+        &self,
+        source_url: &str,
+        sha256: &str,
+        signer: &UserSigner,
+    ) -> Result<BlobDescriptor> {
+        let auth = auth_header(
+            signer,
+            BlossomAction::Upload,
+            &format!("Mirror {sha256}"),
+            Some(sha256),
+        )
+        .await?;
+
+        debug!(
+            "Mirroring blob {sha256} from {source_url} to {server}",
+            server = self.server
+        );
+        let request = self
+            .http
+            .put(format!("{server}/mirror", server = self.server))
+            .header(AUTHORIZATION, auth)
+            .json(&serde_json::json!({ "url": source_url }));
+        let response = self.send(request).await?;
+        let response = self.check(response, "the mirror request").await?;
+        let descriptor: BlobDescriptor = response
+            .json()
+            .await
+            .context("Failed to parse Blossom blob descriptor")?;
+
+        ensure!(
+            descriptor.sha256 == sha256,
+            "Blossom server {server} mirrored {source_url} as {stored}, expected {sha256}",
+            server = self.server,
+            stored = descriptor.sha256
+        );
+        Ok(descriptor)
+    }
+
+    /// Whether the server has a blob (`HEAD /<sha256>`)
+    pub async fn has_blob(&self, sha256: &str) -> Result<bool> {
+        let request = self
+            .http
+            .head(format!("{server}/{sha256}", server = self.server));
+        let response = self.send(request).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => {
+                self.check(response, "the existence check").await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Downloads a blob (`GET /<sha256>`), verifying its hash
+    pub async fn get_blob(&self, sha256: &str) -> Result<Vec<u8>> {
+        let request = self
+            .http
+            .get(format!("{server}/{sha256}", server = self.server));
+        let response = self.send(request).await?;
+        let response = self.check(response, "the download").await?;
+        let data = response
+            .bytes()
+            .await
+            .context("Failed to read Blossom blob")?
+            .to_vec();
+
+        let actual = sha256_hex(&data);
+        ensure!(
+            actual == sha256,
+            "Blob {sha256} from {server} has hash {actual}",
+            server = self.server
+        );
+        Ok(data)
+    }
+
+    /// Lists the blobs uploaded by a pubkey (`GET /list/<pubkey>`)
+    ///
+    /// Servers that require authorization for listing need a signer for the pubkey.
+    pub async fn list(
+        &self,
+        pubkey: &PublicKey,
+        signer: Option<&UserSigner>,
+    ) -> Result<Vec<BlobDescriptor>> {
+        let mut request = self.http.get(format!(
+            "{server}/list/{pubkey}",
+            server = self.server,
+            pubkey = pubkey.to_hex()
+        ));
+        if let Some(signer) = signer {
+            let auth = auth_header(signer, BlossomAction::List, "List blobs", None).await?;
+            request = request.header(AUTHORIZATION, auth);
+        }
+        let response = self.send(request).await?;
+        let response = self.check(response, "the listing").await?;
+        response
+            .json()
+            .await
+            .context("Failed to parse Blossom blob list")
+    }
+
+    /// Deletes a blob the signer uploaded (`DELETE /<sha256>`)
+    pub async fn delete(&self, sha256: &str, signer: &UserSigner) -> Result<()> {
+        let auth = auth_header(
+            signer,
+            BlossomAction::Delete,
+            &format!("Delete {sha256}"),
+            Some(sha256),
+        )
+        .await?;
+        let request = self
+            .http
+            .delete(format!("{server}/{sha256}", server = self.server))
+            .header(AUTHORIZATION, auth);
+        let response = self.send(request).await?;
+        self.check(response, "the deletion").await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blossom_mock::MockBlossom;
+
+    fn new_signer() -> UserSigner {
+        UserSigner::Local(nostr_sdk::Keys::generate())
+    }
+
+    #[test]
+    fn test_auth_builder_tags() -> Result<()> {
+        let keys = nostr_sdk::Keys::generate();
+        let sha256 = sha256_hex(b"blob");
+        let event = auth_builder(BlossomAction::Upload, "Upload blob", Some(&sha256))?
+            .sign_with_keys(&keys)?;
+
+        assert_eq!(event.kind, Kind::Custom(AUTH_KIND));
+        let tags: Vec<Vec<String>> = event.tags.iter().map(|t| t.clone().to_vec()).collect();
+        assert!(tags.contains(&vec!["t".to_string(), "upload".to_string()]));
+        assert!(tags.contains(&vec!["x".to_string(), sha256]));
+        let expiration = tags
+            .iter()
+            .find(|tag| tag[0] == "expiration")
+            .and_then(|tag| tag[1].parse::<u64>().ok())
+            .context("missing expiration tag")?;
+        assert!(expiration > event.created_at.as_u64());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_get_list_delete() -> Result<()> {
+        let server = MockBlossom::start().await?;
+        let client = BlossomClient::new(&server.url())?;
+        let signer = new_signer();
+        let data = b"not really a jpeg".to_vec();
+        let sha256 = sha256_hex(&data);
+
+        assert!(!client.has_blob(&sha256).await?);
+
+        let descriptor = client.upload(data.clone(), "image/jpeg", &signer).await?;
+        assert_eq!(descriptor.sha256, sha256);
+        assert_eq!(descriptor.size, data.len() as u64);
+        assert_eq!(descriptor.mime_type.as_deref(), Some("image/jpeg"));
+        assert!(descriptor.url.contains(&sha256));
+
+        assert!(client.has_blob(&sha256).await?);
+        assert_eq!(client.get_blob(&sha256).await?, data);
+
+        let listed = client.list(&signer.public_key(), None).await?;
+        assert_eq!(listed, vec![descriptor]);
+
+        // Only the uploader can delete
+        assert!(client.delete(&sha256, &new_signer()).await.is_err());
+        client.delete(&sha256, &signer).await?;
+        assert!(!client.has_blob(&sha256).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_file_streams_from_disk() -> Result<()> {
+        let server = MockBlossom::start().await?;
+        let client = BlossomClient::new(&server.url())?;
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("video.mp4");
+        // Several hashing chunks and stream frames
+        let data: Vec<u8> = (0..HASH_CHUNK_SIZE * 3 + 17)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&path, &data)?;
+
+        let sha256 = sha256_file(&path).await?;
+        assert_eq!(sha256, sha256_hex(&data));
+
+        let descriptor = client
+            .upload_file(&path, &sha256, "video/mp4", &new_signer())
+            .await?;
+        assert_eq!(descriptor.size, data.len() as u64);
+        assert_eq!(server.blob(&sha256)?, Some(data));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mirror() -> Result<()> {
+        let origin = MockBlossom::start().await?;
+        let mirror = MockBlossom::start().await?;
+        let signer = new_signer();
+        let data = b"mirrored blob".to_vec();
+        let sha256 = sha256_hex(&data);
+
+        let uploaded = BlossomClient::new(&origin.url())?
+            .upload(data.clone(), "image/png", &signer)
+            .await?;
+        let mirrored = BlossomClient::new(&mirror.url())?
+            .mirror(&uploaded.url, &sha256, &signer)
+            .await?;
+        assert_eq!(mirrored.sha256, sha256);
+        assert_eq!(mirror.blob(&sha256)?, Some(data));
+
+        // The authorization must name the mirrored blob
+        let other = sha256_hex(b"something else");
+        assert!(
+            BlossomClient::new(&mirror.url())?
+                .mirror(&uploaded.url, &other, &signer)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_payment_required() -> Result<()> {
+        let server = MockBlossom::start().await?;
+        server.require_payment("lnbc10n1mockinvoice")?;
+        let client = BlossomClient::new(&server.url())?;
+
+        let error = client
+            .upload(b"blob".to_vec(), "image/png", &new_signer())
+            .await
+            .expect_err("upload should require payment");
+        let Some(BlossomError::PaymentRequired {
+            lightning, cashu, ..
+        }) = error.downcast_ref::<BlossomError>()
+        else {
+            bail!("Expected a payment required error, got: {error:#}");
+        };
+        assert_eq!(lightning.as_deref(), Some("lnbc10n1mockinvoice"));
+        assert!(cashu.is_none());
+        assert!(error.to_string().contains("lnbc10n1mockinvoice"));
+        assert_eq!(server.uploads()?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_rejects_invalid_auth() -> Result<()> {
+        let server = MockBlossom::start().await?;
+        let signer = new_signer();
+
+        // A NIP-98 style event, as strict servers see it
+        let event = signer
+            .sign_event(
+                EventBuilder::new(Kind::Custom(27235), "").tag(Tag::parse(vec!["method", "PUT"])?),
+            )
+            .await?;
+        let auth = format!(
+            "Nostr {token}",
+            token = STANDARD.encode(serde_json::to_string(&event)?)
+        );
+        let response = reqwest::Client::new()
+            .put(format!("{url}/upload", url = server.url()))
+            .header(AUTHORIZATION, auth)
+            .body(b"blob".to_vec())
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key("X-Reason"));
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+//! In-memory Blossom server for tests, checking authorization like strict servers
+
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{HeaderMap, Method, Request, Response, StatusCode, header};
+use hyper_util::rt::TokioIo;
+use nostr_sdk::{Event, Kind, PublicKey, Timestamp};
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+use crate::blossom::{AUTH_KIND, sha256_hex};
+
+struct StoredBlob {
+    data: Bytes,
+    mime_type: Option<String>,
+    owner: PublicKey,
+    uploaded: u64,
+}
+
+#[derive(Default)]
+struct MockState {
+    blobs: HashMap<String, StoredBlob>,
+    /// Successful uploads, including ones of blobs already stored
+    uploads: usize,
+    /// Successful mirror requests
+    mirrors: usize,
+    /// Lightning invoice uploads are answered with, as 402 Payment Required
+    payment_invoice: Option<String>,
+}
+
+/// Blossom server on 127.0.0.1 serving BUD-01/02 from memory
+pub struct MockBlossom {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockBlossom {
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .context("Failed to bind mock Blossom server")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let stream = tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(_) => continue,
+                    },
+                };
+
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request: Request<Incoming>| {
+                        let state = state.clone();
+                        async move { Ok::<_, Infallible>(handle(&state, addr, request).await) }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown: Some(shutdown_tx),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{addr}", addr = self.addr)
+    }
+
+    /// Content of a stored blob
+    pub fn blob(&self, sha256: &str) -> Result<Option<Vec<u8>>> {
+        let state = lock(&self.state)?;
+        Ok(state.blobs.get(sha256).map(|blob| blob.data.to_vec()))
+    }
+
+    /// Number of successful uploads so far
+    pub fn uploads(&self) -> Result<usize> {
+        Ok(lock(&self.state)?.uploads)
+    }
+
+    /// Number of successful mirror requests so far
+    pub fn mirrors(&self) -> Result<usize> {
+        Ok(lock(&self.state)?.mirrors)
+    }
+
+    /// Answers uploads with 402 Payment Required and this invoice in `X-Lightning`
+    pub fn require_payment(&self, invoice: &str) -> Result<()> {
+        lock(&self.state)?.payment_invoice = Some(invoice.to_string());
+        Ok(())
+    }
+}
+
+fn lock(state: &Mutex<MockState>) -> Result<MutexGuard<'_, MockState>> {
+    state
+        .lock()
+        .map_err(|_| anyhow!("Mock Blossom state poisoned by a panicking request"))
+}
+
+impl Drop for MockBlossom {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn respond(status: StatusCode, body: Bytes) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body));
+    *response.status_mut() = status;
+    response
+}
+
+fn json_response(body: serde_json::Value) -> Response<Full<Bytes>> {
+    let mut response = respond(StatusCode::OK, Bytes::from(body.to_string()));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Error response with the reason in `X-Reason`, as BUD-01 asks
+fn reject(status: StatusCode, reason: &str) -> Response<Full<Bytes>> {
+    let mut response = respond(status, Bytes::new());
+    if let Ok(value) = header::HeaderValue::from_str(reason) {
+        response.headers_mut().insert("X-Reason", value);
+    }
+    response
+}
+
+fn descriptor(addr: SocketAddr, sha256: &str, blob: &StoredBlob) -> serde_json::Value {
+    json!({
+        "url": format!("http://{addr}/{sha256}"),
+        "sha256": sha256,
+        "size": blob.data.len(),
+        "type": blob.mime_type,
+        "uploaded": blob.uploaded,
+    })
+}
+
+/// Checks a kind 24242 authorization for `action`, returning its author
+fn authorize(
+    headers: &HeaderMap,
+    action: &str,
+    sha256: &str,
+) -> std::result::Result<PublicKey, &'static str> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Nostr "))
+        .ok_or("Missing Nostr authorization")?;
+    let json = STANDARD
+        .decode(token)
+        .map_err(|_| "Authorization is not base64")?;
+    let event: Event =
+        serde_json::from_slice(&json).map_err(|_| "Authorization is not a Nostr event")?;
+    event
+        .verify()
+        .map_err(|_| "Invalid authorization signature")?;
+    if event.kind != Kind::Custom(AUTH_KIND) {
+        return Err("Authorization must be a kind 24242 event");
+    }
+
+    let tags: Vec<Vec<String>> = event.tags.iter().map(|t| t.clone().to_vec()).collect();
+    let value = |name: &str| {
+        tags.iter()
+            .find(|tag| tag.first().map(String::as_str) == Some(name))
+            .and_then(|tag| tag.get(1).cloned())
+    };
+    if value("t").as_deref() != Some(action) {
+        return Err("Authorization is for another action");
+    }
+    let expiration = value("expiration")
+        .and_then(|e| e.parse::<u64>().ok())
+        .ok_or("Authorization has no expiration")?;
+    if expiration <= Timestamp::now().as_u64() {
+        return Err("Authorization expired");
+    }
+    let names_blob = tags.iter().any(|tag| {
+        tag.first().map(String::as_str) == Some("x")
+            && tag.get(1).map(String::as_str) == Some(sha256)
+    });
+    if !names_blob {
+        return Err("Authorization is for another blob");
+    }
+    Ok(event.pubkey)
+}
+
+async fn handle(
+    state: &Mutex<MockState>,
+    addr: SocketAddr,
+    request: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    let path = request.uri().path().trim_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').collect();
+    let method = request.method().clone();
+
+    match (&method, segments.as_slice()) {
+        (&Method::PUT, ["upload"]) => {
+            let headers = request.headers().clone();
+            let mime_type = headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let Ok(body) = request.into_body().collect().await else {
+                return reject(StatusCode::BAD_REQUEST, "Failed to read body");
+            };
+            let data = body.to_bytes();
+            let sha256 = sha256_hex(&data);
+            let pubkey = match authorize(&headers, "upload", &sha256) {
+                Ok(pubkey) => pubkey,
+                Err(reason) => return reject(StatusCode::UNAUTHORIZED, reason),
+            };
+
+            let Ok(mut state) = lock(state) else {
+                return reject(StatusCode::INTERNAL_SERVER_ERROR, "Mock state poisoned");
+            };
+            if let Some(invoice) = &state.payment_invoice {
+                let mut response = reject(StatusCode::PAYMENT_REQUIRED, "Payment required");
+                if let Ok(value) = header::HeaderValue::from_str(invoice) {
+                    response.headers_mut().insert("X-Lightning", value);
+                }
+                return response;
+            }
+            state.uploads += 1;
+            let blob = state.blobs.entry(sha256.clone()).or_insert(StoredBlob {
+                data,
+                mime_type,
+                owner: pubkey,
+                uploaded: Timestamp::now().as_u64(),
+            });
+            json_response(descriptor(addr, &sha256, blob))
+        }
+        (&Method::PUT, ["mirror"]) => {
+            let headers = request.headers().clone();
+            let Ok(body) = request.into_body().collect().await else {
+                return reject(StatusCode::BAD_REQUEST, "Failed to read body");
+            };
+            let Some(source_url) = serde_json::from_slice::<serde_json::Value>(&body.to_bytes())
+                .ok()
+                .and_then(|v| v.get("url")?.as_str().map(str::to_string))
+            else {
+                return reject(StatusCode::BAD_REQUEST, "Body must be {\"url\": ...}");
+            };
+            let fetched = match reqwest::get(&source_url).await {
+                Ok(response) if response.status().is_success() => response.bytes().await.ok(),
+                _ => None,
+            };
+            let Some(data) = fetched else {
+                return reject(StatusCode::BAD_GATEWAY, "Failed to fetch blob to mirror");
+            };
+            let sha256 = sha256_hex(&data);
+            let pubkey = match authorize(&headers, "upload", &sha256) {
+                Ok(pubkey) => pubkey,
+                Err(reason) => return reject(StatusCode::UNAUTHORIZED, reason),
+            };
+
+            let Ok(mut state) = lock(state) else {
+                return reject(StatusCode::INTERNAL_SERVER_ERROR, "Mock state poisoned");
+            };
+            state.mirrors += 1;
+            let blob = state.blobs.entry(sha256.clone()).or_insert(StoredBlob {
+                data,
+                mime_type: None,
+                owner: pubkey,
+                uploaded: Timestamp::now().as_u64(),
+            });
+            json_response(descriptor(addr, &sha256, blob))
+        }
+        (&Method::GET, ["list", pubkey]) => {
+            let Ok(state) = lock(state) else {
+                return reject(StatusCode::INTERNAL_SERVER_ERROR, "Mock state poisoned");
+            };
+            let listed: Vec<serde_json::Value> = state
+                .blobs
+                .iter()
+                .filter(|(_, blob)| blob.owner.to_hex() == *pubkey)
+                .map(|(sha256, blob)| descriptor(addr, sha256, blob))
+                .collect();
+            json_response(json!(listed))
+        }
+        (&Method::DELETE, [name]) => {
+            let sha256 = name.split('.').next().unwrap_or(name).to_string();
+            let pubkey = match authorize(request.headers(), "delete", &sha256) {
+                Ok(pubkey) => pubkey,
+                Err(reason) => return reject(StatusCode::UNAUTHORIZED, reason),
+            };
+            let Ok(mut state) = lock(state) else {
+                return reject(StatusCode::INTERNAL_SERVER_ERROR, "Mock state poisoned");
+            };
+            match state.blobs.get(&sha256) {
+                None => reject(StatusCode::NOT_FOUND, "Blob not found"),
+                Some(blob) if blob.owner != pubkey => {
+                    reject(StatusCode::FORBIDDEN, "Blob belongs to another pubkey")
+                }
+                Some(_) => {
+                    state.blobs.remove(&sha256);
+                    respond(StatusCode::OK, Bytes::new())
+                }
+            }
+        }
+        (&Method::GET | &Method::HEAD, [name]) => {
+            let sha256 = name.split('.').next().unwrap_or(name);
+            let Ok(state) = lock(state) else {
+                return reject(StatusCode::INTERNAL_SERVER_ERROR, "Mock state poisoned");
+            };
+            match state.blobs.get(sha256) {
+                None => reject(StatusCode::NOT_FOUND, "Blob not found"),
+                Some(_) if method == Method::HEAD => respond(StatusCode::OK, Bytes::new()),
+                Some(blob) => respond(StatusCode::OK, blob.data.clone()),
+            }
+        }
+        _ => reject(StatusCode::NOT_FOUND, "Unknown endpoint"),
+    }
+}
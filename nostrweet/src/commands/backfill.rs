@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::info;
+
+use crate::backfill;
+use crate::twitter::TwitterApi;
+
+/// Walk a user's timeline backward and cache every tweet the API still returns
+///
+/// Progress is checkpointed after each page, so an interrupted run (crash,
+/// rate limit, Ctrl-C) continues where it stopped when started again. Media
+/// is not downloaded here; posting fetches it when needed.
+pub async fn execute(
+    username: &str,
+    data_dir: &Path,
+    max_tweets: u32,
+    restart: bool,
+    twitter_api: &TwitterApi,
+) -> Result<()> {
+    let username = username.trim_start_matches('@');
+
+    let client = twitter_api
+        .client(data_dir)
+        .context("Failed to initialize Twitter client")?;
+
+    if restart {
+        backfill::remove_checkpoint(data_dir, username)?;
+    }
+
+    let mut checkpoint = backfill::load_or_start(&client, data_dir, username).await?;
+    checkpoint.extend_limit(max_tweets);
+    if checkpoint.completed {
+        println!(
+            "Backfill of @{username} already complete ({fetched} tweet(s) fetched). Use --restart to run it again.",
+            fetched = checkpoint.fetched
+        );
+        return Ok(());
+    }
+    if checkpoint.pages > 0 {
+        info!(
+            "Resuming backfill of @{username} after {pages} page(s), {fetched} tweet(s)",
+            pages = checkpoint.pages,
+            fetched = checkpoint.fetched
+        );
+    }
+
+    while !checkpoint.completed {
+        backfill::backfill_page(&client, data_dir, &mut checkpoint, max_tweets)
+            .await
+            .with_context(|| {
+                format!(
+                    "Backfill of @{username} interrupted after {fetched} tweet(s); progress is saved, run the command again to resume",
+                    fetched = checkpoint.fetched
+                )
+            })?;
+        println!(
+            "@{username}: {fetched} tweet(s) fetched, {saved} new",
+            fetched = checkpoint.fetched,
+            saved = checkpoint.saved
+        );
+    }
+
+    println!(
+        "Backfill of @{username} complete: {fetched} tweet(s) fetched, {saved} newly cached",
+        fetched = checkpoint.fetched,
+        saved = checkpoint.saved
+    );
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use anyhow::{Result, ensure};
+use nostr_sdk::PublicKey;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::blob_ledger::{self, BlobLedger};
+use crate::blossom::BlossomClient;
+use crate::signer::{self, Signers, UserSigner};
+use crate::storage;
+
+/// Outcome of a repair pass over the blob ledger
+#[derive(Debug, Default)]
+struct RepairReport {
+    checked: usize,
+    healthy: usize,
+    repaired: usize,
+    failed: usize,
+}
+
+/// Signers of every cached Twitter user able to sign, by pubkey (hex)
+async fn uploader_signers(
+    data_dir: &Path,
+    signers: &Signers,
+) -> Result<HashMap<String, UserSigner>> {
+    let mut uploaders = HashMap::new();
+    for user in storage::list_cached_user_profiles(data_dir)? {
+        match signer::signer_for_user(&user, signers).await {
+            Ok(signer) if signer.can_sign() => {
+                uploaders.insert(signer.public_key().to_hex(), signer);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("No signer for @{username}: {e:#}", username = user.username),
+        }
+    }
+    Ok(uploaders)
+}
+
+/// Mirror blobs from the blob ledger until each is on `replicas` of the given servers
+///
+/// Copies the ledger lists are checked with `HEAD` first, so blobs a server
+/// lost are mirrored again. Mirrors are signed with the key of the blob's
+/// original uploader.
+pub async fn repair(
+    blossom_servers: &[String],
+    replicas: Option<usize>,
+    data_dir: &Path,
+    signers: &Signers,
+) -> Result<()> {
+    ensure!(!blossom_servers.is_empty(), "No Blossom servers given");
+    let wanted = replicas
+        .unwrap_or(blossom_servers.len())
+        .min(blossom_servers.len());
+    ensure!(wanted > 0, "At least one replica is needed");
+
+    let clients = blossom_servers
+        .iter()
+        .map(|server| BlossomClient::new(server))
+        .collect::<Result<Vec<_>>>()?;
+    let ledger = BlobLedger::load(data_dir)?;
+    if ledger.blobs.is_empty() {
+        println!("No uploaded blobs recorded in the blob ledger");
+        return Ok(());
+    }
+    info!(
+        "Checking {count} blob(s) for {wanted} replica(s) each",
+        count = ledger.blobs.len()
+    );
+    let uploaders = uploader_signers(data_dir, signers).await?;
+
+    let mut report = RepairReport::default();
+    for (sha256, blob) in &ledger.blobs {
+        report.checked += 1;
+        let uploader = blob
+            .uploader
+            .as_deref()
+            .and_then(|pubkey| PublicKey::from_hex(pubkey).ok());
+
+        // Confirm the copies the ledger knows about
+        let mut holders = Vec::new();
+        let mut missing = Vec::new();
+        for client in &clients {
+            if !blob.servers.contains_key(client.server()) {
+                missing.push(client);
+                continue;
+            }
+            match blob_ledger::find_on_server(
+                client,
+                sha256,
+                blob.size,
+                blob.mime_type.as_deref(),
+                uploader.as_ref(),
+                data_dir,
+            )
+            .await
+            {
+                Ok(Some(url)) => holders.push(url),
+                Ok(None) => missing.push(client),
+                Err(e) => warn!("{e:#}"),
+            }
+        }
+        if holders.len() >= wanted {
+            report.healthy += 1;
+            continue;
+        }
+
+        // Servers that are no longer configured can still be the source
+        let source = holders.first().cloned().or_else(|| {
+            blob.servers
+                .iter()
+                .find(|(server, _)| !clients.iter().any(|c| c.server() == server.as_str()))
+                .map(|(_, url)| url.clone())
+        });
+        let Some(source) = source else {
+            warn!("Can't repair blob {sha256}, no server holds it anymore");
+            report.failed += 1;
+            continue;
+        };
+        let Some(signer) = blob
+            .uploader
+            .as_deref()
+            .and_then(|pubkey| uploaders.get(pubkey))
+        else {
+            warn!("Can't repair blob {sha256}, the key of its uploader is not available");
+            report.failed += 1;
+            continue;
+        };
+
+        for client in missing {
+            if holders.len() >= wanted {
+                break;
+            }
+            match blob_ledger::mirror_to_server(
+                client,
+                &source,
+                sha256,
+                blob.size,
+                blob.mime_type.as_deref(),
+                signer,
+                data_dir,
+            )
+            .await
+            {
+                Ok(url) => {
+                    info!(
+                        "Mirrored blob {sha256} to {server}",
+                        server = client.server()
+                    );
+                    holders.push(url);
+                }
+                Err(e) => warn!("{e:#}"),
+            }
+        }
+        if holders.len() >= wanted {
+            report.repaired += 1;
+        } else {
+            report.failed += 1;
+        }
+    }
+
+    println!(
+        "Checked {checked} blob(s): {healthy} healthy, {repaired} repaired, {failed} below {wanted} replica(s)",
+        checked = report.checked,
+        healthy = report.healthy,
+        repaired = report.repaired,
+        failed = report.failed
+    );
+    Ok(())
+}
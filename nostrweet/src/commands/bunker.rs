@@ -0,0 +1,78 @@
+use anyhow::{Context, Result, ensure};
+use nostr_sdk::prelude::*;
+use tokio::signal;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::keys::KeySource;
+use crate::remote_signer::Bunker;
+
+/// Runs a local NIP-46 bunker that holds the mnemonic and signs for every
+/// mirrored Twitter user on request
+pub async fn execute(relays: &[String], keys: &KeySource, secret: Option<String>) -> Result<()> {
+    ensure!(
+        keys.mnemonic().is_some(),
+        "Mnemonic not provided. Please use --mnemonic flag or NOSTRWEET_MNEMONIC environment variable."
+    );
+    let mut bunker = Bunker::new(keys.clone(), secret)?;
+
+    let client = Client::new(bunker.keys().clone());
+    for relay in relays {
+        client
+            .add_relay(relay)
+            .await
+            .with_context(|| format!("Failed to add relay {relay}"))?;
+    }
+    client.connect().await;
+
+    let filter = Filter::new()
+        .kind(Kind::NostrConnect)
+        .pubkey(bunker.public_key())
+        .since(Timestamp::now());
+    client
+        .subscribe(filter, None)
+        .await
+        .context("Failed to subscribe to NIP-46 requests")?;
+
+    info!("Bunker listening on {count} relay(s)", count = relays.len());
+    println!("{uri}", uri = bunker.bunker_uri(relays));
+
+    let mut notifications = client.notifications();
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!("Received shutdown signal, stopping bunker");
+                break;
+            }
+            notification = notifications.recv() => {
+                let notification = match notification {
+                    Ok(notification) => notification,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Bunker lagged behind, skipped {skipped} notification(s)");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => {
+                        warn!("Relay notification channel closed, stopping bunker");
+                        break;
+                    }
+                };
+                let RelayPoolNotification::Event { event, .. } = notification else {
+                    continue;
+                };
+
+                match bunker.handle_event(&event) {
+                    Ok(Some(response)) => {
+                        if let Err(e) = client.send_event(&response).await {
+                            warn!("Failed to send bunker response: {e}");
+                        }
+                    }
+                    Ok(None) => debug!("Ignoring event {id}", id = event.id),
+                    Err(e) => warn!("Failed to handle bunker request {id}: {e}", id = event.id),
+                }
+            }
+        }
+    }
+
+    client.disconnect().await;
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::signal;
@@ -8,11 +8,20 @@ use tokio::sync::{Mutex, RwLock, oneshot};
 use tokio::time;
 use tracing::{debug, error, info, trace, warn};
 
+use crate::backfill;
+use crate::deletions::{self, DeletionPolicy, DeletionReport};
+use crate::edits;
+use crate::media_backend;
+use crate::mirror_options::MirrorOptions;
 use crate::nostr;
 use crate::nostr_profile;
 use crate::profile_collector;
+use crate::repost;
+use crate::signer::{self, Signers, UserSigner};
 use crate::storage;
-use crate::twitter::{TwitterClient, TwitterError};
+use crate::thread;
+use crate::tweet_event;
+use crate::twitter::{TWEET_LOOKUP_LIMIT, TwitterApi, TwitterClient, TwitterError};
 
 /// Configuration for the daemon
 pub struct DaemonConfig {
@@ -20,9 +29,16 @@ pub struct DaemonConfig {
     pub relays: Vec<String>,
     pub blossom_servers: Vec<String>,
     pub poll_interval: u64,
+    /// Backfill user history in idle time between polls
+    pub backfill: bool,
+    /// Hours between rechecks of mirrored tweets for deletions, if enabled
+    pub verify_deletions: Option<u64>,
+    /// Log deletions without publishing them
+    pub deletions_dry_run: bool,
     pub data_dir: std::path::PathBuf,
-    pub mnemonic: Option<String>,
-    pub bearer_token: String,
+    pub signers: Signers,
+    pub mirror_options: MirrorOptions,
+    pub twitter_api: TwitterApi,
 }
 
 /// Per-user state tracking
@@ -75,6 +91,9 @@ pub struct DaemonStats {
     pub failed_polls: u64,
     pub total_tweets_downloaded: u64,
     pub total_tweets_posted: u64,
+    pub total_media_uploaded: u64,
+    /// Tweets posted with links to Twitter because their media failed to upload
+    pub media_upload_failures: u64,
     pub _total_profiles_downloaded: u64,
     pub _total_profiles_posted: u64,
 }
@@ -87,6 +106,8 @@ pub struct DaemonState {
     pub user_states: Arc<RwLock<HashMap<String, UserState>>>,
     pub stats: Arc<RwLock<DaemonStats>>,
     pub rate_limiter: Arc<Mutex<RateLimiter>>,
+    /// When mirrored tweets were last rechecked for deletions
+    pub last_deletion_check: Arc<Mutex<Option<Instant>>>,
 }
 
 /// Rate limiter for Twitter API
@@ -131,17 +152,29 @@ impl RateLimiter {
         // Record this request
         self.request_times.push_back(Instant::now());
     }
+
+    /// Whether less than half of the window is used, leaving room for polling
+    fn has_spare_capacity(&self) -> bool {
+        let cutoff = Instant::now() - self.window_duration;
+        let recent = self.request_times.iter().filter(|&&t| t >= cutoff).count();
+        recent < (self.requests_per_window / 2) as usize
+    }
 }
 
 /// Main entry point for daemon mode
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     users: Vec<String>,
     relays: Vec<String>,
     blossom_servers: Vec<String>,
     poll_interval: u64,
+    backfill: bool,
+    verify_deletions: Option<u64>,
+    deletions_dry_run: bool,
     data_dir: &Path,
-    mnemonic: Option<&str>,
-    bearer_token: &str,
+    signers: Signers,
+    mirror_options: MirrorOptions,
+    twitter_api: TwitterApi,
 ) -> Result<()> {
     info!(
         "Starting daemon v2 for {user_count} users with {poll_interval} second base interval",
@@ -153,9 +186,13 @@ pub async fn execute(
         relays,
         blossom_servers,
         poll_interval,
+        backfill,
+        verify_deletions,
+        deletions_dry_run,
         data_dir: data_dir.to_path_buf(),
-        mnemonic: mnemonic.map(|s| s.to_string()),
-        bearer_token: bearer_token.to_string(),
+        signers,
+        mirror_options,
+        twitter_api,
     });
 
     // Initialize daemon state
@@ -213,7 +250,9 @@ async fn init_daemon(config: Arc<DaemonConfig>) -> Result<DaemonState> {
     // Initialize Twitter client
     info!("Initializing Twitter client");
     let twitter_client = Arc::new(
-        TwitterClient::new(&config.data_dir, &config.bearer_token)
+        config
+            .twitter_api
+            .client(&config.data_dir)
             .context("Failed to initialize Twitter client")?,
     );
 
@@ -223,9 +262,9 @@ async fn init_daemon(config: Arc<DaemonConfig>) -> Result<DaemonState> {
         relay_count = config.relays.len()
     );
     // Use ephemeral keys for relay connection (just for subscribing/querying)
-    let keys = nostr_sdk::Keys::generate();
+    let ephemeral = UserSigner::Local(nostr_sdk::Keys::generate());
     let nostr_client = Arc::new(
-        nostr::initialize_nostr_client(&keys, &config.relays)
+        nostr::initialize_nostr_client(&ephemeral, &config.relays)
             .await
             .context("Failed to connect to Nostr relays")?,
     );
@@ -251,10 +290,13 @@ async fn init_daemon(config: Arc<DaemonConfig>) -> Result<DaemonState> {
             failed_polls: 0,
             total_tweets_downloaded: 0,
             total_tweets_posted: 0,
+            total_media_uploaded: 0,
+            media_upload_failures: 0,
             _total_profiles_downloaded: 0,
             _total_profiles_posted: 0,
         })),
         rate_limiter,
+        last_deletion_check: Arc::new(Mutex::new(None)),
     })
 }
 
@@ -269,6 +311,16 @@ async fn run_daemon_v2(state: DaemonState) -> Result<()> {
         let users_to_poll = get_users_ready_for_polling(&state).await;
 
         if users_to_poll.is_empty() {
+            if state.config.backfill
+                && let Err(e) = backfill_next_user(&state).await
+            {
+                warn!("Backfill step failed, will retry later: {e:#}");
+            }
+            if let Some(hours) = state.config.verify_deletions
+                && let Err(e) = verify_deletions_if_due(&state, hours).await
+            {
+                warn!("Deletion check failed, will retry later: {e:#}");
+            }
             trace!("No users ready for polling, sleeping for 10 seconds");
             time::sleep(Duration::from_secs(10)).await;
             continue;
@@ -395,6 +447,11 @@ async fn run_daemon_v2(state: DaemonState) -> Result<()> {
                 total_tweets_downloaded = stats.total_tweets_downloaded,
                 total_tweets_posted = stats.total_tweets_posted
             );
+            info!(
+                "Media: {total_media_uploaded} uploaded, {media_upload_failures} tweets with failed uploads",
+                total_media_uploaded = stats.total_media_uploaded,
+                media_upload_failures = stats.media_upload_failures
+            );
             info!("Users: {healthy_users} healthy, {failing_users} failing");
 
             // Log failing users for debugging
@@ -443,6 +500,100 @@ async fn get_users_ready_for_polling(state: &DaemonState) -> Vec<String> {
     ready_users
 }
 
+/// Fetch one page of history for the first user whose backfill isn't complete
+///
+/// Runs only while half of the rate limit window is unused, so tailing new
+/// tweets always takes precedence. Tweets are only cached; posting them
+/// stays with `post-user-to-nostr`.
+async fn backfill_next_user(state: &DaemonState) -> Result<()> {
+    if !state.rate_limiter.lock().await.has_spare_capacity() {
+        trace!("Rate limit window busy, postponing backfill");
+        return Ok(());
+    }
+
+    let data_dir = &state.config.data_dir;
+    for username in &state.config.users {
+        let mut checkpoint = match backfill::load_checkpoint(data_dir, username)? {
+            Some(mut checkpoint) => {
+                checkpoint.extend_limit(backfill::TIMELINE_HISTORY_LIMIT);
+                if checkpoint.completed {
+                    continue;
+                }
+                checkpoint
+            }
+            None => {
+                info!("Starting history backfill of @{username}");
+                // Starting looks up the user ID
+                state.rate_limiter.lock().await.wait_if_needed().await;
+                backfill::load_or_start(&state.twitter_client, data_dir, username).await?
+            }
+        };
+
+        state.rate_limiter.lock().await.wait_if_needed().await;
+        let saved = backfill::backfill_page(
+            &state.twitter_client,
+            data_dir,
+            &mut checkpoint,
+            backfill::TIMELINE_HISTORY_LIMIT,
+        )
+        .await?;
+        state.stats.write().await.total_tweets_downloaded += saved as u64;
+        return Ok(());
+    }
+
+    Ok(())
+}
+
+/// Recheck the monitored users' mirrored tweets for deletions every `hours`
+///
+/// Only the daemon's own users are eligible, and each lookup of 100 tweets
+/// goes through the rate limiter like any other request.
+async fn verify_deletions_if_due(state: &DaemonState, hours: u64) -> Result<()> {
+    let mut last_check = state.last_deletion_check.lock().await;
+    if last_check.is_some_and(|last| last.elapsed() < Duration::from_secs(hours * 3600)) {
+        return Ok(());
+    }
+    // Recorded up front, so a failing pass isn't retried every idle cycle
+    *last_check = Some(Instant::now());
+    drop(last_check);
+
+    let data_dir = &state.config.data_dir;
+    let policy = DeletionPolicy {
+        dry_run: state.config.deletions_dry_run,
+        allowlist: state.config.users.clone(),
+    };
+    let candidates = deletions::candidates(data_dir, &policy).await?;
+    info!(
+        "Checking {count} mirrored tweet(s) for deletions",
+        count = candidates.len()
+    );
+
+    let mut report = DeletionReport::default();
+    for batch in candidates.chunks(TWEET_LOOKUP_LIMIT) {
+        state.rate_limiter.lock().await.wait_if_needed().await;
+        report.add(
+            deletions::verify_batch(
+                &state.twitter_client,
+                &state.nostr_client,
+                batch,
+                data_dir,
+                &state.config.relays,
+                &policy,
+                &state.config.signers,
+            )
+            .await?,
+        );
+    }
+
+    info!(
+        "Deletion check finished: {checked} checked, {missing} deleted on Twitter, {deleted} deletion request(s) published",
+        checked = report.checked,
+        missing = report.missing,
+        deleted = report.deleted
+    );
+    Ok(())
+}
+
 /// Process a single user with better error handling and state tracking
 async fn process_user_v2(state: DaemonState, username: String) -> Result<()> {
     // Mark as processing
@@ -555,7 +706,8 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
     let mut new_tweet_count = 0u64;
     let mut posted_to_nostr_count = 0u64;
 
-    for tweet in tweets {
+    // The timeline is newest first, post oldest first so thread parts follow their parents
+    for tweet in tweets.into_iter().rev() {
         let tweet_id = &tweet.id;
 
         // Check if tweet is already cached
@@ -567,14 +719,29 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
             {
                 let cached_tweet = storage::load_tweet_from_file(&tweet_path)?;
 
-                // Get keys for this tweet's author
-                let keys = crate::keys::get_keys_for_tweet(
-                    &cached_tweet.author.id,
-                    state.config.mnemonic.as_deref(),
-                )?;
+                // Get the signer of this tweet's author
+                let author_signer =
+                    signer::signer_for_user(&cached_tweet.author, &state.config.signers).await?;
 
                 // Check if already posted to Nostr by querying the relay
-                if !is_tweet_posted_to_nostr(tweet_id, &state.nostr_client, &keys).await? {
+                if !author_signer.can_sign() {
+                    debug!(
+                        "Author of tweet {tweet_id} has a pubkey-only key override, not posting"
+                    );
+                } else if !is_tweet_posted_to_nostr(
+                    tweet_id,
+                    &state.nostr_client,
+                    author_signer.public_key(),
+                )
+                .await?
+                {
+                    match post_linked_tweets(&cached_tweet, state, &author_signer).await {
+                        Ok(posted) => posted_to_nostr_count += posted,
+                        Err(e) => {
+                            warn!("Failed to post tweets linked from {tweet_id}: {e:#}")
+                        }
+                    }
+
                     // Post the cached tweet to Nostr
                     if post_tweet_to_nostr_with_state(&cached_tweet, state)
                         .await
@@ -590,7 +757,8 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
                                 &referenced_usernames,
                                 &state.nostr_client,
                                 &state.config.data_dir,
-                                state.config.mnemonic.as_deref(),
+                                &state.config.signers,
+                                &state.config.mirror_options,
                             )
                             .await;
                         }
@@ -607,7 +775,7 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
         if let Err(e) = storage::ensure_tweet_enriched(
             &mut enriched_tweet,
             &state.config.data_dir,
-            Some(&state.config.bearer_token),
+            Some(&state.config.twitter_api),
         )
         .await
         {
@@ -618,7 +786,7 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
         let _media_results = crate::media::download_media(
             &enriched_tweet,
             &state.config.data_dir,
-            Some(&state.config.bearer_token),
+            Some(state.config.twitter_api.bearer_token()),
         )
         .await
         .with_context(|| format!("Failed to download media for tweet {tweet_id}"))?;
@@ -638,12 +806,23 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
         }
 
         // Check if already posted to Nostr before attempting to post
-        let keys = crate::keys::get_keys_for_tweet(
-            &enriched_tweet.author.id,
-            state.config.mnemonic.as_deref(),
-        )?;
+        let author_signer =
+            signer::signer_for_user(&enriched_tweet.author, &state.config.signers).await?;
+
+        if !author_signer.can_sign() {
+            debug!("Author of tweet {tweet_id} has a pubkey-only key override, not posting");
+        } else if !is_tweet_posted_to_nostr(
+            tweet_id,
+            &state.nostr_client,
+            author_signer.public_key(),
+        )
+        .await?
+        {
+            match post_linked_tweets(&enriched_tweet, state, &author_signer).await {
+                Ok(posted) => posted_to_nostr_count += posted,
+                Err(e) => warn!("Failed to post tweets linked from {tweet_id}: {e:#}"),
+            }
 
-        if !is_tweet_posted_to_nostr(tweet_id, &state.nostr_client, &keys).await? {
             // Post to Nostr
             if post_tweet_to_nostr_with_state(&enriched_tweet, state)
                 .await
@@ -657,7 +836,8 @@ async fn process_user_tweets(state: &DaemonState, username: &str) -> Result<(u64
                         &referenced_usernames,
                         &state.nostr_client,
                         &state.config.data_dir,
-                        state.config.mnemonic.as_deref(),
+                        &state.config.signers,
+                        &state.config.mirror_options,
                     )
                     .await;
                 }
@@ -684,12 +864,14 @@ fn spawn_stats_reporter(stats: Arc<RwLock<DaemonStats>>) -> tokio::task::JoinHan
             let minutes = (uptime.as_secs() % 3600) / 60;
 
             info!(
-                "📊 Stats | Uptime: {hours}h{minutes}m | Polls: {total_polls} (✓{successful_polls} ✗{failed_polls}) | Downloaded: {total_tweets_downloaded} | Posted: {total_tweets_posted}",
+                "📊 Stats | Uptime: {hours}h{minutes}m | Polls: {total_polls} (✓{successful_polls} ✗{failed_polls}) | Downloaded: {total_tweets_downloaded} | Posted: {total_tweets_posted} | Media: {total_media_uploaded} (✗{media_upload_failures})",
                 total_polls = stats.total_polls,
                 successful_polls = stats.successful_polls,
                 failed_polls = stats.failed_polls,
                 total_tweets_downloaded = stats.total_tweets_downloaded,
-                total_tweets_posted = stats.total_tweets_posted
+                total_tweets_posted = stats.total_tweets_posted,
+                total_media_uploaded = stats.total_media_uploaded,
+                media_upload_failures = stats.media_upload_failures
             );
         }
     })
@@ -725,6 +907,14 @@ async fn print_final_stats(stats: &Arc<RwLock<DaemonStats>>) {
         "Total tweets posted to Nostr: {total_tweets_posted}",
         total_tweets_posted = stats.total_tweets_posted
     );
+    info!(
+        "Total media files uploaded: {total_media_uploaded}",
+        total_media_uploaded = stats.total_media_uploaded
+    );
+    info!(
+        "Tweets with failed media uploads: {media_upload_failures}",
+        media_upload_failures = stats.media_upload_failures
+    );
     info!("===============================");
 }
 
@@ -739,10 +929,10 @@ pub fn is_tweet_cached(tweet_id: &str, data_dir: &Path) -> bool {
 pub async fn is_tweet_posted_to_nostr(
     tweet_id: &str,
     nostr_client: &nostr_sdk::Client,
-    keys: &nostr_sdk::Keys,
+    pubkey: nostr_sdk::PublicKey,
 ) -> Result<bool> {
     // Use the existing find_existing_event function to check the relay
-    match crate::nostr::find_existing_event(nostr_client, tweet_id, keys).await {
+    match crate::nostr::find_existing_event(nostr_client, tweet_id, pubkey).await {
         Ok(Some(_event)) => {
             debug!("Tweet {tweet_id} already exists on Nostr relay");
             Ok(true)
@@ -780,7 +970,7 @@ async fn ensure_user_profile_posted(state: &DaemonState, username: &str) -> Resu
         username,
         &state.nostr_client,
         &state.config.data_dir,
-        state.config.mnemonic.as_deref(),
+        &state.config.signers,
     )
     .await?;
 
@@ -809,7 +999,8 @@ async fn post_user_profile(state: &DaemonState, username: &str) -> Result<bool>
         username,
         &state.nostr_client,
         &state.config.data_dir,
-        state.config.mnemonic.as_deref(),
+        &state.config.signers,
+        &state.config.mirror_options,
         &state.config.relays,
     )
     .await
@@ -903,74 +1094,236 @@ pub async fn fetch_timeline_with_retry(
     .map_err(|e| anyhow::anyhow!("Failed after retries: {e}"))
 }
 
+/// Post the tweets this one links to that are not on Nostr yet
+///
+/// Earlier parts of a self-thread go first, root first, then replied-to and
+/// quoted tweets of other authors we can sign for. Those are posted as they
+/// are, without following their own references.
+async fn post_linked_tweets(
+    tweet: &crate::twitter::Tweet,
+    state: &DaemonState,
+    author_signer: &UserSigner,
+) -> Result<u64> {
+    let data_dir = &state.config.data_dir;
+    let parts =
+        thread::load_thread_ancestors(tweet, data_dir, Some(&state.config.twitter_api)).await?;
+
+    let mut posted = 0;
+    for part in parts {
+        if nostr::check_existing_nostr_event(data_dir, &part.id)
+            .await?
+            .is_some()
+            || is_tweet_posted_to_nostr(&part.id, &state.nostr_client, author_signer.public_key())
+                .await?
+        {
+            continue;
+        }
+
+        info!(
+            "Posting earlier part {part_id} of the thread of tweet {tweet_id}",
+            part_id = part.id,
+            tweet_id = tweet.id
+        );
+        post_tweet_to_nostr_with_state(&part, state)
+            .await
+            .with_context(|| format!("Failed to post thread part {id}", id = part.id))?;
+        posted += 1;
+    }
+
+    let self_parent = thread::self_reply_parent(tweet);
+    for ref_tweet in tweet.referenced_tweets.iter().flatten() {
+        if !matches!(ref_tweet.type_field.as_str(), "replied_to" | "quoted")
+            || self_parent == Some(ref_tweet.id.as_str())
+        {
+            continue;
+        }
+        let Some(data) = &ref_tweet.data else {
+            continue;
+        };
+        if data.author.id.is_empty()
+            || nostr::check_existing_nostr_event(data_dir, &ref_tweet.id)
+                .await?
+                .is_some()
+        {
+            continue;
+        }
+
+        let ref_signer = signer::signer_for_user(&data.author, &state.config.signers).await?;
+        if !ref_signer.can_sign()
+            || is_tweet_posted_to_nostr(&ref_tweet.id, &state.nostr_client, ref_signer.public_key())
+                .await?
+        {
+            continue;
+        }
+
+        info!(
+            "Mirroring {type_field} tweet {ref_id} of tweet {tweet_id}",
+            type_field = ref_tweet.type_field,
+            ref_id = ref_tweet.id,
+            tweet_id = tweet.id
+        );
+        post_tweet_to_nostr_with_state(data, state)
+            .await
+            .with_context(|| format!("Failed to mirror tweet {id}", id = ref_tweet.id))?;
+        posted += 1;
+    }
+
+    Ok(posted)
+}
+
+/// Publish a plain retweet as a NIP-18 repost of the original's mirrored note
+///
+/// The original is mirrored first when its author can be signed for. Returns
+/// `None` when there is no note to repost, so the retweet is posted as a
+/// copied text note instead.
+async fn repost_with_state(
+    tweet: &crate::twitter::Tweet,
+    original: &crate::twitter::ReferencedTweet,
+    signer: &UserSigner,
+    state: &DaemonState,
+) -> Result<Option<nostr_sdk::EventId>> {
+    let data_dir = &state.config.data_dir;
+
+    if let Some(data) =
+        tweet_event::original_to_mirror(original, data_dir, &state.config.signers).await?
+    {
+        info!(
+            "Mirroring retweeted tweet {orig_id} before reposting it",
+            orig_id = original.id
+        );
+        if let Err(e) = Box::pin(post_tweet_to_nostr_with_state(data, state)).await {
+            warn!(
+                "Failed to mirror retweeted tweet {orig_id}: {e:#}",
+                orig_id = original.id
+            );
+        }
+    }
+
+    let Some(event) = tweet_event::build_repost_event(tweet, original, signer, data_dir).await?
+    else {
+        return Ok(None);
+    };
+    let output = state.nostr_client.send_event(&event).await?;
+    storage::save_nostr_event(&event, data_dir)?;
+
+    let event_info =
+        nostr::NostrEventInfo::from_event(&tweet.id, &event, Vec::new(), &state.config.relays)?;
+    nostr::save_nostr_event_info(&event_info, data_dir).await?;
+
+    Ok(Some(*output.id()))
+}
+
 /// Post a tweet to Nostr using the daemon state v2
 async fn post_tweet_to_nostr_with_state(
     tweet: &crate::twitter::Tweet,
     state: &DaemonState,
 ) -> Result<nostr_sdk::EventId> {
-    use crate::datetime_utils::parse_rfc3339;
     use crate::media;
-    use nostr_sdk::{EventBuilder, Kind, Timestamp};
 
     let tweet_id = &tweet.id;
 
-    // Get keys for the tweet
-    let keys = crate::keys::get_keys_for_tweet(&tweet.author.id, state.config.mnemonic.as_deref())?;
+    // Get the signer for the tweet author
+    let signer = signer::signer_for_user(&tweet.author, &state.config.signers).await?;
+
+    if state.config.mirror_options.native_reposts
+        && let Some(original) = repost::plain_retweet_of(tweet)
+        && let Some(event_id) = repost_with_state(tweet, original, &signer, state).await?
+    {
+        return Ok(event_id);
+    }
 
     // Extract media URLs
     let tweet_media_urls = media::extract_media_urls_from_tweet(tweet);
 
-    // Upload media to Blossom if configured
-    let media_files = Vec::new(); // For daemon, we assume media is already downloaded
-    let blossom_urls = if !media_files.is_empty() && !state.config.blossom_servers.is_empty() {
-        nostr::upload_media_to_blossom(&media_files, &state.config.blossom_servers, &keys).await?
-    } else {
-        Vec::new()
+    // Media of new tweets was downloaded when they were fetched, so this
+    // mostly finds the files in the cache
+    let media_files: Vec<PathBuf> = match media::download_media(
+        tweet,
+        &state.config.data_dir,
+        Some(state.config.twitter_api.bearer_token()),
+    )
+    .await
+    {
+        Ok(results) => results.into_iter().map(|r| r.file_path).collect(),
+        Err(e) => {
+            warn!("Failed to locate media files of tweet {tweet_id}: {e:#}");
+            Vec::new()
+        }
     };
 
-    // Choose which URLs to include
-    let media_urls = if blossom_urls.is_empty() {
-        tweet_media_urls.clone()
-    } else {
-        blossom_urls.clone()
+    // Upload media through the media backend, falling back to the Twitter URLs
+    let uploaded_media = match media_backend::upload_media(
+        &media_files,
+        &state.config.blossom_servers,
+        &signer,
+        &state.config.data_dir,
+        &state.config.mirror_options,
+    )
+    .await
+    {
+        Ok(uploaded) => {
+            state.stats.write().await.total_media_uploaded += uploaded.len() as u64;
+            uploaded
+        }
+        Err(e) => {
+            warn!("Failed to upload media of tweet {tweet_id}, linking it on Twitter: {e:#}");
+            state.stats.write().await.media_upload_failures += 1;
+            Vec::new()
+        }
     };
+    let uploaded_urls: Vec<String> = uploaded_media.iter().map(|m| m.url.clone()).collect();
+    let described_media =
+        media_backend::describe_media(tweet, &media_files, &tweet_media_urls, uploaded_media).await;
 
-    // Create resolver for mentions
-    let data_dir = Some(state.config.data_dir.to_string_lossy().to_string());
-    let mut resolver =
-        crate::nostr_linking::NostrLinkResolver::new(data_dir, state.config.mnemonic.clone());
-
-    // Format content
-    let (content, mentioned_pubkeys) =
-        nostr::format_tweet_as_nostr_content_with_mentions(tweet, &media_urls, &mut resolver)?;
-
-    // Parse timestamp
-    let tweet_created_at = parse_rfc3339(&tweet.created_at)?.timestamp() as u64;
-    let timestamp = Timestamp::from(tweet_created_at);
-
-    // Create tags
-    let tags = crate::commands::post_tweet_to_nostr::create_nostr_event_tags(
-        tweet_id,
+    let built = tweet_event::build_tweet_event(
+        tweet,
         &tweet_media_urls,
-        &blossom_urls,
-        &mentioned_pubkeys,
-    )?;
-
-    // Build event
-    let builder = EventBuilder::new(Kind::TextNote, content)
-        .custom_created_at(timestamp)
-        .tags(tags);
-
-    let event = builder.sign_with_keys(&keys)?;
+        &uploaded_urls,
+        &described_media,
+        &signer,
+        &state.config.data_dir,
+        &state.config.signers,
+        &state.config.mirror_options,
+    )
+    .await?;
 
-    // Send event to relays
-    let output = state.nostr_client.send_event(&event).await?;
-    let event_id = *output.id();
+    // Send events to relays and save them
+    for event in &built.events {
+        state.nostr_client.send_event(event).await?;
+        storage::save_nostr_event(event, &state.config.data_dir)?;
+    }
 
-    // Save the event
-    storage::save_nostr_event(&event, &state.config.data_dir)?;
+    if let Some(previous) = &built.superseded
+        && let Err(e) = edits::delete_superseded(
+            previous,
+            &signer,
+            &state.nostr_client,
+            &state.config.data_dir,
+            &state.config.relays,
+            state.config.mirror_options.edit_mode,
+        )
+        .await
+    {
+        warn!(
+            "Failed to delete superseded version {previous_id} of tweet {tweet_id}: {e:#}",
+            previous_id = previous.tweet_id
+        );
+    }
 
-    Ok(event_id)
+    // Record them, so later parts of a thread can reference the first
+    let event = built.event()?;
+    let media_urls = tweet_media_urls
+        .iter()
+        .chain(uploaded_urls.iter())
+        .cloned()
+        .collect();
+    let mut event_info =
+        nostr::NostrEventInfo::from_event(tweet_id, event, media_urls, &state.config.relays)?;
+    event_info.media_event_ids = built.media_event_ids();
+    event_info.media_event_kinds = built.media_event_kinds();
+    nostr::save_nostr_event_info(&event_info, &state.config.data_dir).await?;
+
+    Ok(event.id)
 }
 
 // Make DaemonState clonable for concurrent processing
@@ -983,6 +1336,7 @@ impl Clone for DaemonState {
             user_states: self.user_states.clone(),
             stats: self.stats.clone(),
             rate_limiter: self.rate_limiter.clone(),
+            last_deletion_check: self.last_deletion_check.clone(),
         }
     }
 }
@@ -3,13 +3,14 @@ use std::path::Path;
 use tracing::info;
 
 use crate::storage;
-use crate::twitter;
+use crate::twitter::TwitterApi;
 
 /// Fetch a user's profile and save it to a file
-pub async fn execute(username: &str, data_dir: &Path, bearer_token: &str) -> Result<()> {
+pub async fn execute(username: &str, data_dir: &Path, twitter_api: &TwitterApi) -> Result<()> {
     info!("Downloading profile for {username}");
 
-    let client = twitter::TwitterClient::new(data_dir, bearer_token)
+    let client = twitter_api
+        .client(data_dir)
         .context("Failed to initialize Twitter client")?;
     let user = client
         .get_user_by_username(username)
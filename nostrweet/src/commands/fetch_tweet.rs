@@ -5,28 +5,28 @@ use tracing::{debug, info};
 use crate::media;
 use crate::profile_collector;
 use crate::storage;
-use crate::twitter;
+use crate::twitter::{self, TwitterApi};
 
 /// Fetch a single tweet and its media
 pub async fn execute(
     tweet_url_or_id: &str,
     data_dir: &Path,
     skip_profiles: bool,
-    bearer_token: &str,
+    twitter_api: &TwitterApi,
 ) -> Result<()> {
     // Extract tweet ID from URL or use as is
     let tweet_id = twitter::parse_tweet_id(tweet_url_or_id).context("Failed to parse tweet ID")?;
 
     // Use the new helper function that handles loading from cache or fetching from API
     // with automatic enrichment of referenced tweets
-    let tweet = storage::load_or_fetch_tweet(&tweet_id, data_dir, Some(bearer_token))
+    let tweet = storage::load_or_fetch_tweet(&tweet_id, data_dir, Some(twitter_api))
         .await
         .with_context(|| format!("Failed to load or fetch tweet {tweet_id}"))?;
 
     info!("Successfully retrieved tweet data");
 
     // Download media
-    let media_results = media::download_media(&tweet, data_dir, Some(bearer_token))
+    let media_results = media::download_media(&tweet, data_dir, Some(twitter_api.bearer_token()))
         .await
         .context("Failed to download media")?;
 
@@ -83,7 +83,8 @@ pub async fn execute(
             );
 
             let username_vec: Vec<String> = usernames.into_iter().collect();
-            let client = twitter::TwitterClient::new(data_dir, bearer_token)
+            let client = twitter_api
+                .client(data_dir)
                 .context("Failed to initialize Twitter client for profile downloads")?;
 
             match client.download_user_profiles(&username_vec, data_dir).await {
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::{debug, info};
+
+use crate::archive::TwitterArchive;
+use crate::storage;
+
+/// Import tweets and media from a Twitter data archive into the data directory
+///
+/// Tweets are stored like downloaded ones, so `post-user-to-nostr` can post
+/// them without a bearer token. Existing tweet files are left untouched.
+pub async fn execute(archive_path: &Path, data_dir: &Path, skip_media: bool) -> Result<()> {
+    let mut archive = TwitterArchive::open(archive_path)?;
+
+    let user = archive.account()?;
+    info!(
+        "Importing archive of @{username} ({id})",
+        username = user.username,
+        id = user.id
+    );
+
+    // A profile fetched from the API is more recent than the archived one
+    if storage::find_latest_user_profile(&user.username, data_dir)?.is_none() {
+        storage::save_user_profile(&user, data_dir).context("Failed to save user profile")?;
+    } else {
+        debug!(
+            "Keeping cached profile of @{username}",
+            username = user.username
+        );
+    }
+
+    let tweets = archive.tweets(&user)?;
+    let mut imported = 0;
+    let mut media_files = 0;
+    for tweet in &tweets {
+        if storage::find_existing_tweet_json(&tweet.id, data_dir).is_none() {
+            storage::save_tweet(tweet, data_dir)?;
+            imported += 1;
+        }
+
+        if !skip_media {
+            media_files += archive
+                .copy_media(tweet, data_dir)
+                .with_context(|| format!("Failed to import media of tweet {id}", id = tweet.id))?;
+        }
+    }
+
+    info!(
+        "Imported {imported} of {total} archived tweet(s) and {media_files} media file(s)",
+        total = tweets.len()
+    );
+    println!(
+        "Imported {imported} new tweet(s) of @{username} ({skipped} already cached), {media_files} media file(s)",
+        username = user.username,
+        skipped = tweets.len() - imported
+    );
+
+    Ok(())
+}
@@ -0,0 +1,307 @@
+use anyhow::{Context, Result, bail};
+use nostr_sdk::ToBech32;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::key_overrides::{AccountOverride, KeyOverride};
+use crate::signer::{self, Signers};
+use crate::{keys, storage, twitter};
+
+/// A derived Nostr identity for one cached Twitter user
+#[derive(Debug, Serialize)]
+pub struct IdentityEntry {
+    pub username: String,
+    pub user_id: String,
+    /// BIP32 account index derived from the user ID
+    pub account_index: u32,
+    pub npub: String,
+    /// Public key (hex)
+    pub pubkey: String,
+    /// Secret key (bech32), only filled in by `keys export --include-nsec`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nsec: Option<String>,
+    /// Kind of key override (nsec, bunker or pubkey) replacing the derived key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_override: Option<String>,
+}
+
+/// Identity of an account signing through its own bunker, known once the bunker was asked
+fn build_account_bunker_entry(
+    user: &twitter::User,
+    account: &AccountOverride,
+    signers: &Signers,
+) -> Result<Option<IdentityEntry>> {
+    let Some(public_key) = signers.override_public_key(account)? else {
+        warn!(
+            "Pubkey of @{username} is not known yet, its bunker has not been asked",
+            username = user.username
+        );
+        return Ok(None);
+    };
+
+    Ok(Some(IdentityEntry {
+        username: user.username.clone(),
+        user_id: user.id.clone(),
+        account_index: keys::account_index_for_twitter_user(&user.id)?,
+        npub: public_key
+            .to_bech32()
+            .context("Failed to encode public key as npub")?,
+        pubkey: public_key.to_hex(),
+        nsec: None,
+        key_override: Some(account.key.kind().to_string()),
+    }))
+}
+
+/// Identity of a cached user as the signer sees it, including overrides and `--bunker`
+async fn build_identity_entry(
+    user: &twitter::User,
+    signers: &Signers,
+    include_nsec: bool,
+) -> Result<Option<IdentityEntry>> {
+    let account = signers.override_for_account(&user.id, &user.username);
+    // Personal bunkers are not contacted just to list keys
+    if let Some(account) = account
+        && matches!(account.key, KeyOverride::Bunker(_))
+    {
+        return build_account_bunker_entry(user, account, signers);
+    }
+
+    let user_signer = signer::signer_for_user(user, signers).await?;
+    let public_key = user_signer.public_key();
+    let nsec = match user_signer.keys() {
+        Some(user_keys) if include_nsec => Some(
+            user_keys
+                .secret_key()
+                .to_bech32()
+                .context("Failed to encode secret key as nsec")?,
+        ),
+        _ => None,
+    };
+
+    Ok(Some(IdentityEntry {
+        username: user.username.clone(),
+        user_id: user.id.clone(),
+        account_index: keys::account_index_for_twitter_user(&user.id)?,
+        npub: public_key
+            .to_bech32()
+            .context("Failed to encode public key as npub")?,
+        pubkey: public_key.to_hex(),
+        nsec,
+        key_override: account.map(|a| a.key.kind().to_string()),
+    }))
+}
+
+/// Derives the identity of every cached Twitter user
+async fn build_identity_inventory(
+    data_dir: &Path,
+    signers: &Signers,
+    include_nsec: bool,
+) -> Result<Vec<IdentityEntry>> {
+    let mut entries = Vec::new();
+    for user in storage::list_cached_user_profiles(data_dir)? {
+        if let Some(entry) = build_identity_entry(&user, signers, include_nsec).await? {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Warns about Twitter users that would share a key, returns the number of collisions
+fn report_account_collisions(entries: &[IdentityEntry]) -> Result<usize> {
+    // Overridden accounts don't use their derived key, so they can't collide
+    let collisions = keys::find_account_index_collisions(
+        entries
+            .iter()
+            .filter(|e| e.key_override.is_none())
+            .map(|e| e.user_id.as_str()),
+    )?;
+
+    for (account, user_ids) in &collisions {
+        let users: Vec<String> = entries
+            .iter()
+            .filter(|e| user_ids.contains(&e.user_id))
+            .map(|e| {
+                format!(
+                    "@{username} ({user_id})",
+                    username = e.username,
+                    user_id = e.user_id
+                )
+            })
+            .collect();
+        warn!(
+            "Account index {account} is shared by {users}, these users derive the same key",
+            users = users.join(", ")
+        );
+    }
+
+    Ok(collisions.len())
+}
+
+fn print_entries(entries: &[IdentityEntry], format: &str) -> Result<()> {
+    match format {
+        "json" => {
+            let json = serde_json::to_string_pretty(entries)
+                .context("Failed to serialize identities to JSON")?;
+            println!("{json}");
+        }
+        _ => {
+            for entry in entries {
+                println!("@{username}", username = entry.username);
+                println!("  User ID:       {user_id}", user_id = entry.user_id);
+                println!("  Account index: {account}", account = entry.account_index);
+                println!("  npub:          {npub}", npub = entry.npub);
+                println!("  Public key:    {pubkey}", pubkey = entry.pubkey);
+                if let Some(nsec) = &entry.nsec {
+                    println!("  nsec:          {nsec}");
+                }
+                if let Some(key_override) = &entry.key_override {
+                    println!("  Key override:  {key_override}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// List the derived Nostr identity of every cached Twitter user
+pub async fn list(data_dir: &Path, signers: &Signers, format: &str) -> Result<()> {
+    let entries = build_identity_inventory(data_dir, signers, false).await?;
+
+    if entries.is_empty() {
+        info!(
+            "No cached user profiles found in {path}",
+            path = data_dir.display()
+        );
+        return Ok(());
+    }
+
+    print_entries(&entries, format)?;
+
+    let collisions = report_account_collisions(&entries)?;
+    info!(
+        "Listed {count} identities ({scheme} derivation), {collisions} account index collision(s)",
+        count = entries.len(),
+        scheme = signers.keys().scheme()
+    );
+
+    Ok(())
+}
+
+/// Show the derived Nostr identity of a single cached Twitter user
+pub async fn show(username: &str, data_dir: &Path, signers: &Signers, format: &str) -> Result<()> {
+    let username = username.trim_start_matches('@');
+
+    let Some(profile_path) = storage::find_latest_user_profile(username, data_dir)? else {
+        bail!("No cached profile found for @{username}, run fetch-profile first");
+    };
+    let user = storage::load_user_from_file(&profile_path)
+        .with_context(|| format!("Failed to load profile for @{username}"))?;
+
+    let Some(entry) = build_identity_entry(&user, signers, false).await? else {
+        bail!("Pubkey of @{username} is not known yet");
+    };
+    print_entries(std::slice::from_ref(&entry), format)
+}
+
+/// Writes an export readable by the owner only, it may hold secret keys
+fn write_export_file(path: &Path, json: &str, force: bool) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create export file {path}", path = path.display()))?;
+
+    // The mode only applies to new files, an overwritten one keeps its own
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .with_context(|| {
+                format!(
+                    "Failed to restrict permissions of {path}",
+                    path = path.display()
+                )
+            })?;
+    }
+
+    file.write_all(json.as_bytes()).with_context(|| {
+        format!(
+            "Failed to write identities to {path}",
+            path = path.display()
+        )
+    })
+}
+
+/// Export every derived identity as JSON, optionally including secret keys
+pub async fn export(
+    data_dir: &Path,
+    signers: &Signers,
+    include_nsec: bool,
+    yes: bool,
+    output: Option<PathBuf>,
+    force: bool,
+) -> Result<()> {
+    if let Some(output_path) = &output
+        && output_path.exists()
+        && !force
+    {
+        bail!(
+            "{path} already exists, pass --force to overwrite it",
+            path = output_path.display()
+        );
+    }
+
+    if include_nsec && signers.is_remote() {
+        bail!("--include-nsec needs the mnemonic, the remote signer holds the secret keys");
+    }
+
+    if include_nsec && !yes {
+        print!(
+            "This will export the secret key (nsec) of every mirrored account. Type 'export' to continue: "
+        );
+        io::stdout().flush().context("Failed to flush stdout")?;
+
+        let mut input = String::new();
+        io::stdin()
+            .read_line(&mut input)
+            .context("Failed to read user input")?;
+
+        if input.trim() != "export" {
+            info!("Key export cancelled");
+            return Ok(());
+        }
+    }
+
+    let entries = build_identity_inventory(data_dir, signers, include_nsec).await?;
+    report_account_collisions(&entries)?;
+
+    let json =
+        serde_json::to_string_pretty(&entries).context("Failed to serialize identities to JSON")?;
+
+    if let Some(output_path) = output {
+        write_export_file(&output_path, &json, force)?;
+        info!(
+            "Exported {count} identities to {path}",
+            count = entries.len(),
+            path = output_path.display()
+        );
+    } else {
+        println!("{json}");
+    }
+
+    Ok(())
+}
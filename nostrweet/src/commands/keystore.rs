@@ -0,0 +1,121 @@
+use anyhow::{Context, Result, bail, ensure};
+use bip39::{Language, Mnemonic};
+use nostr_sdk::ToBech32;
+use std::path::Path;
+use tracing::info;
+
+use crate::keys::{self, DerivationScheme, KeySource};
+use crate::keystore;
+
+/// Encrypts the mnemonic into `keystore.json` in the data directory
+///
+/// The mnemonic comes from `--mnemonic`, a hidden prompt, or is freshly
+/// generated with `--generate` (and printed once for backup). With
+/// `--bip39-passphrase` the BIP39 passphrase is prompted for and stored too.
+pub async fn init(
+    data_dir: &Path,
+    mnemonic: Option<&str>,
+    bip39_passphrase: bool,
+    generate: bool,
+    log_n: u8,
+    force: bool,
+    passphrase_fd: Option<i32>,
+) -> Result<()> {
+    let path = keystore::keystore_path(data_dir);
+    if path.exists() && !force {
+        bail!(
+            "Keystore already exists at {path}, use --force to overwrite it",
+            path = path.display()
+        );
+    }
+
+    let mnemonic = if generate {
+        let entropy: [u8; 32] = rand::random();
+        let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy)
+            .context("Failed to generate mnemonic")?;
+        println!("Generated mnemonic, write it down and keep it offline:\n\n{mnemonic}\n");
+        mnemonic.to_string()
+    } else if let Some(mnemonic) = mnemonic {
+        mnemonic.to_string()
+    } else {
+        rpassword::prompt_password("Mnemonic: ").context("Failed to read mnemonic")?
+    };
+
+    let bip39_passphrase = if bip39_passphrase {
+        let bip39_passphrase = keystore::read_passphrase("BIP39 passphrase: ", None)?;
+        let confirmation = keystore::read_passphrase("Repeat BIP39 passphrase: ", None)?;
+        ensure!(
+            bip39_passphrase == confirmation,
+            "BIP39 passphrases do not match"
+        );
+        Some(bip39_passphrase)
+    } else {
+        None
+    };
+
+    let passphrase = keystore::read_passphrase("New keystore passphrase: ", passphrase_fd)?;
+    if passphrase_fd.is_none() {
+        let confirmation = keystore::read_passphrase("Repeat passphrase: ", None)?;
+        ensure!(passphrase == confirmation, "Passphrases do not match");
+    }
+    ensure!(
+        !passphrase.is_empty(),
+        "Keystore passphrase must not be empty"
+    );
+
+    let keystore = keystore::encrypt_mnemonic(
+        mnemonic.trim(),
+        bip39_passphrase.as_deref(),
+        &passphrase,
+        log_n,
+    )?;
+    keystore::save_keystore(&keystore, data_dir)?;
+
+    info!(
+        "Encrypted {words}-word mnemonic to {path}",
+        words = keystore.word_count,
+        path = path.display()
+    );
+    println!("Keystore written to {path}", path = path.display());
+    println!("You can now remove NOSTRWEET_MNEMONIC from your environment and .env files.");
+
+    Ok(())
+}
+
+/// Decrypts the keystore and prints the root identity, to check the passphrase
+pub async fn unlock(
+    data_dir: &Path,
+    passphrase_fd: Option<i32>,
+    scheme: DerivationScheme,
+) -> Result<()> {
+    ensure!(
+        keystore::keystore_exists(data_dir),
+        "No keystore found in {path}, run keystore init first",
+        path = data_dir.display()
+    );
+
+    let unlocked = keystore::unlock(data_dir, passphrase_fd)?;
+    let source = KeySource::new(Some(unlocked.mnemonic), scheme)
+        .with_bip39_passphrase(unlocked.bip39_passphrase);
+    let root_keys = source.root_keys()?;
+
+    println!("Keystore unlocked");
+    println!(
+        "  Root npub ({path}): {npub}",
+        path = keys::ROOT_DERIVATION_PATH,
+        npub = root_keys
+            .public_key()
+            .to_bech32()
+            .context("Failed to encode public key as npub")?
+    );
+    println!(
+        "  Derivation: {scheme}{passphrase}",
+        passphrase = if source.has_bip39_passphrase() {
+            " with BIP39 passphrase"
+        } else {
+            ""
+        }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result, bail};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::keys::DerivationScheme;
+use crate::signer::{Signers, UserSigner};
+use crate::{nostr, storage};
+
+/// Record of a published legacy → NIP-06 handover, stored in `migrations/<user_id>.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyMigrationRecord {
+    pub twitter_user_id: String,
+    pub username: String,
+    pub from_scheme: DerivationScheme,
+    pub to_scheme: DerivationScheme,
+    /// Legacy public key (hex)
+    pub old_pubkey: String,
+    /// NIP-06 public key (hex)
+    pub new_pubkey: String,
+    /// Handover note event ID (hex)
+    pub event_id: String,
+    /// Creation time (UNIX timestamp)
+    pub created_at: u64,
+    pub relays: Vec<String>,
+}
+
+fn migration_record_path(data_dir: &Path, twitter_user_id: &str) -> PathBuf {
+    data_dir
+        .join("migrations")
+        .join(format!("{twitter_user_id}.json"))
+}
+
+/// Builds the body of the handover note published from the old pubkey
+pub fn build_handover_content(username: &str, new_pubkey: &PublicKey) -> Result<String> {
+    let npub = new_pubkey
+        .to_bech32()
+        .context("Failed to encode new public key as npub")?;
+    Ok(format!(
+        "This mirror of @{username} has moved to a new Nostr key.\n\nPlease follow nostr:{npub} for future posts."
+    ))
+}
+
+/// Finds every cached Twitter user, derives its legacy and NIP-06 keys and publishes a
+/// signed handover note from the legacy pubkey pointing to the NIP-06 one.
+pub async fn execute(
+    relays: &[String],
+    usernames: &[String],
+    data_dir: &Path,
+    signers: &Signers,
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let mut users = storage::list_cached_user_profiles(data_dir)?;
+    if !usernames.is_empty() {
+        let wanted: Vec<String> = usernames
+            .iter()
+            .map(|u| u.trim_start_matches('@').to_lowercase())
+            .collect();
+        users.retain(|user| wanted.contains(&user.username.to_lowercase()));
+    }
+
+    if users.is_empty() {
+        bail!(
+            "No cached user profiles found in {path}",
+            path = data_dir.display()
+        );
+    }
+
+    info!(
+        "Migrating {count} account(s) from the legacy scheme to NIP-06",
+        count = users.len()
+    );
+
+    let mut migrated = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+
+    for user in &users {
+        let username = &user.username;
+        let old_keys = signers
+            .keys()
+            .derive_keys_with_scheme(&user.id, DerivationScheme::Legacy)?;
+        let new_keys = signers
+            .keys()
+            .derive_keys_with_scheme(&user.id, DerivationScheme::Nip06)?;
+
+        let old_npub = old_keys.public_key().to_bech32()?;
+        let new_npub = new_keys.public_key().to_bech32()?;
+        println!("@{username} ({user_id})", user_id = user.id);
+        println!("  legacy: {old_npub}");
+        println!("  nip06:  {new_npub}");
+
+        let record_path = migration_record_path(data_dir, &user.id);
+        if record_path.exists() && !force {
+            debug!("Handover for @{username} already published, skipping");
+            println!("  already migrated");
+            skipped += 1;
+            continue;
+        }
+
+        let content = build_handover_content(username, &new_keys.public_key())?;
+        let event = EventBuilder::new(Kind::TextNote, content)
+            .tag(Tag::public_key(new_keys.public_key()))
+            .sign(&old_keys)
+            .await
+            .context("Failed to sign handover note")?;
+
+        if dry_run {
+            println!(
+                "  would publish handover {event_id}",
+                event_id = event.id.to_hex()
+            );
+            continue;
+        }
+
+        let client =
+            nostr::initialize_nostr_client(&UserSigner::Local(old_keys.clone()), relays).await?;
+        let result = nostr::publish_nostr_event(&client, &event).await;
+        client.disconnect().await;
+
+        if let Err(e) = result {
+            warn!("Failed to publish handover for @{username}: {e}");
+            failed += 1;
+            continue;
+        }
+
+        storage::save_nostr_event(&event, data_dir)?;
+
+        let record = KeyMigrationRecord {
+            twitter_user_id: user.id.clone(),
+            username: username.clone(),
+            from_scheme: DerivationScheme::Legacy,
+            to_scheme: DerivationScheme::Nip06,
+            old_pubkey: old_keys.public_key().to_hex(),
+            new_pubkey: new_keys.public_key().to_hex(),
+            event_id: event.id.to_hex(),
+            created_at: event.created_at.as_u64(),
+            relays: relays.to_vec(),
+        };
+        if let Some(parent) = record_path.parent() {
+            std::fs::create_dir_all(parent).context("Failed to create migrations directory")?;
+        }
+        let json = serde_json::to_string_pretty(&record)
+            .context("Failed to serialize key migration record")?;
+        std::fs::write(&record_path, json).with_context(|| {
+            format!(
+                "Failed to write key migration record to {path}",
+                path = record_path.display()
+            )
+        })?;
+
+        println!(
+            "  published handover {event_id}",
+            event_id = event.id.to_hex()
+        );
+        migrated += 1;
+    }
+
+    info!("Key migration finished: {migrated} migrated, {skipped} skipped, {failed} failed");
+
+    if failed > 0 {
+        bail!("Failed to publish {failed} handover note(s)");
+    }
+
+    Ok(())
+}
@@ -1,13 +1,22 @@
+pub mod backfill;
+pub mod blossom;
+pub mod bunker;
 pub mod clear_cache;
 pub mod daemon;
 pub mod fetch_profile;
 pub mod fetch_tweet;
+pub mod import_archive;
+pub mod keys;
+pub mod keystore;
 pub mod list_tweets;
+pub mod migrate_keys;
 pub mod post_profile_to_nostr;
 pub mod post_tweet;
 pub mod post_tweet_to_nostr;
 pub mod post_user_to_nostr;
+pub mod serve_nip05;
 pub mod show_tweet;
 pub mod update_relay_list;
 pub mod user_tweets;
 pub mod utils;
+pub mod verify_deletions;
@@ -4,13 +4,16 @@ use anyhow::{Context, Result, bail};
 use nostr_sdk::prelude::*;
 use tracing::{debug, info};
 
-use crate::{keys, nostr, nostr_profile, storage};
+use crate::mirror_options::MirrorOptions;
+use crate::signer::{self, Signers};
+use crate::{nostr, nostr_profile, storage};
 
 pub async fn execute(
     username: &str,
     relays: &[String],
     data_dir: &Path,
-    mnemonic: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
 ) -> Result<()> {
     info!(
         "Attempting to post profile for user '{}' to Nostr.",
@@ -34,18 +37,27 @@ pub async fn execute(
     let user =
         storage::load_user_from_file(&profile_path).context("Failed to load user profile")?;
 
-    // Get Nostr keys
-    let keys = keys::get_keys_for_tweet(&user.id, mnemonic)?;
+    // Get the Nostr signer
+    let signer = signer::signer_for_user(&user, signers).await?;
+    if !signer.can_sign() {
+        bail!("@{username} has a pubkey-only key override, refusing to post their profile");
+    }
 
     // Initialize Nostr client
-    let client = nostr::initialize_nostr_client(&keys, relays).await?;
+    let client = nostr::initialize_nostr_client(&signer, relays).await?;
 
     // Create metadata using the shared function
-    let metadata = nostr_profile::build_nostr_metadata_from_user(&user, username);
+    let metadata = nostr_profile::build_nostr_metadata_from_user(
+        &user,
+        username,
+        mirror_options.nip05_domain.as_deref(),
+    );
 
     // Build the event
-    let event = EventBuilder::metadata(&metadata)
-        .sign(&keys)
+    let event = signer
+        .sign_event(
+            EventBuilder::metadata(&metadata).tag(nostr::profile_proxy_tag(&user.username)?),
+        )
         .await
         .context("Failed to build metadata event")?;
 
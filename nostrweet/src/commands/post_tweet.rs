@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 
+use crate::mirror_options::MirrorOptions;
+use crate::signer::Signers;
+use crate::twitter::TwitterApi;
+
 #[allow(clippy::too_many_arguments)]
 pub async fn execute(
     tweet_url_or_id: &str,
@@ -9,8 +13,9 @@ pub async fn execute(
     data_dir: &Path,
     force: bool,
     skip_profiles: bool,
-    mnemonic: Option<&str>,
-    bearer_token: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
+    twitter_api: Option<&TwitterApi>,
 ) -> Result<()> {
     super::post_tweet_to_nostr::execute(
         tweet_url_or_id,
@@ -19,8 +24,9 @@ pub async fn execute(
         data_dir,
         force,
         skip_profiles,
-        mnemonic,
-        bearer_token,
+        signers,
+        mirror_options,
+        twitter_api,
     )
     .await
     .context("Failed to post tweet to Nostr")
@@ -1,68 +1,205 @@
 use anyhow::{Context, Result, bail, ensure};
-// No Keys import needed as we're using it through the keys module
+// No Keys import needed as signing goes through the signer module
 use std::path::Path;
 use tokio::fs;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-// Import necessary types from nostr_sdk
-use nostr_sdk::{EventBuilder, Kind, Tag};
-
-use crate::datetime_utils::parse_rfc3339;
-use crate::keys;
+use crate::edits;
 use crate::media;
+use crate::media_backend;
+use crate::mirror_options::MirrorOptions;
 use crate::nostr;
 use crate::nostr_profile;
 use crate::profile_collector;
+use crate::repost;
+use crate::signer::{self, Signers};
 use crate::storage;
-use crate::twitter;
-
-/// Creates tags for a Nostr event including original and Blossom media URLs and mentions
-pub fn create_nostr_event_tags(
-    tweet_id: &str,
-    orig_urls: &[String],
-    blossom_urls: &[String],
-    mentioned_pubkeys: &[nostr_sdk::PublicKey],
-) -> Result<Vec<Tag>> {
-    let mut tags = Vec::new();
-
-    // reference original tweet
-    let twitter_url = crate::nostr::build_twitter_status_url(tweet_id);
-    tags.push(Tag::parse(vec!["r", twitter_url.as_str()])?);
-
-    // Add p-tags for mentioned users
-    for pubkey in mentioned_pubkeys {
-        tags.push(Tag::parse(vec!["p", &pubkey.to_hex()])?);
+use crate::thread;
+use crate::tweet_event;
+use crate::twitter::{self, TwitterApi};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    tweet_url_or_id: &str,
+    relays: &[String],
+    blossom_servers: &[String],
+    data_dir: &Path,
+    force: bool,
+    skip_profiles: bool,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
+    twitter_api: Option<&TwitterApi>,
+) -> Result<()> {
+    post_tweet(
+        tweet_url_or_id,
+        relays,
+        blossom_servers,
+        data_dir,
+        force,
+        skip_profiles,
+        signers,
+        mirror_options,
+        twitter_api,
+        true,
+    )
+    .await
+}
+
+/// Mirrors the replied-to and quoted tweets of `tweet` that aren't on Nostr yet
+///
+/// Only tweets whose author we can sign for are posted; the others stay
+/// quoted inline. Failures are logged and fall back to quoting as well.
+#[allow(clippy::too_many_arguments)]
+async fn mirror_referenced_tweets(
+    tweet: &twitter::Tweet,
+    relays: &[String],
+    blossom_servers: &[String],
+    data_dir: &Path,
+    skip_profiles: bool,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
+    twitter_api: Option<&TwitterApi>,
+) -> Result<()> {
+    let self_parent = thread::self_reply_parent(tweet);
+
+    for ref_tweet in tweet.referenced_tweets.iter().flatten() {
+        if !matches!(ref_tweet.type_field.as_str(), "replied_to" | "quoted")
+            || self_parent == Some(ref_tweet.id.as_str())
+            || nostr::check_existing_nostr_event(data_dir, &ref_tweet.id)
+                .await?
+                .is_some()
+        {
+            continue;
+        }
+        let Some(data) = &ref_tweet.data else {
+            continue;
+        };
+        if data.author.id.is_empty()
+            || !signer::signer_for_user(&data.author, signers)
+                .await?
+                .can_sign()
+        {
+            continue;
+        }
+
+        if storage::find_existing_tweet_json(&ref_tweet.id, data_dir).is_none() {
+            storage::save_tweet(data, data_dir)?;
+        }
+
+        info!(
+            "Mirroring {type_field} tweet {ref_id} before tweet {tweet_id}",
+            type_field = ref_tweet.type_field,
+            ref_id = ref_tweet.id,
+            tweet_id = tweet.id
+        );
+        if let Err(e) = Box::pin(post_tweet(
+            &ref_tweet.id,
+            relays,
+            blossom_servers,
+            data_dir,
+            false,
+            skip_profiles,
+            signers,
+            mirror_options,
+            twitter_api,
+            false,
+        ))
+        .await
+        {
+            warn!(
+                "Failed to mirror tweet {ref_id}, quoting it instead: {e:#}",
+                ref_id = ref_tweet.id
+            );
+        }
     }
 
-    // media tagging: if no blossom uploads, tag original URLs as media
-    if blossom_urls.is_empty() {
-        for orig in orig_urls {
-            tags.push(Tag::parse(vec!["media", orig.as_str()])?);
+    Ok(())
+}
+
+/// Publishes a plain retweet as a NIP-18 repost of the original's mirrored note
+///
+/// The original is mirrored first when its author can be signed for. Returns
+/// false when there is no note to repost, so the caller posts the retweet as
+/// a copied text note instead.
+#[allow(clippy::too_many_arguments)]
+async fn post_as_repost(
+    tweet: &twitter::Tweet,
+    original: &twitter::ReferencedTweet,
+    signer: &signer::UserSigner,
+    relays: &[String],
+    blossom_servers: &[String],
+    data_dir: &Path,
+    skip_profiles: bool,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
+    twitter_api: Option<&TwitterApi>,
+) -> Result<bool> {
+    if let Some(data) = tweet_event::original_to_mirror(original, data_dir, signers).await? {
+        if storage::find_existing_tweet_json(&original.id, data_dir).is_none() {
+            storage::save_tweet(data, data_dir)?;
         }
-    } else {
-        // tag source and uploaded media URLs
-        for (orig, bloss) in orig_urls.iter().zip(blossom_urls.iter()) {
-            tags.push(Tag::parse(vec!["source", orig.as_str()])?);
-            tags.push(Tag::parse(vec!["media", bloss.as_str()])?);
+        info!(
+            "Mirroring retweeted tweet {orig_id} before reposting it",
+            orig_id = original.id
+        );
+        if let Err(e) = Box::pin(post_tweet(
+            &original.id,
+            relays,
+            blossom_servers,
+            data_dir,
+            false,
+            skip_profiles,
+            signers,
+            mirror_options,
+            twitter_api,
+            false,
+        ))
+        .await
+        {
+            warn!(
+                "Failed to mirror retweeted tweet {orig_id}: {e:#}",
+                orig_id = original.id
+            );
         }
     }
 
-    // Add client identifier
-    tags.push(Tag::parse(vec!["client", "nostrweet"])?);
+    let Some(event) = tweet_event::build_repost_event(tweet, original, signer, data_dir).await?
+    else {
+        return Ok(false);
+    };
+    storage::save_nostr_event(&event, data_dir).context("Failed to save nostr event locally")?;
+
+    let client = nostr::initialize_nostr_client(signer, relays).await?;
+    nostr::publish_nostr_event(&client, &event).await?;
 
-    Ok(tags)
+    let event_info = nostr::NostrEventInfo::from_event(&tweet.id, &event, Vec::new(), relays)?;
+    nostr::save_nostr_event_info(&event_info, data_dir).await?;
+
+    info!(
+        "Reposted tweet {orig_id} for retweet {tweet_id} with event ID: {event_id}",
+        orig_id = original.id,
+        tweet_id = tweet.id,
+        event_id = event.id.to_hex()
+    );
+    Ok(true)
 }
 
+/// Posts one tweet, first posting the tweets it needs to link to
+///
+/// `mirror_references` is only set for the requested tweet, so mirroring a
+/// referenced tweet doesn't walk the whole conversation.
 #[allow(clippy::too_many_arguments)]
-pub async fn execute(
+async fn post_tweet(
     tweet_url_or_id: &str,
     relays: &[String],
     blossom_servers: &[String],
     data_dir: &Path,
     force: bool,
     skip_profiles: bool,
-    mnemonic: Option<&str>,
-    bearer_token: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
+    twitter_api: Option<&TwitterApi>,
+    mirror_references: bool,
 ) -> Result<()> {
     // Parse tweet ID from URL or ID string
     let tweet_id = twitter::parse_tweet_id(tweet_url_or_id)
@@ -89,7 +226,7 @@ pub async fn execute(
 
     // Use the new helper function that handles loading from cache or fetching from API
     // with automatic enrichment of referenced tweets
-    let tweet = storage::load_or_fetch_tweet(&tweet_id, data_dir, bearer_token)
+    let tweet = storage::load_or_fetch_tweet(&tweet_id, data_dir, twitter_api)
         .await
         .with_context(|| format!("Failed to load or fetch tweet {tweet_id}"))?;
 
@@ -102,12 +239,79 @@ pub async fn execute(
 
     debug!("Using Twitter user ID: {twitter_user_id}");
 
-    // Initialize Nostr keys - either from provided private key or derive from Twitter user ID
-    let keys = keys::get_keys_for_tweet(&twitter_user_id, mnemonic)?;
+    // Earlier parts of a self-thread go first, so this part can reference their events
+    for part in thread::load_thread_ancestors(&tweet, data_dir, twitter_api).await? {
+        if nostr::check_existing_nostr_event(data_dir, &part.id)
+            .await?
+            .is_none()
+        {
+            info!(
+                "Posting earlier part {part_id} of the thread before tweet {tweet_id}",
+                part_id = part.id
+            );
+            Box::pin(post_tweet(
+                &part.id,
+                relays,
+                blossom_servers,
+                data_dir,
+                false,
+                skip_profiles,
+                signers,
+                mirror_options,
+                twitter_api,
+                false,
+            ))
+            .await
+            .with_context(|| format!("Failed to post thread part {id}", id = part.id))?;
+        }
+    }
+
+    if mirror_references {
+        mirror_referenced_tweets(
+            &tweet,
+            relays,
+            blossom_servers,
+            data_dir,
+            skip_profiles,
+            signers,
+            mirror_options,
+            twitter_api,
+        )
+        .await?;
+    }
+
+    // Initialize Nostr signer - either a remote signer or keys derived from Twitter user ID
+    let signer = signer::signer_for_user(&tweet.author, signers).await?;
+
+    if !signer.can_sign() {
+        info!(
+            "Skipping tweet {tweet_id}: Twitter user {twitter_user_id} has a pubkey-only key override"
+        );
+        return Ok(());
+    }
+
+    if mirror_options.native_reposts
+        && let Some(original) = repost::plain_retweet_of(&tweet)
+        && post_as_repost(
+            &tweet,
+            original,
+            &signer,
+            relays,
+            blossom_servers,
+            data_dir,
+            skip_profiles,
+            signers,
+            mirror_options,
+            twitter_api,
+        )
+        .await?
+    {
+        return Ok(());
+    }
 
     debug!(
         "Using Nostr public key: {pubkey}",
-        pubkey = keys.public_key().to_string()
+        pubkey = signer.public_key().to_string()
     );
 
     // Extract all media URLs from tweet and referenced tweets
@@ -137,8 +341,8 @@ pub async fn execute(
     // If we suspect there's a video but don't have direct media URLs, fetch extended media
     if need_extended_media {
         debug!("Fetching extended media information for tweet {tweet_id}");
-        if let Some(bearer) = bearer_token {
-            let twitter_client_result = twitter::TwitterClient::new(data_dir, bearer);
+        if let Some(twitter_api) = twitter_api {
+            let twitter_client_result = twitter_api.client(data_dir);
             if let Ok(twitter_client_instance) = twitter_client_result {
                 let extended_tweet = twitter_client_instance
                     .get_tweet_with_media(&tweet_id)
@@ -191,39 +395,26 @@ pub async fn execute(
         media_files.push(file_path);
     }
 
-    // Upload media if blossom servers provided, else skip and use original URLs
-    let blossom_urls = if !media_files.is_empty() && !blossom_servers.is_empty() {
-        info!(
-            "Uploading {count} media files to Blossom servers",
-            count = media_files.len()
-        );
-        nostr::upload_media_to_blossom(&media_files, blossom_servers, &keys).await?
-    } else {
-        Vec::new()
-    };
-
-    // choose which URLs to include in content: fallback to original if no uploads
-    let media_urls = if blossom_urls.is_empty() {
-        tweet_media_urls.clone()
-    } else {
-        blossom_urls.clone()
-    };
-
-    // Create a resolver for Twitter username to Nostr pubkey mapping
-    let mut resolver = crate::nostr_linking::NostrLinkResolver::new(
-        Some(data_dir.to_string_lossy().to_string()),
-        mnemonic.map(|s| s.to_string()),
-    );
-
-    // Format tweet content for Nostr with mention resolution
-    let (content, mentioned_pubkeys) =
-        nostr::format_tweet_as_nostr_content_with_mentions(&tweet, &media_urls, &mut resolver)?;
+    // Upload media through the media backend, else skip and use original URLs
+    let uploaded_media = media_backend::upload_media(
+        &media_files,
+        blossom_servers,
+        &signer,
+        data_dir,
+        mirror_options,
+    )
+    .await?;
+    let uploaded_urls: Vec<String> = uploaded_media.iter().map(|m| m.url.clone()).collect();
+    let described_media =
+        media_backend::describe_media(&tweet, &media_files, &tweet_media_urls, uploaded_media)
+            .await;
 
     // Create Nostr client and connect to relays
-    let client = nostr::initialize_nostr_client(&keys, relays).await?;
+    let client = nostr::initialize_nostr_client(&signer, relays).await?;
 
     // Check if we've already published this tweet to any of the relays
-    let existing_event = nostr::find_existing_event(&client, &tweet_id, &keys).await?;
+    let existing_event =
+        nostr::find_existing_event(&client, &tweet_id, signer.public_key()).await?;
 
     // Determine whether to use existing event or create a new one
     let create_new_event = if let Some(existing) = &existing_event {
@@ -247,57 +438,29 @@ pub async fn execute(
     };
 
     // Initialize variables for event tracking
-    let (event_id, event_json) = if create_new_event {
+    let (event_id, event_json, media_event_ids, media_event_kinds) = if create_new_event {
         // Create and publish new event
         debug!("Creating new Nostr event for tweet {tweet_id}");
 
-        // Parse the tweet's creation date
-        // First check if the tweet has a creation date
-        if tweet.created_at.is_empty() {
-            bail!(
-                "No creation date found in tweet - cannot create Nostr event without a valid timestamp"
-            );
-        }
-
-        // Parse the ISO 8601 date into a timestamp
-        let tweet_created_at = parse_rfc3339(&tweet.created_at)?.timestamp() as u64;
-
-        debug!("Using tweet creation timestamp: {tweet_created_at}");
-
-        use nostr_sdk::Timestamp;
-        let timestamp = Timestamp::from(tweet_created_at);
-
-        // Create tags for the event
-        let tags = create_nostr_event_tags(
-            &tweet_id,
+        let built = tweet_event::build_tweet_event(
+            &tweet,
             &tweet_media_urls,
-            &blossom_urls,
-            &mentioned_pubkeys,
-        )?;
-
-        // Create a fresh builder with all the necessary components
-        let mut final_builder =
-            EventBuilder::new(Kind::TextNote, content.clone()).custom_created_at(timestamp);
-
-        // Add all the tags - use a reference to avoid moving the tags
-        for tag in &tags {
-            final_builder = final_builder.tag(tag.clone());
+            &uploaded_urls,
+            &described_media,
+            &signer,
+            data_dir,
+            signers,
+            mirror_options,
+        )
+        .await?;
+
+        // Save the events locally before publishing
+        for event in &built.events {
+            storage::save_nostr_event(event, data_dir)
+                .context("Failed to save nostr event locally")?;
         }
+        let event = built.event()?;
 
-        // Now sign it all at once with the keys
-        // We need to use the entire keys object as it implements NostrSigner
-        // Since we're already in an async context, we can just await directly
-        let event = final_builder.sign(&keys).await?;
-
-        // Save the event locally before publishing
-        storage::save_nostr_event(&event, data_dir)
-            .context("Failed to save nostr event locally")?;
-
-        debug!("Successfully created event with tweet's original timestamp");
-        debug!(
-            "Original tweet timestamp: {timestamp} (unix: {unix_timestamp})",
-            unix_timestamp = timestamp.as_u64()
-        );
         debug!(
             "Event timestamp: {event_timestamp}",
             event_timestamp = event.created_at
@@ -306,15 +469,39 @@ pub async fn execute(
         // Now we have proper idempotency - same tweet will produce the same event ID
 
         // Publish to all relays
-        nostr::publish_nostr_event(&client, &event).await?;
+        for event in &built.events {
+            nostr::publish_nostr_event(&client, event).await?;
+        }
+
+        if let Some(previous) = &built.superseded
+            && let Err(e) = edits::delete_superseded(
+                previous,
+                &signer,
+                &client,
+                data_dir,
+                relays,
+                mirror_options.edit_mode,
+            )
+            .await
+        {
+            warn!(
+                "Failed to delete superseded version {previous_id} of tweet {tweet_id}: {e:#}",
+                previous_id = previous.tweet_id
+            );
+        }
 
         let event_id_hex = event.id.to_hex();
 
         // Serialize the event to JSON
-        let json = serde_json::to_string_pretty(&event)
+        let json = serde_json::to_string_pretty(event)
             .context("Failed to serialize Nostr event to JSON")?;
 
-        (event_id_hex, Some(json))
+        (
+            event_id_hex,
+            Some(json),
+            built.media_event_ids(),
+            built.media_event_kinds(),
+        )
     } else if let Some(existing) = existing_event {
         // Use existing event ID and serialize it
         let event_id_hex = existing.id.to_hex();
@@ -323,11 +510,11 @@ pub async fn execute(
         let json = serde_json::to_string_pretty(&existing)
             .context("Failed to serialize existing Nostr event to JSON")?;
 
-        (event_id_hex, Some(json))
+        (event_id_hex, Some(json), Vec::new(), Vec::new())
     } else {
         // This shouldn't happen, but handle it gracefully
         debug!("No event object available");
-        (String::new(), None)
+        (String::new(), None, Vec::new(), Vec::new())
     };
 
     // Create a record of the event for future reference
@@ -355,20 +542,22 @@ pub async fn execute(
     let event_info = nostr::NostrEventInfo {
         tweet_id: tweet_id.to_string(),
         event_id: event_id.to_string(),
-        pubkey: keys.public_key().to_string(),
+        pubkey: signer.public_key().to_string(),
         created_at: event_timestamp,
         // include both original and Blossom media URLs
-        media_urls: if blossom_urls.is_empty() {
+        media_urls: if uploaded_urls.is_empty() {
             tweet_media_urls.clone()
         } else {
             tweet_media_urls
                 .iter()
-                .chain(blossom_urls.iter())
+                .chain(uploaded_urls.iter())
                 .cloned()
                 .collect()
         },
         relays: relays.to_vec(),
         event_json,
+        media_event_ids,
+        media_event_kinds,
     };
 
     // Save event info to file
@@ -393,7 +582,7 @@ pub async fn execute(
 
             // Filter profiles that need to be posted
             let profiles_to_post = nostr_profile::filter_profiles_to_post(
-                usernames, &client, data_dir, force, mnemonic,
+                usernames, &client, data_dir, force, signers,
             )
             .await?;
 
@@ -403,7 +592,8 @@ pub async fn execute(
                     &profiles_to_post,
                     &client,
                     data_dir,
-                    mnemonic,
+                    signers,
+                    mirror_options,
                 )
                 .await?;
 
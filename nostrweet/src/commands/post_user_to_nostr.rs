@@ -5,9 +5,11 @@ use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
 use crate::commands::post_tweet_to_nostr;
+use crate::mirror_options::MirrorOptions;
 use crate::nostr;
 use crate::nostr_profile;
 use crate::profile_collector;
+use crate::signer::{self, Signers};
 use crate::storage;
 
 /// Find all tweet JSON files for a specific user in the output directory
@@ -79,6 +81,7 @@ pub struct PostUserOptions {
 }
 
 /// Post all cached tweets for a user to Nostr relays with filtering options
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     username: &str,
     relays: &[String],
@@ -86,7 +89,8 @@ pub async fn execute(
     data_dir: &Path,
     force: bool,
     skip_profiles: bool,
-    mnemonic: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
 ) -> Result<()> {
     let options = PostUserOptions {
         force,
@@ -99,7 +103,8 @@ pub async fn execute(
         blossom_servers,
         data_dir,
         options,
-        mnemonic,
+        signers,
+        mirror_options,
     )
     .await
 }
@@ -111,7 +116,8 @@ pub async fn execute_with_options(
     blossom_servers: &[String],
     data_dir: &Path,
     options: PostUserOptions,
-    mnemonic: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
 ) -> Result<()> {
     // Clean username (remove @ if present)
     let username = username.trim_start_matches('@');
@@ -252,7 +258,8 @@ pub async fn execute_with_options(
             data_dir,
             options.force,
             true, // Always skip profiles here, we'll post them all at once at the end
-            mnemonic,
+            signers,
+            mirror_options,
             None, // Bearer token not needed for cached tweets
         )
         .await
@@ -317,13 +324,13 @@ pub async fn execute_with_options(
         );
 
         // We need to get the Nostr keys for the main user
-        // Try to load any tweet to get the author ID
-        let user_id = if let Some(tweet_file) = tweet_files.first() {
+        // Try to load any tweet to get the author
+        let author = if let Some(tweet_file) = tweet_files.first() {
             storage::load_tweet_from_file(tweet_file)
                 .ok()
                 .and_then(|t| {
                     if !t.author.id.is_empty() {
-                        Some(t.author.id)
+                        Some(t.author)
                     } else {
                         None
                     }
@@ -332,11 +339,11 @@ pub async fn execute_with_options(
             None
         };
 
-        // Only proceed if we have a user ID
-        if let Some(uid) = user_id {
+        // Only proceed if we have the author
+        if let Some(author) = author {
             // Initialize Nostr client with the user's keys
-            let keys = crate::keys::get_keys_for_tweet(&uid, mnemonic)?;
-            let client = nostr::initialize_nostr_client(&keys, relays).await?;
+            let signer = signer::signer_for_user(&author, signers).await?;
+            let client = nostr::initialize_nostr_client(&signer, relays).await?;
 
             // Filter profiles that need to be posted
             let profiles_to_post = nostr_profile::filter_profiles_to_post(
@@ -344,7 +351,7 @@ pub async fn execute_with_options(
                 &client,
                 data_dir,
                 options.force,
-                mnemonic,
+                signers,
             )
             .await?;
 
@@ -354,7 +361,8 @@ pub async fn execute_with_options(
                     &profiles_to_post,
                     &client,
                     data_dir,
-                    mnemonic,
+                    signers,
+                    mirror_options,
                 )
                 .await?;
 
@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::{signal, task, time};
+use tracing::{info, warn};
+
+use crate::nip05::{self, Nip05Directory};
+use crate::signer::Signers;
+
+/// Serves `/.well-known/nostr.json` for every cached profile until interrupted
+pub async fn execute(
+    bind: SocketAddr,
+    relays: &[String],
+    data_dir: &Path,
+    signers: &Signers,
+    nip05_domain: Option<&str>,
+) -> Result<()> {
+    if nip05_domain.is_none() {
+        warn!("No --nip05-domain set, published profiles won't reference this server");
+    }
+
+    let directory = Arc::new(Nip05Directory::load(data_dir, relays, signers)?);
+
+    // Pick up accounts fetched while the server runs
+    let refreshed = directory.clone();
+    tokio::spawn(async move {
+        let mut interval = time::interval(nip05::NIP05_REFRESH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let directory = refreshed.clone();
+            match task::spawn_blocking(move || directory.refresh()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("Failed to refresh NIP-05 names: {e:#}"),
+                Err(e) => warn!("NIP-05 refresh task failed: {e}"),
+            }
+        }
+    });
+
+    let listener = TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind NIP-05 server to {bind}"))?;
+    info!(
+        "Serving NIP-05 identifiers on http://{addr}{path}",
+        addr = listener.local_addr()?,
+        path = nip05::NIP05_PATH
+    );
+
+    nip05::serve(listener, directory, async {
+        if let Err(e) = signal::ctrl_c().await {
+            warn!("Failed to listen for shutdown signal: {e}");
+        }
+        info!("Received shutdown signal, stopping NIP-05 server");
+    })
+    .await
+}
@@ -8,9 +8,12 @@ use tracing::{debug, info};
 
 use crate::{
     datetime_utils, media,
+    mirror_options::MirrorOptions,
     nostr::{self, format_tweet_as_nostr_content_with_mentions},
     nostr_linking::NostrLinkResolver,
-    storage, twitter,
+    signer::Signers,
+    storage,
+    twitter::{self, TwitterApi},
 };
 
 #[derive(Args, Debug)]
@@ -32,8 +35,9 @@ impl ShowTweetCommand {
     pub async fn execute(
         self,
         data_dir: &Path,
-        bearer_token: Option<&str>,
-        mnemonic: Option<&str>,
+        twitter_api: Option<&TwitterApi>,
+        signers: &Signers,
+        mirror_options: &MirrorOptions,
     ) -> Result<()> {
         // Parse tweet ID from input (could be ID or URL)
         let tweet_id = twitter::parse_tweet_id(&self.tweet).with_context(|| {
@@ -44,7 +48,7 @@ impl ShowTweetCommand {
 
         // Use the new helper function that handles loading from cache or fetching from API
         // with automatic enrichment of referenced tweets
-        let tweet = storage::load_or_fetch_tweet(&tweet_id, data_dir, bearer_token)
+        let tweet = storage::load_or_fetch_tweet(&tweet_id, data_dir, twitter_api)
             .await
             .with_context(|| format!("Failed to load or fetch tweet {tweet_id}"))?;
 
@@ -55,11 +59,15 @@ impl ShowTweetCommand {
         // Create a temporary key for demonstration (in real usage, user would provide keys)
         let keys = Keys::generate();
 
-        // Format tweet content for Nostr with resolver (including mnemonic for mention resolution)
+        // Format tweet content for Nostr with resolver (including signers for mention resolution)
         let data_dir_str = Some(data_dir.to_string_lossy().to_string());
-        let mut resolver = NostrLinkResolver::new(data_dir_str, mnemonic.map(|s| s.to_string()));
-        let (content, _mentioned_pubkeys) =
-            format_tweet_as_nostr_content_with_mentions(&tweet, &media_urls, &mut resolver)?;
+        let mut resolver = NostrLinkResolver::new(data_dir_str, signers.clone());
+        let (content, _mentioned_pubkeys) = format_tweet_as_nostr_content_with_mentions(
+            &tweet,
+            &media_urls,
+            &mut resolver,
+            &mirror_options.content_templates,
+        )?;
 
         // Parse tweet timestamp
         let timestamp = if let Ok(parsed) = datetime_utils::parse_rfc3339(&tweet.created_at) {
@@ -72,6 +80,7 @@ impl ShowTweetCommand {
         let mut tags = Vec::new();
         let twitter_url = nostr::build_twitter_status_url(&tweet.id);
         tags.push(Tag::parse(vec!["r", &twitter_url])?);
+        tags.push(nostr::tweet_proxy_tag(&tweet.id)?);
 
         // Build event
         let mut builder = EventBuilder::new(Kind::TextNote, content).custom_created_at(timestamp);
@@ -1,29 +1,29 @@
 use anyhow::{Context, Result};
 use tracing::info;
 
-use crate::keys;
 use crate::nostr;
+use crate::signer::Signers;
 
 /// Executes the relay list update command.
-/// Updates the relay list for the master/root key derived from the mnemonic.
-pub async fn execute(relays: &[String], mnemonic: Option<&str>) -> Result<()> {
+/// Updates the relay list of the mnemonic's root identity (`keys::ROOT_DERIVATION_PATH`).
+pub async fn execute(relays: &[String], signers: &Signers) -> Result<()> {
     info!("Updating relay list for master key");
 
-    // Get the master key from mnemonic/private key (using None for root derivation)
-    let keys = keys::get_keys_for_tweet("", mnemonic)?; // Empty string gives us the root key
+    // The root identity, or the bunker's own key with a remote signer
+    let signer = signers.root_signer()?;
 
     // Initialize Nostr client with keys and relays
-    let client = nostr::initialize_nostr_client(&keys, relays)
+    let client = nostr::initialize_nostr_client(&signer, relays)
         .await
         .context("Failed to initialize Nostr client")?;
 
     // Update the relay list
-    nostr::update_relay_list(&client, &keys, relays)
+    nostr::update_relay_list(&client, &signer, relays)
         .await
         .context("Failed to update relay list")?;
 
     info!("Successfully updated relay list for master key");
-    info!("Public key: {public_key}", public_key = keys.public_key());
+    info!("Public key: {public_key}", public_key = signer.public_key());
 
     Ok(())
 }
@@ -6,7 +6,7 @@ use tracing::{debug, info};
 use crate::media;
 use crate::profile_collector;
 use crate::storage;
-use crate::twitter;
+use crate::twitter::TwitterApi;
 
 /// Fetch recent tweets from a user's timeline
 ///
@@ -21,7 +21,7 @@ pub async fn execute(
     max_results: Option<u32>,
     days: Option<u32>,
     skip_profiles: bool,
-    bearer_token: &str,
+    twitter_api: &TwitterApi,
 ) -> Result<()> {
     // Clean username (remove @ if present)
     let username = username.trim_start_matches('@');
@@ -29,7 +29,8 @@ pub async fn execute(
     info!("Fetching recent tweets for user @{username}");
 
     // Create Twitter client
-    let client = twitter::TwitterClient::new(data_dir, bearer_token)
+    let client = twitter_api
+        .client(data_dir)
         .context("Failed to initialize Twitter client")?;
 
     // Fetch tweets from user's timeline
@@ -98,15 +99,16 @@ pub async fn execute(
 
         // Enrich referenced tweets using the centralized helper
         if let Err(e) =
-            storage::ensure_tweet_enriched(&mut tweet_to_save, data_dir, Some(bearer_token)).await
+            storage::ensure_tweet_enriched(&mut tweet_to_save, data_dir, Some(twitter_api)).await
         {
             debug!("Failed to enrich referenced tweets for {tweet_id}: {e}");
         }
 
         // Download media for tweet and its references
-        let media_results = media::download_media(&tweet_to_save, data_dir, Some(bearer_token))
-            .await
-            .with_context(|| format!("Failed to download media for tweet {tweet_id}"))?;
+        let media_results =
+            media::download_media(&tweet_to_save, data_dir, Some(twitter_api.bearer_token()))
+                .await
+                .with_context(|| format!("Failed to download media for tweet {tweet_id}"))?;
         let new_media = media_results.iter().filter(|r| !r.from_cache).count();
         let cached_media = media_results.len() - new_media;
         if new_media > 0 {
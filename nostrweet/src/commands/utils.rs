@@ -112,6 +112,7 @@ pub async fn query_events(
                     Kind::TextNote => "Text Note (1)",
                     Kind::ContactList => "Contact List (3)",
                     Kind::Repost => "Repost (6)",
+                    Kind::GenericRepost => "Generic Repost (16)",
                     Kind::Reaction => "Reaction (7)",
                     _ => &format!("Kind {}", event.kind.as_u16()),
                 };
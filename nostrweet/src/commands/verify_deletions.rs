@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::deletions::{self, DeletionPolicy, DeletionReport};
+use crate::nostr;
+use crate::signer::{Signers, UserSigner};
+use crate::twitter::{TWEET_LOOKUP_LIMIT, TwitterApi};
+
+/// Recheck every mirrored tweet and publish NIP-09 deletions for those removed from Twitter
+///
+/// Tweets are looked up in batches of 100. Only tweets the API reports as not
+/// found count as deleted; protected or suspended accounts are left alone.
+/// Without `usernames` every mirrored account is checked, but only as a dry run.
+pub async fn execute(
+    relays: &[String],
+    usernames: &[String],
+    data_dir: &Path,
+    signers: &Signers,
+    dry_run: bool,
+    twitter_api: &TwitterApi,
+) -> Result<()> {
+    if usernames.is_empty() && !dry_run {
+        warn!("No --user given, only reporting deletions (dry run)");
+    }
+    let dry_run = dry_run || usernames.is_empty();
+    let policy = DeletionPolicy {
+        dry_run,
+        allowlist: usernames.to_vec(),
+    };
+
+    let candidates = deletions::candidates(data_dir, &policy).await?;
+    if candidates.is_empty() {
+        println!("No mirrored tweets to verify");
+        return Ok(());
+    }
+    info!(
+        "Verifying {count} mirrored tweet(s){mode}",
+        count = candidates.len(),
+        mode = if dry_run { " (dry run)" } else { "" }
+    );
+
+    let twitter_client = twitter_api
+        .client(data_dir)
+        .context("Failed to initialize Twitter client")?;
+    // Deletions are signed before publishing, the client only needs a connection
+    let ephemeral = UserSigner::Local(nostr_sdk::Keys::generate());
+    let nostr_client = nostr::initialize_nostr_client(&ephemeral, relays).await?;
+
+    let mut report = DeletionReport::default();
+    for batch in candidates.chunks(TWEET_LOOKUP_LIMIT) {
+        let batch_report = deletions::verify_batch(
+            &twitter_client,
+            &nostr_client,
+            batch,
+            data_dir,
+            relays,
+            &policy,
+            signers,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Verification interrupted after {checked} tweet(s)",
+                checked = report.checked
+            )
+        })?;
+        report.add(batch_report);
+    }
+    nostr_client.disconnect().await;
+
+    println!(
+        "Checked {checked} tweet(s): {missing} deleted on Twitter, {deleted} deletion request(s) published, {skipped} skipped",
+        checked = report.checked,
+        missing = report.missing,
+        deleted = report.deleted,
+        skipped = report.skipped
+    );
+
+    Ok(())
+}
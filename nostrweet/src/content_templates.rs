@@ -0,0 +1,395 @@
+use anyhow::{Context, Result, bail, ensure};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+use crate::nostr;
+use crate::thread;
+use crate::twitter::Tweet;
+
+/// File inside the data directory overriding the layout of mirrored notes
+pub const CONTENT_TEMPLATES_FILENAME: &str = "content_templates.json";
+
+/// Variables templates can refer to as `{name}`
+///
+/// - `author`: `@username` of the tweet author, `User <id>` when only the ID is known
+/// - `text`: the tweet text with expanded links and resolved mentions (empty for retweets)
+/// - `quoted_author`: `nostr:npub1...` or `@username` of the retweeted, quoted or replied-to tweet
+/// - `quoted_text`: its text, expanded like `text`
+/// - `quoted_url`: its Twitter URL
+/// - `references`: every referenced tweet laid out as before templates existed
+/// - `media`: media URLs not already in the text, one per line
+/// - `links`: expanded links of the tweet, one per line
+/// - `date`: when the tweet was posted, e.g. `2024-01-20 15:30:00 UTC`
+/// - `tweet_url`: the Twitter URL of the tweet
+pub const VARIABLES: [&str; 10] = [
+    "author",
+    "text",
+    "quoted_author",
+    "quoted_text",
+    "quoted_url",
+    "references",
+    "media",
+    "links",
+    "date",
+    "tweet_url",
+];
+
+/// Kinds of tweets that get their own template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TweetType {
+    Plain,
+    Reply,
+    Quote,
+    /// A plain retweet without text of its own
+    Retweet,
+    /// A reply of the author to themselves
+    Thread,
+}
+
+impl TweetType {
+    pub fn of(tweet: &Tweet) -> Self {
+        let (is_simple_retweet, _) = nostr::analyze_retweet(tweet);
+        let has_reference = |type_field: &str| {
+            tweet
+                .referenced_tweets
+                .iter()
+                .flatten()
+                .any(|r| r.type_field == type_field)
+        };
+
+        if is_simple_retweet {
+            TweetType::Retweet
+        } else if thread::self_reply_parent(tweet).is_some() {
+            TweetType::Thread
+        } else if has_reference("replied_to") {
+            TweetType::Reply
+        } else if has_reference("quoted") {
+            TweetType::Quote
+        } else {
+            TweetType::Plain
+        }
+    }
+
+    /// Type of the referenced tweet the `quoted_*` variables describe
+    pub fn quoted_reference(self) -> Option<&'static str> {
+        match self {
+            TweetType::Plain => None,
+            TweetType::Reply | TweetType::Thread => Some("replied_to"),
+            TweetType::Quote => Some("quoted"),
+            TweetType::Retweet => Some("retweeted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Variable(&'static str),
+}
+
+/// A note layout with `{variable}` placeholders; `{{` and `}}` stand for literal braces
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses a template, rejecting unknown variables so typos fail at startup
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                bail!("Unclosed {{ in template, write {{{{ for a literal brace")
+                            }
+                        }
+                    }
+                    let name = name.trim();
+                    let Some(variable) = VARIABLES.iter().find(|v| **v == name) else {
+                        bail!(
+                            "Unknown template variable {{{name}}}, expected one of {variables}",
+                            variables = VARIABLES.join(", ")
+                        );
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Variable(*variable));
+                }
+                '}' => bail!("Unmatched }} in template, write }}}} for a literal brace"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    pub fn uses(&self, variable: &str) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Variable(name) if *name == variable))
+    }
+
+    /// Fills in the variables, missing values render as nothing
+    pub fn render(&self, values: &BTreeMap<&str, String>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.as_str(),
+                Part::Variable(name) => values.get(name).map_or("", String::as_str),
+            })
+            .collect()
+    }
+}
+
+/// Layout of notes as it was before templates, ending with the tweet link if `tweet_link`
+fn default_source(tweet_type: TweetType, tweet_link: bool) -> String {
+    let body = match tweet_type {
+        // Retweets only show the retweeted tweet
+        TweetType::Retweet => "{references}",
+        _ => "🐦 {author}: {text}\n\n{references}{media}",
+    };
+    if tweet_link {
+        format!("{body}\nOriginal tweet: {{tweet_url}}")
+    } else {
+        body.to_string()
+    }
+}
+
+/// Contents of `content_templates.json`, every type is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateSources {
+    plain: Option<String>,
+    reply: Option<String>,
+    quote: Option<String>,
+    retweet: Option<String>,
+    thread: Option<String>,
+}
+
+/// One template per tweet type
+///
+/// Types missing from `content_templates.json` keep the built-in layout:
+///
+/// ```json
+/// {
+///   "reply": "{text}\n\n↩️ {quoted_author}: {quoted_text}\n{tweet_url}",
+///   "retweet": "🔁 {quoted_author}: {quoted_text}\n{quoted_url}"
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentTemplates {
+    plain: Template,
+    reply: Template,
+    quote: Template,
+    retweet: Template,
+    thread: Template,
+}
+
+impl ContentTemplates {
+    /// The built-in layouts, `tweet_link` ends them with an "Original tweet:" link
+    pub fn builtin(tweet_link: bool) -> Result<Self> {
+        Self::from_sources(TemplateSources::default(), tweet_link)
+    }
+
+    /// Loads the templates file from the data directory, built-in layouts if it doesn't exist
+    ///
+    /// `tweet_link` only applies to the built-in layouts, templates from the file
+    /// contain `{tweet_url}` where they want it.
+    pub fn load(data_dir: &Path, tweet_link: bool) -> Result<Self> {
+        let path = data_dir.join(CONTENT_TEMPLATES_FILENAME);
+        if !path.exists() {
+            return Self::builtin(tweet_link);
+        }
+
+        let json = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Failed to read content templates from {path}",
+                path = path.display()
+            )
+        })?;
+        let templates = Self::parse(&json, tweet_link)
+            .with_context(|| format!("Failed to parse {path}", path = path.display()))?;
+        info!(
+            "Loaded content templates from {path}",
+            path = path.display()
+        );
+        Ok(templates)
+    }
+
+    pub fn parse(json: &str, tweet_link: bool) -> Result<Self> {
+        let sources: TemplateSources = serde_json::from_str(json)?;
+        Self::from_sources(sources, tweet_link)
+    }
+
+    fn from_sources(sources: TemplateSources, tweet_link: bool) -> Result<Self> {
+        let template = |source: Option<String>, tweet_type: TweetType| {
+            let source = source.unwrap_or_else(|| default_source(tweet_type, tweet_link));
+            ensure!(
+                !source.trim().is_empty(),
+                "Template for {tweet_type:?} is empty"
+            );
+            Template::parse(&source).with_context(|| format!("Invalid {tweet_type:?} template"))
+        };
+
+        Ok(Self {
+            plain: template(sources.plain, TweetType::Plain)?,
+            reply: template(sources.reply, TweetType::Reply)?,
+            quote: template(sources.quote, TweetType::Quote)?,
+            retweet: template(sources.retweet, TweetType::Retweet)?,
+            thread: template(sources.thread, TweetType::Thread)?,
+        })
+    }
+
+    pub fn for_type(&self, tweet_type: TweetType) -> &Template {
+        match tweet_type {
+            TweetType::Plain => &self.plain,
+            TweetType::Reply => &self.reply,
+            TweetType::Quote => &self.quote,
+            TweetType::Retweet => &self.retweet,
+            TweetType::Thread => &self.thread,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    fn values(pairs: &[(&'static str, &str)]) -> BTreeMap<&'static str, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_template() -> Result<()> {
+        let template = Template::parse("{{{ author }}} said: {text}\n{tweet_url}")?;
+        assert!(template.uses("text"));
+        assert!(!template.uses("media"));
+        assert_eq!(
+            template.render(&values(&[("author", "@alice"), ("text", "Hello")])),
+            "{@alice} said: Hello\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_templates() {
+        assert!(Template::parse("{txt}").is_err());
+        assert!(Template::parse("{text").is_err());
+        assert!(Template::parse("text}").is_err());
+    }
+
+    #[test]
+    fn test_load_templates_file() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        assert_eq!(
+            ContentTemplates::load(temp_dir.path(), true)?,
+            ContentTemplates::builtin(true)?
+        );
+
+        std::fs::write(
+            temp_dir.path().join(CONTENT_TEMPLATES_FILENAME),
+            r#"{ "quote": "{text}\n\n> {quoted_text}" }"#,
+        )?;
+        let templates = ContentTemplates::load(temp_dir.path(), true)?;
+        assert_eq!(
+            templates.for_type(TweetType::Quote),
+            &Template::parse("{text}\n\n> {quoted_text}")?
+        );
+        assert_eq!(
+            templates.for_type(TweetType::Plain),
+            ContentTemplates::builtin(true)?.for_type(TweetType::Plain)
+        );
+
+        std::fs::write(
+            temp_dir.path().join(CONTENT_TEMPLATES_FILENAME),
+            r#"{ "repost": "{text}" }"#,
+        )?;
+        assert!(ContentTemplates::load(temp_dir.path(), true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_builtin_tweet_link() -> Result<()> {
+        let with_link = ContentTemplates::builtin(true)?;
+        let without_link = ContentTemplates::builtin(false)?;
+        for tweet_type in [TweetType::Plain, TweetType::Retweet] {
+            assert!(with_link.for_type(tweet_type).uses("tweet_url"));
+            assert!(!without_link.for_type(tweet_type).uses("tweet_url"));
+        }
+
+        // Custom templates keep their own links
+        let custom = ContentTemplates::parse(r#"{ "plain": "{text} {tweet_url}" }"#, false)?;
+        assert!(custom.for_type(TweetType::Plain).uses("tweet_url"));
+        assert!(!custom.for_type(TweetType::Reply).uses("tweet_url"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tweet_type() -> Result<()> {
+        let tweet = |references: serde_json::Value, text: &str| {
+            test_tweet(
+                "2000",
+                "author",
+                serde_json::json!({
+                    "text": text,
+                    "in_reply_to_user_id": "7",
+                    "referenced_tweets": references
+                }),
+            )
+        };
+
+        assert_eq!(
+            TweetType::of(&tweet(serde_json::json!([]), "Hello")?),
+            TweetType::Plain
+        );
+        assert_eq!(
+            TweetType::of(&tweet(
+                serde_json::json!([{ "type": "retweeted", "id": "1" }]),
+                "RT @other: Hello"
+            )?),
+            TweetType::Retweet
+        );
+        assert_eq!(
+            TweetType::of(&tweet(
+                serde_json::json!([{ "type": "replied_to", "id": "1" }, { "type": "quoted", "id": "3" }]),
+                "@other Look"
+            )?),
+            TweetType::Reply
+        );
+        assert_eq!(
+            TweetType::of(&tweet(
+                serde_json::json!([{ "type": "quoted", "id": "1" }]),
+                "Look"
+            )?),
+            TweetType::Quote
+        );
+        Ok(())
+    }
+}
@@ -14,6 +14,9 @@ pub mod formats {
 
     /// Date-only format for display: "2024-01-20"
     pub const DISPLAY_DATE: &str = "%Y-%m-%d";
+
+    /// Legacy Twitter format used by data archives: "Sat Jan 20 15:30:00 +0000 2024"
+    pub const TWITTER_LEGACY: &str = "%a %b %d %H:%M:%S %z %Y";
 }
 
 /// Parse an RFC3339/ISO 8601 datetime string (e.g., from Twitter API)
@@ -23,6 +26,18 @@ pub fn parse_rfc3339(date_str: &str) -> Result<DateTime<Utc>> {
         .with_context(|| format!("Failed to parse RFC3339 date: {date_str}"))
 }
 
+/// Parse a legacy Twitter datetime string (e.g., from a Twitter data archive)
+pub fn parse_twitter_legacy(date_str: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_str(date_str, formats::TWITTER_LEGACY)
+        .map(|dt| dt.with_timezone(&Utc))
+        .with_context(|| format!("Failed to parse Twitter date: {date_str}"))
+}
+
+/// Format a datetime the way the Twitter API v2 returns `created_at`
+pub fn format_rfc3339(datetime: &DateTime<Utc>) -> String {
+    datetime.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
 /// Parse a datetime string in compact format (e.g., "20240120153000")
 pub fn parse_compact_datetime(date_str: &str) -> Result<NaiveDateTime> {
     NaiveDateTime::parse_from_str(date_str, formats::FILENAME_COMPACT)
@@ -93,6 +108,13 @@ mod tests {
         assert_eq!(parsed_tz.timestamp(), 1705764600);
     }
 
+    #[test]
+    fn test_parse_twitter_legacy() {
+        let parsed = parse_twitter_legacy("Sat Jan 20 15:30:00 +0000 2024").unwrap();
+        assert_eq!(parsed.timestamp(), 1705764600);
+        assert_eq!(format_rfc3339(&parsed), "2024-01-20T15:30:00.000Z");
+    }
+
     #[test]
     fn test_parse_compact_datetime() {
         let date_str = "20240120153000";
@@ -0,0 +1,478 @@
+use anyhow::{Context, Result};
+use nostr_sdk::{Client, EventBuilder, Kind, Tag};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::nostr::{self, NostrEventInfo};
+use crate::signer::{self, Signers};
+use crate::storage;
+use crate::twitter::{Tweet, TwitterClient};
+
+/// Directory inside the data directory holding one record per published deletion
+const DELETIONS_DIR: &str = "deletions";
+
+/// Content of the deletion requests, shown by clients that display a reason
+const DELETION_REASON: &str = "Deleted on Twitter";
+
+/// Which mirrored tweets may get deletion requests
+#[derive(Debug, Clone, Default)]
+pub struct DeletionPolicy {
+    /// Log what would be deleted without publishing anything
+    pub dry_run: bool,
+    /// Usernames or Twitter user IDs whose deletions are honoured, empty allows every account
+    pub allowlist: Vec<String>,
+}
+
+impl DeletionPolicy {
+    fn allows(&self, tweet: &Tweet) -> bool {
+        self.allowlist.is_empty()
+            || self.allowlist.iter().any(|allowed| {
+                let allowed = allowed.trim_start_matches('@');
+                allowed.eq_ignore_ascii_case(&tweet.author.username)
+                    || Some(allowed) == author_id(tweet)
+            })
+    }
+}
+
+/// Record of a NIP-09 deletion request published for a mirrored tweet
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletionRecord {
+    pub tweet_id: String,
+    /// Mirrored event that was deleted (hex)
+    pub event_id: String,
+    /// Kind 5 deletion request (hex)
+    pub deletion_event_id: String,
+    /// Author of both events (hex)
+    pub pubkey: String,
+    /// Creation time of the deletion request (UNIX timestamp)
+    pub deleted_at: u64,
+    pub relays: Vec<String>,
+}
+
+/// A mirrored tweet whose existence can be rechecked
+#[derive(Debug)]
+pub struct DeletionCandidate {
+    pub tweet: Tweet,
+    pub event: NostrEventInfo,
+}
+
+/// Counts of one verification pass
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DeletionReport {
+    /// Tweets looked up
+    pub checked: usize,
+    /// Tweets confirmed gone from Twitter
+    pub missing: usize,
+    /// Deletion requests published
+    pub deleted: usize,
+    /// Confirmed deletions that were not published (dry run, no key)
+    pub skipped: usize,
+}
+
+impl DeletionReport {
+    pub fn add(&mut self, other: DeletionReport) {
+        self.checked += other.checked;
+        self.missing += other.missing;
+        self.deleted += other.deleted;
+        self.skipped += other.skipped;
+    }
+}
+
+fn author_id(tweet: &Tweet) -> Option<&str> {
+    if tweet.author.id.is_empty() {
+        tweet.author_id.as_deref()
+    } else {
+        Some(tweet.author.id.as_str())
+    }
+}
+
+pub fn record_path(data_dir: &Path, tweet_id: &str) -> PathBuf {
+    data_dir
+        .join(DELETIONS_DIR)
+        .join(format!("{tweet_id}.json"))
+}
+
+/// Loads the deletion record of a tweet, if its deletion was published before
+pub fn load_record(data_dir: &Path, tweet_id: &str) -> Result<Option<DeletionRecord>> {
+    let path = record_path(data_dir, tweet_id);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read deletion record {path}",
+            path = path.display()
+        )
+    })?;
+    let record = serde_json::from_str(&json).with_context(|| {
+        format!(
+            "Failed to parse deletion record {path}",
+            path = path.display()
+        )
+    })?;
+    Ok(Some(record))
+}
+
+pub fn save_record(data_dir: &Path, record: &DeletionRecord) -> Result<()> {
+    let path = record_path(data_dir, &record.tweet_id);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create deletions directory")?;
+    }
+    let json =
+        serde_json::to_string_pretty(record).context("Failed to serialize deletion record")?;
+    std::fs::write(&path, json).with_context(|| {
+        format!(
+            "Failed to write deletion record to {path}",
+            path = path.display()
+        )
+    })
+}
+
+/// Mirrored tweets without a published deletion whose authors the policy allows
+///
+/// Tweets are taken from the `nostr/<tweet_id>.json` records. Their cached JSON
+/// is needed to know the author, records without one are skipped.
+pub async fn candidates(
+    data_dir: &Path,
+    policy: &DeletionPolicy,
+) -> Result<Vec<DeletionCandidate>> {
+    let nostr_dir = data_dir.join("nostr");
+    if !nostr_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut candidates = Vec::new();
+    for entry in std::fs::read_dir(&nostr_dir).with_context(|| {
+        format!(
+            "Failed to read Nostr records in {path}",
+            path = nostr_dir.display()
+        )
+    })? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(tweet_id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if load_record(data_dir, tweet_id)?.is_some() {
+            continue;
+        }
+
+        let Some(tweet_path) = storage::find_existing_tweet_json(tweet_id, data_dir) else {
+            debug!("Tweet {tweet_id} is not cached, can't tell its author, skipping");
+            continue;
+        };
+        let tweet = storage::load_tweet_from_file(&tweet_path)?;
+        if !policy.allows(&tweet) {
+            continue;
+        }
+        let Some(event) = nostr::check_existing_nostr_event(data_dir, tweet_id).await? else {
+            continue;
+        };
+        candidates.push(DeletionCandidate { tweet, event });
+    }
+
+    // Oldest first, tweet IDs grow over time
+    candidates
+        .sort_by(|a, b| (a.tweet.id.len(), &a.tweet.id).cmp(&(b.tweet.id.len(), &b.tweet.id)));
+    Ok(candidates)
+}
+
+/// Builds the NIP-09 deletion request for a mirrored event
+pub fn deletion_builder(event: &NostrEventInfo, reason: &str) -> Result<EventBuilder> {
+    // Picture and video events published for the tweet go with it
+    let mut tags = event
+        .event_ids()
+        .into_iter()
+        .map(|id| Tag::parse(vec!["e", id]))
+        .collect::<Result<Vec<_>, _>>()?;
+    // One k tag per kind of deleted event, reposts and media events included
+    for kind in event.event_kinds() {
+        tags.push(Tag::parse(vec!["k", kind.to_string().as_str()])?);
+    }
+    tags.push(nostr::tweet_proxy_tag(&event.tweet_id)?);
+    Ok(EventBuilder::new(Kind::EventDeletion, reason).tags(tags))
+}
+
+/// Rechecks one batch of mirrored tweets and deletes the events of those gone
+///
+/// The batch must fit one lookup request (`twitter::TWEET_LOOKUP_LIMIT`).
+/// Deletions are signed with the key of the tweet's author, and only when it
+/// is the key the mirrored event was published with.
+pub async fn verify_batch(
+    twitter_client: &TwitterClient,
+    nostr_client: &Client,
+    batch: &[DeletionCandidate],
+    data_dir: &Path,
+    relays: &[String],
+    policy: &DeletionPolicy,
+    signers: &Signers,
+) -> Result<DeletionReport> {
+    let ids: Vec<String> = batch.iter().map(|c| c.tweet.id.clone()).collect();
+    let lookup = twitter_client.lookup_tweets(&ids).await?;
+
+    let mut report = DeletionReport {
+        checked: ids.len(),
+        ..Default::default()
+    };
+
+    for candidate in batch
+        .iter()
+        .filter(|c| lookup.missing.contains(&c.tweet.id))
+    {
+        let tweet_id = &candidate.tweet.id;
+        let event_id = &candidate.event.event_id;
+        report.missing += 1;
+
+        if policy.dry_run {
+            info!("Tweet {tweet_id} was deleted, would delete Nostr event {event_id} (dry run)");
+            report.skipped += 1;
+            continue;
+        }
+        storage::mark_tweet_as_not_found(tweet_id, data_dir)?;
+
+        let Some(author_id) = author_id(&candidate.tweet) else {
+            warn!("Tweet {tweet_id} was deleted but has no author ID, not deleting {event_id}");
+            report.skipped += 1;
+            continue;
+        };
+        let signer =
+            signer::signer_for_account(author_id, &candidate.tweet.author.username, signers)
+                .await?;
+        if !signer.can_sign() || signer.public_key().to_hex() != candidate.event.pubkey {
+            warn!(
+                "Tweet {tweet_id} was deleted but event {event_id} was published with another key, not deleting it"
+            );
+            report.skipped += 1;
+            continue;
+        }
+
+        let deletion = signer
+            .sign_event(deletion_builder(&candidate.event, DELETION_REASON)?)
+            .await?;
+        storage::save_nostr_event(&deletion, data_dir)
+            .context("Failed to save deletion request locally")?;
+        nostr::publish_nostr_event(nostr_client, &deletion).await?;
+
+        save_record(
+            data_dir,
+            &DeletionRecord {
+                tweet_id: tweet_id.clone(),
+                event_id: event_id.clone(),
+                deletion_event_id: deletion.id.to_hex(),
+                pubkey: candidate.event.pubkey.clone(),
+                deleted_at: deletion.created_at.as_u64(),
+                relays: relays.to_vec(),
+            },
+        )?;
+        info!(
+            "Tweet {tweet_id} was deleted, published deletion request {deletion_id} for event {event_id}",
+            deletion_id = deletion.id.to_hex()
+        );
+        report.deleted += 1;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    async fn mirror(data_dir: &Path, tweet: &Tweet) -> Result<()> {
+        storage::save_tweet(tweet, data_dir)?;
+        let info = NostrEventInfo {
+            tweet_id: tweet.id.clone(),
+            event_id: "01".repeat(32),
+            pubkey: "ab".repeat(32),
+            created_at: 1705764600,
+            media_urls: Vec::new(),
+            relays: Vec::new(),
+            event_json: Some(r#"{ "kind": 6 }"#.to_string()),
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        };
+        nostr::save_nostr_event_info(&info, data_dir).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_deletion_builder() -> Result<()> {
+        let keys = nostr_sdk::Keys::generate();
+        let info = NostrEventInfo {
+            tweet_id: "1000".to_string(),
+            event_id: "01".repeat(32),
+            pubkey: keys.public_key().to_hex(),
+            created_at: 1705764600,
+            media_urls: Vec::new(),
+            relays: Vec::new(),
+            event_json: None,
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        };
+
+        let event = deletion_builder(&info, DELETION_REASON)?.sign_with_keys(&keys)?;
+        assert_eq!(event.kind, Kind::EventDeletion);
+        let tags: Vec<Vec<String>> = event.tags.iter().map(|t| t.clone().to_vec()).collect();
+        assert_eq!(
+            tags,
+            vec![
+                vec!["e".to_string(), "01".repeat(32)],
+                vec!["k".to_string(), "1".to_string()],
+                vec![
+                    "proxy".to_string(),
+                    "https://x.com/i/status/1000".to_string(),
+                    "web".to_string()
+                ]
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deletion_builder_includes_media_events() -> Result<()> {
+        let keys = nostr_sdk::Keys::generate();
+        let info = NostrEventInfo {
+            tweet_id: "1000".to_string(),
+            event_id: "01".repeat(32),
+            pubkey: keys.public_key().to_hex(),
+            created_at: 1705764600,
+            media_urls: Vec::new(),
+            relays: Vec::new(),
+            event_json: Some(r#"{ "kind": 20 }"#.to_string()),
+            // The picture event that replaced the text note is listed twice
+            media_event_ids: vec!["01".repeat(32), "02".repeat(32)],
+            media_event_kinds: vec![20, 21],
+        };
+
+        let event = deletion_builder(&info, DELETION_REASON)?.sign_with_keys(&keys)?;
+        let deleted: Vec<String> = event
+            .tags
+            .iter()
+            .map(|t| t.clone().to_vec())
+            .filter(|t| t[0] == "e")
+            .map(|t| t[1].clone())
+            .collect();
+        assert_eq!(deleted, vec!["01".repeat(32), "02".repeat(32)]);
+
+        let kinds: Vec<String> = event
+            .tags
+            .iter()
+            .map(|t| t.clone().to_vec())
+            .filter(|t| t[0] == "k")
+            .map(|t| t[1].clone())
+            .collect();
+        assert_eq!(kinds, vec!["20", "21"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_candidates_respect_allowlist() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        mirror(
+            temp_dir.path(),
+            &test_tweet("1002", "alice", serde_json::json!({}))?,
+        )
+        .await?;
+        mirror(
+            temp_dir.path(),
+            &test_tweet("1001", "bob", serde_json::json!({}))?,
+        )
+        .await?;
+
+        let all = candidates(temp_dir.path(), &DeletionPolicy::default()).await?;
+        let ids: Vec<&str> = all.iter().map(|c| c.tweet.id.as_str()).collect();
+        assert_eq!(ids, vec!["1001", "1002"]);
+
+        let policy = DeletionPolicy {
+            dry_run: false,
+            allowlist: vec!["@Alice".to_string()],
+        };
+        let allowed = candidates(temp_dir.path(), &policy).await?;
+        assert_eq!(allowed.len(), 1);
+        assert_eq!(allowed[0].tweet.id, "1002");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_batch_dry_run() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        for id in ["1001", "1002", "1003"] {
+            mirror(
+                temp_dir.path(),
+                &test_tweet(id, "alice", serde_json::json!({}))?,
+            )
+            .await?;
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        let lookup = server
+            .mock("GET", "/2/tweets")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "ids".to_string(),
+                "1001,1002,1003".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "data": [{ "id": "1001", "text": "still there" }],
+                    "errors": [
+                        {
+                            "value": "1002",
+                            "resource_id": "1002",
+                            "title": "Not Found Error",
+                            "type": "https://api.twitter.com/2/problems/resource-not-found"
+                        },
+                        {
+                            // Protected accounts are not deletions
+                            "value": "1003",
+                            "resource_id": "1003",
+                            "title": "Authorization Error",
+                            "type": "https://api.twitter.com/2/problems/not-authorized-for-resource"
+                        }
+                    ]
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let twitter_client = TwitterClient::new(temp_dir.path(), "test-token")?
+            .with_api_base(&format!("{url}/2", url = server.url()));
+        let policy = DeletionPolicy {
+            dry_run: true,
+            allowlist: Vec::new(),
+        };
+        let batch = candidates(temp_dir.path(), &policy).await?;
+        let report = verify_batch(
+            &twitter_client,
+            &Client::default(),
+            &batch,
+            temp_dir.path(),
+            &[],
+            &policy,
+            &Signers::default(),
+        )
+        .await?;
+
+        lookup.assert_async().await;
+        assert_eq!(
+            report,
+            DeletionReport {
+                checked: 3,
+                missing: 1,
+                deleted: 0,
+                skipped: 1
+            }
+        );
+        // Dry runs leave no trace, not even the not-found marker
+        assert!(!storage::is_tweet_not_found("1002", temp_dir.path()));
+        assert!(!storage::is_tweet_not_found("1003", temp_dir.path()));
+        assert!(load_record(temp_dir.path(), "1002")?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use nostr_sdk::{Client, Tag};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::deletions::{self, DeletionRecord};
+use crate::nostr::{self, NostrEventInfo};
+use crate::nostr_linking::MirroredEvent;
+use crate::signer::UserSigner;
+use crate::storage;
+use crate::twitter::Tweet;
+
+/// Directory inside the data directory holding the version list of each edited tweet
+const EDITS_DIR: &str = "edits";
+
+/// Content of the deletion requests for superseded versions
+const SUPERSEDED_REASON: &str = "Edited on Twitter";
+
+/// How a new version of an already mirrored tweet is published
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum EditMode {
+    /// Post new versions like any other tweet, without linking them
+    #[default]
+    Off,
+    /// Link the new note to the old one and publish a NIP-09 deletion for the old one
+    Replace,
+    /// Link the new note to the old one as a correction and keep the old one
+    Correction,
+}
+
+impl std::fmt::Display for EditMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditMode::Off => write!(f, "off"),
+            EditMode::Replace => write!(f, "replace"),
+            EditMode::Correction => write!(f, "correction"),
+        }
+    }
+}
+
+/// Every version of an edited tweet seen so far, stored in `edits/<original_id>.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditHistory {
+    /// ID of the first version
+    pub original_id: String,
+    /// Versions in the order they were posted
+    pub versions: Vec<EditVersion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditVersion {
+    pub tweet_id: String,
+    pub created_at: String,
+    pub text: String,
+}
+
+/// IDs of the versions posted before this one, oldest first
+pub fn previous_versions(tweet: &Tweet) -> &[String] {
+    let Some(ids) = &tweet.edit_history_tweet_ids else {
+        return &[];
+    };
+    match ids.iter().position(|id| *id == tweet.id) {
+        Some(index) => &ids[..index],
+        None => &[],
+    }
+}
+
+pub fn history_path(data_dir: &Path, original_id: &str) -> PathBuf {
+    data_dir.join(EDITS_DIR).join(format!("{original_id}.json"))
+}
+
+pub fn load_history(data_dir: &Path, original_id: &str) -> Result<Option<EditHistory>> {
+    let path = history_path(data_dir, original_id);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read edit history {path}", path = path.display()))?;
+    let history = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse edit history {path}", path = path.display()))?;
+    Ok(Some(history))
+}
+
+/// Adds this version of an edited tweet to its edit history
+///
+/// Tweets that were never edited have a single version and are skipped.
+pub fn record_version(tweet: &Tweet, data_dir: &Path) -> Result<()> {
+    let Some(ids) = &tweet.edit_history_tweet_ids else {
+        return Ok(());
+    };
+    let Some(original_id) = ids.first().filter(|_| ids.len() > 1) else {
+        return Ok(());
+    };
+
+    let mut history = load_history(data_dir, original_id)?.unwrap_or_else(|| EditHistory {
+        original_id: original_id.clone(),
+        versions: Vec::new(),
+    });
+    if history.versions.iter().any(|v| v.tweet_id == tweet.id) {
+        return Ok(());
+    }
+
+    let text = match &tweet.note_tweet {
+        Some(note) => note.text.clone(),
+        None => tweet.text.clone(),
+    };
+    history.versions.push(EditVersion {
+        tweet_id: tweet.id.clone(),
+        created_at: tweet.created_at.clone(),
+        text,
+    });
+    // Tweet IDs grow over time
+    history
+        .versions
+        .sort_by(|a, b| (a.tweet_id.len(), &a.tweet_id).cmp(&(b.tweet_id.len(), &b.tweet_id)));
+
+    let path = history_path(data_dir, original_id);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create edits directory")?;
+    }
+    let json =
+        serde_json::to_string_pretty(&history).context("Failed to serialize edit history")?;
+    std::fs::write(&path, json)
+        .with_context(|| format!("Failed to write edit history {path}", path = path.display()))?;
+    debug!(
+        "Recorded version {tweet_id} of edited tweet {original_id}",
+        tweet_id = tweet.id
+    );
+    Ok(())
+}
+
+/// The mirrored event of the newest earlier version, when edits are linked
+pub async fn superseded_event(
+    tweet: &Tweet,
+    data_dir: &Path,
+    edit_mode: EditMode,
+) -> Result<Option<NostrEventInfo>> {
+    if edit_mode == EditMode::Off {
+        return Ok(None);
+    }
+
+    for version_id in previous_versions(tweet).iter().rev() {
+        if let Some(info) = nostr::check_existing_nostr_event(data_dir, version_id).await? {
+            return Ok(Some(info));
+        }
+    }
+    Ok(None)
+}
+
+/// NIP-18 `q` tag pointing at the superseded version of an edited tweet
+pub fn superseded_tag(previous: &NostrEventInfo) -> Result<Tag> {
+    let relay = previous.relays.first().map(String::as_str).unwrap_or("");
+    Ok(Tag::parse(vec![
+        "q",
+        previous.event_id.as_str(),
+        relay,
+        previous.pubkey.as_str(),
+    ])?)
+}
+
+/// Links the previous version in the content, in correction mode
+pub fn add_correction_note(
+    content: &mut String,
+    previous: &NostrEventInfo,
+    edit_mode: EditMode,
+) -> Result<()> {
+    if edit_mode != EditMode::Correction {
+        return Ok(());
+    }
+    let nevent = MirroredEvent::from_info(previous)?.to_nevent()?;
+    content.push_str(&format!("\n\nCorrects nostr:{nevent}"));
+    Ok(())
+}
+
+/// Publishes the NIP-09 deletion of a version superseded by an edit, in replace mode
+///
+/// Only events published with `signer`'s key can be deleted by it; others
+/// are left alone with a warning.
+pub async fn delete_superseded(
+    previous: &NostrEventInfo,
+    signer: &UserSigner,
+    client: &Client,
+    data_dir: &Path,
+    relays: &[String],
+    edit_mode: EditMode,
+) -> Result<()> {
+    if edit_mode != EditMode::Replace
+        || deletions::load_record(data_dir, &previous.tweet_id)?.is_some()
+    {
+        return Ok(());
+    }
+    if signer.public_key().to_hex() != previous.pubkey {
+        warn!(
+            "Superseded event {event_id} of tweet {tweet_id} was published with another key, not deleting it",
+            event_id = previous.event_id,
+            tweet_id = previous.tweet_id
+        );
+        return Ok(());
+    }
+
+    let deletion = signer
+        .sign_event(deletions::deletion_builder(previous, SUPERSEDED_REASON)?)
+        .await?;
+    storage::save_nostr_event(&deletion, data_dir)
+        .context("Failed to save deletion request locally")?;
+    nostr::publish_nostr_event(client, &deletion).await?;
+
+    deletions::save_record(
+        data_dir,
+        &DeletionRecord {
+            tweet_id: previous.tweet_id.clone(),
+            event_id: previous.event_id.clone(),
+            deletion_event_id: deletion.id.to_hex(),
+            pubkey: previous.pubkey.clone(),
+            deleted_at: deletion.created_at.as_u64(),
+            relays: relays.to_vec(),
+        },
+    )?;
+    info!(
+        "Deleted superseded version {tweet_id} of an edited tweet (event {event_id})",
+        tweet_id = previous.tweet_id,
+        event_id = previous.event_id
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    fn version(id: &str, text: &str) -> Result<Tweet> {
+        test_tweet(
+            id,
+            "editor",
+            serde_json::json!({
+                "text": text,
+                "edit_history_tweet_ids": ["1000", "1001", "1002"]
+            }),
+        )
+    }
+
+    #[test]
+    fn test_previous_versions() -> Result<()> {
+        assert_eq!(previous_versions(&version("1002", "v3")?), ["1000", "1001"]);
+        assert!(previous_versions(&version("1000", "v1")?).is_empty());
+
+        let mut unedited = version("2000", "never edited")?;
+        unedited.edit_history_tweet_ids = Some(vec!["2000".to_string()]);
+        assert!(previous_versions(&unedited).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_superseded_tag() -> Result<()> {
+        let previous = NostrEventInfo {
+            tweet_id: "1001".to_string(),
+            event_id: "01".repeat(32),
+            pubkey: "ab".repeat(32),
+            created_at: 1705764600,
+            media_urls: Vec::new(),
+            relays: vec!["wss://relay.example.com".to_string()],
+            event_json: None,
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        };
+        assert_eq!(
+            superseded_tag(&previous)?.to_vec(),
+            vec![
+                "q".to_string(),
+                "01".repeat(32),
+                "wss://relay.example.com".to_string(),
+                "ab".repeat(32)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_version() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        record_version(&version("1002", "third")?, temp_dir.path())?;
+        record_version(&version("1000", "first")?, temp_dir.path())?;
+        // Saving a version again doesn't duplicate it
+        record_version(&version("1002", "third")?, temp_dir.path())?;
+
+        let history = load_history(temp_dir.path(), "1000")?.context("missing edit history")?;
+        let versions: Vec<(&str, &str)> = history
+            .versions
+            .iter()
+            .map(|v| (v.tweet_id.as_str(), v.text.as_str()))
+            .collect();
+        assert_eq!(versions, vec![("1000", "first"), ("1002", "third")]);
+
+        let mut unedited = version("2000", "never edited")?;
+        unedited.edit_history_tweet_ids = Some(vec!["2000".to_string()]);
+        record_version(&unedited, temp_dir.path())?;
+        assert!(load_history(temp_dir.path(), "2000")?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+use nostr_sdk::{Keys, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::remote_signer::{self, BunkerUri};
+use crate::storage;
+
+/// File inside the data directory mapping Twitter accounts to their own Nostr identity
+pub const KEY_OVERRIDES_FILENAME: &str = "key_overrides.json";
+
+/// Nostr identity a Twitter user already controls, used instead of the derived key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyOverride {
+    /// Sign with this secret key (nsec or hex)
+    Nsec(String),
+    /// Sign through the user's own NIP-46 remote signer
+    Bunker(String),
+    /// Only point mentions at this pubkey (npub or hex), never post for the user
+    Pubkey(String),
+}
+
+impl KeyOverride {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KeyOverride::Nsec(_) => "nsec",
+            KeyOverride::Bunker(_) => "bunker",
+            KeyOverride::Pubkey(_) => "pubkey",
+        }
+    }
+
+    /// Parses the stored value, so a broken mapping fails at startup
+    fn validate(&self) -> Result<()> {
+        match self {
+            KeyOverride::Nsec(nsec) => {
+                Keys::parse(nsec).context("Invalid nsec in key override")?;
+            }
+            KeyOverride::Bunker(uri) => {
+                BunkerUri::parse(uri)?;
+            }
+            KeyOverride::Pubkey(pubkey) => {
+                PublicKey::parse(pubkey).context("Invalid pubkey in key override")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A key override together with the Twitter account it applies to
+#[derive(Debug, Clone)]
+pub struct AccountOverride {
+    /// Twitter user ID, when keyed by ID or known from a cached profile
+    pub user_id: Option<String>,
+    /// Lowercase Twitter username, when keyed by username or known from a cached profile
+    pub username: Option<String>,
+    pub key: KeyOverride,
+}
+
+/// Parsed contents of `key_overrides.json`
+///
+/// Entries are keyed by Twitter username (with or without @) or numeric user ID:
+///
+/// ```json
+/// {
+///   "jack": { "nsec": "nsec1..." },
+///   "12": { "bunker": "bunker://<pubkey>?relay=wss://relay.example.com" },
+///   "@alice": { "pubkey": "npub1..." }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct KeyOverrides {
+    data_dir: PathBuf,
+    accounts: Vec<AccountOverride>,
+}
+
+impl KeyOverrides {
+    /// Loads the mapping file from the data directory, empty if it doesn't exist
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(KEY_OVERRIDES_FILENAME);
+        if !path.exists() {
+            return Ok(Self {
+                data_dir: data_dir.to_path_buf(),
+                accounts: Vec::new(),
+            });
+        }
+
+        let json = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Failed to read key overrides from {path}",
+                path = path.display()
+            )
+        })?;
+        let entries: BTreeMap<String, KeyOverride> = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse {path}", path = path.display()))?;
+
+        let overrides = Self::from_entries(data_dir, entries)?;
+        info!(
+            "Loaded {count} key override(s) from {path}",
+            count = overrides.accounts.len(),
+            path = path.display()
+        );
+        Ok(overrides)
+    }
+
+    /// Builds the overrides, filling in the missing ID or username from cached profiles
+    pub fn from_entries(data_dir: &Path, entries: BTreeMap<String, KeyOverride>) -> Result<Self> {
+        let mut accounts = Vec::with_capacity(entries.len());
+
+        for (account, key) in entries {
+            key.validate()
+                .with_context(|| format!("Invalid key override for {account}"))?;
+
+            let account = account.trim_start_matches('@');
+            let entry = if !account.is_empty() && account.chars().all(|c| c.is_ascii_digit()) {
+                AccountOverride {
+                    user_id: Some(account.to_string()),
+                    username: None,
+                    key,
+                }
+            } else {
+                let user_id = match storage::find_latest_user_profile(account, data_dir)? {
+                    Some(path) => Some(storage::load_user_from_file(&path)?.id),
+                    None => {
+                        debug!(
+                            "No cached profile for @{account}, its key override is matched by username"
+                        );
+                        None
+                    }
+                };
+                AccountOverride {
+                    user_id,
+                    username: Some(account.to_lowercase()),
+                    key,
+                }
+            };
+            accounts.push(entry);
+        }
+
+        Ok(Self {
+            data_dir: data_dir.to_path_buf(),
+            accounts,
+        })
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    pub fn for_user_id(&self, twitter_user_id: &str) -> Option<&AccountOverride> {
+        self.accounts
+            .iter()
+            .find(|a| a.user_id.as_deref() == Some(twitter_user_id))
+    }
+
+    pub fn for_username(&self, username: &str) -> Option<&AccountOverride> {
+        let username = username.trim_start_matches('@').to_lowercase();
+        self.accounts
+            .iter()
+            .find(|a| a.username.as_deref() == Some(username.as_str()))
+    }
+
+    /// Override for an account whose ID and username are both known
+    ///
+    /// Username entries only learn the user ID from a profile cached at startup,
+    /// so they are also matched by username. An entry tied to another user ID
+    /// is not, the username changed hands.
+    pub fn for_account(&self, twitter_user_id: &str, username: &str) -> Option<&AccountOverride> {
+        self.for_user_id(twitter_user_id).or_else(|| {
+            self.for_username(username)
+                .filter(|a| a.user_id.as_deref().is_none_or(|id| id == twitter_user_id))
+        })
+    }
+
+    /// Pubkey of an override without network access
+    ///
+    /// Bunker entries are only known once the signer was asked for the pubkey.
+    pub fn public_key(&self, account: &AccountOverride) -> Result<Option<PublicKey>> {
+        match &account.key {
+            KeyOverride::Nsec(nsec) => Ok(Some(
+                Keys::parse(nsec)
+                    .context("Invalid nsec in key override")?
+                    .public_key(),
+            )),
+            KeyOverride::Pubkey(pubkey) => Ok(Some(
+                PublicKey::parse(pubkey).context("Invalid pubkey in key override")?,
+            )),
+            KeyOverride::Bunker(uri) => {
+                let Some(user_id) = &account.user_id else {
+                    return Ok(None);
+                };
+                let uri = BunkerUri::parse(uri)?;
+                Ok(remote_signer::cached_public_key_in(
+                    &self.data_dir,
+                    &uri.remote_public_key,
+                    user_id,
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::ToBech32;
+
+    #[test]
+    fn test_parse_key_override_entries() -> Result<()> {
+        let keys = Keys::generate();
+        let nsec = keys.secret_key().to_bech32()?;
+        let npub = Keys::generate().public_key().to_bech32()?;
+        let json = format!(
+            r#"{{
+                "12345": {{ "nsec": "{nsec}" }},
+                "@Alice": {{ "pubkey": "{npub}" }}
+            }}"#
+        );
+        let entries: BTreeMap<String, KeyOverride> = serde_json::from_str(&json)?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let overrides = KeyOverrides::from_entries(temp_dir.path(), entries)?;
+
+        let by_id = overrides
+            .for_user_id("12345")
+            .context("missing ID override")?;
+        assert_eq!(by_id.key.kind(), "nsec");
+        assert_eq!(overrides.public_key(by_id)?, Some(keys.public_key()));
+
+        let by_username = overrides
+            .for_username("alice")
+            .context("missing username override")?;
+        assert_eq!(by_username.key, KeyOverride::Pubkey(npub.clone()));
+        assert!(by_username.user_id.is_none());
+        assert_eq!(
+            overrides.public_key(by_username)?,
+            Some(PublicKey::parse(&npub)?)
+        );
+
+        assert!(overrides.for_user_id("99999").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_username_override_without_cached_profile() -> Result<()> {
+        let keys = Keys::generate();
+        let entries = BTreeMap::from([(
+            "@Jack".to_string(),
+            KeyOverride::Nsec(keys.secret_key().to_bech32()?),
+        )]);
+        let temp_dir = tempfile::tempdir()?;
+        let overrides = KeyOverrides::from_entries(temp_dir.path(), entries)?;
+
+        // Nothing ties the username to a user ID yet
+        assert!(overrides.for_user_id("12").is_none());
+        let account = overrides
+            .for_account("12", "jack")
+            .context("username override not matched")?;
+        assert_eq!(overrides.public_key(account)?, Some(keys.public_key()));
+        assert!(overrides.for_account("12", "someone_else").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_username_override_of_another_user_id() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let overrides = KeyOverrides {
+            data_dir: temp_dir.path().to_path_buf(),
+            accounts: vec![AccountOverride {
+                user_id: Some("12".to_string()),
+                username: Some("jack".to_string()),
+                key: KeyOverride::Pubkey(Keys::generate().public_key().to_hex()),
+            }],
+        };
+
+        assert!(overrides.for_account("12", "jack").is_some());
+        // Someone else took over the username
+        assert!(overrides.for_account("34", "jack").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_key_override_is_rejected() -> Result<()> {
+        let entries = BTreeMap::from([(
+            "jack".to_string(),
+            KeyOverride::Nsec("not-a-key".to_string()),
+        )]);
+        let temp_dir = tempfile::tempdir()?;
+        assert!(KeyOverrides::from_entries(temp_dir.path(), entries).is_err());
+        Ok(())
+    }
+}
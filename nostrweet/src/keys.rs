@@ -1,51 +1,104 @@
 use anyhow::{Context, Result};
 use bip39::{Language, Mnemonic};
+use clap::ValueEnum;
 use nostr_sdk::Keys;
+use nostr_sdk::nips::nip06::FromMnemonic;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use tracing::debug;
 
+/// Versioned schemes for turning a mnemonic and a Twitter user ID into Nostr keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DerivationScheme {
+    /// v1: SHA-256 of the BIP39 seed and the account index (not reproducible in wallets)
+    #[default]
+    #[value(alias = "v1")]
+    Legacy,
+    /// v2: standard NIP-06 BIP32 derivation at m/44'/1237'/<account>'/0/0
+    #[value(alias = "v2")]
+    Nip06,
+}
+
+impl std::fmt::Display for DerivationScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerivationScheme::Legacy => write!(f, "legacy"),
+            DerivationScheme::Nip06 => write!(f, "nip06"),
+        }
+    }
+}
+
+/// BIP32 path of the root identity of the mnemonic, see [`KeySource::root_keys`]
+pub const ROOT_DERIVATION_PATH: &str = "m/44'/1237'/0'/0/0";
+
+/// Maps a Twitter user ID to its BIP32 account index
+///
+/// The first 4 bytes of SHA-256(user_id) are masked to 31 bits so the index
+/// is valid for hardened derivation. Both schemes use the same index.
+pub fn account_index_for_twitter_user(twitter_user_id: &str) -> Result<u32> {
+    let mut hasher = Sha256::new();
+    hasher.update(twitter_user_id.as_bytes());
+    let hash = hasher.finalize();
+
+    let account_bytes: [u8; 4] = hash[0..4]
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Failed to extract 4 bytes from hash"))?;
+    let account_raw = u32::from_be_bytes(account_bytes);
+    Ok(account_raw & 0x7FFFFFFF)
+}
+
+/// Groups Twitter user IDs whose 31-bit account indices collide
+///
+/// Users sharing an account index would also share a derived key, so every
+/// returned group is a problem that needs an explicit key override.
+pub fn find_account_index_collisions<'a>(
+    twitter_user_ids: impl IntoIterator<Item = &'a str>,
+) -> Result<BTreeMap<u32, Vec<String>>> {
+    let mut by_account: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for user_id in twitter_user_ids {
+        let account = account_index_for_twitter_user(user_id)?;
+        let ids = by_account.entry(account).or_default();
+        if !ids.iter().any(|id| id == user_id) {
+            ids.push(user_id.to_string());
+        }
+    }
+    by_account.retain(|_, ids| ids.len() > 1);
+    Ok(by_account)
+}
+
 /// Derives a deterministic Nostr private key for a Twitter user
-/// using NIP-06 compliant BIP39/BIP32 derivation
-pub fn derive_key_for_twitter_user_with_mnemonic(
+/// using an explicit derivation scheme
+pub fn derive_key_for_twitter_user_with_scheme(
     twitter_user_id: &str,
     mnemonic_str: Option<&str>,
     passphrase: Option<&str>,
+    scheme: DerivationScheme,
 ) -> Result<Keys> {
     // Get the mnemonic from parameter
     let mnemonic_str = mnemonic_str
         .ok_or_else(|| anyhow::anyhow!("Mnemonic not provided. Please use --mnemonic flag or NOSTRWEET_MNEMONIC environment variable."))?;
 
-    // Get passphrase from parameter (default to empty string)
-    let passphrase = passphrase.unwrap_or("");
-
     // Parse the mnemonic
     let mnemonic = Mnemonic::parse_in(Language::English, mnemonic_str)
         .context("Failed to parse mnemonic phrase. Please provide a valid BIP39 mnemonic.")?;
 
-    // Convert Twitter user ID to account index
-    // We use a hash to get a deterministic u32 value within valid BIP32 range
-    let mut hasher = Sha256::new();
-    hasher.update(twitter_user_id.as_bytes());
-    let hash = hasher.finalize();
-
-    // Take first 4 bytes of hash and convert to u32, then ensure it's within valid range
-    // BIP32 allows account indices from 0 to 2^31-1 (hardened derivation)
-    let account_bytes: [u8; 4] = hash[0..4]
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("Failed to extract 4 bytes from hash"))?;
-    let account_raw = u32::from_be_bytes(account_bytes);
-    let account = account_raw & 0x7FFFFFFF; // Ensure it's within 31-bit range
+    let account = account_index_for_twitter_user(twitter_user_id)?;
 
-    debug!("Deriving NIP-06 key for Twitter user {twitter_user_id} with account index {account}");
+    debug!("Deriving {scheme} key for Twitter user {twitter_user_id} with account index {account}");
 
-    // Derive keys using NIP-06 path: m/44'/1237'/<account>'/0/0
-    // Since nostr-sdk doesn't expose from_mnemonic directly in Rust (it's mainly for bindings),
-    // we need to use the seed and derive manually
-    let seed = mnemonic.to_seed(passphrase);
+    match scheme {
+        DerivationScheme::Legacy => derive_legacy_keys(&mnemonic, passphrase, account),
+        DerivationScheme::Nip06 => derive_nip06_keys(mnemonic_str, passphrase, account),
+    }
+}
 
-    // We'll use the seed directly as entropy for now since full BIP32 implementation
-    // would require additional dependencies. For proper NIP-06, we should use
-    // the full derivation path, but for MVP we'll use seed + account as entropy
+/// Legacy (v1) derivation: SHA-256(seed || account)
+///
+/// Kept so identities created before NIP-06 support keep working.
+fn derive_legacy_keys(mnemonic: &Mnemonic, passphrase: Option<&str>, account: u32) -> Result<Keys> {
+    let seed = mnemonic.to_seed(passphrase.unwrap_or(""));
 
     // Combine seed with account for deterministic key
     let mut key_material = Vec::from(&seed[..]);
@@ -66,35 +119,111 @@ pub fn derive_key_for_twitter_user_with_mnemonic(
     Ok(keys)
 }
 
-/// Derives a deterministic Nostr private key for a Twitter user
-/// using NIP-06 compliant BIP39/BIP32 derivation
-/// Requires mnemonic to be provided as a parameter
-pub fn derive_key_for_twitter_user(twitter_user_id: &str, mnemonic: Option<&str>) -> Result<Keys> {
-    derive_key_for_twitter_user_with_mnemonic(twitter_user_id, mnemonic, None)
+/// NIP-06 (v2) derivation along m/44'/1237'/<account>'/0/0
+fn derive_nip06_keys(mnemonic: &str, passphrase: Option<&str>, account: u32) -> Result<Keys> {
+    Keys::from_mnemonic_advanced(mnemonic, passphrase, Some(account), Some(0), Some(0))
+        .with_context(|| format!("Failed to derive NIP-06 keys for account {account}"))
+}
+
+/// Mnemonic and derivation settings the keys of mirrored users come from
+///
+/// Built once at startup from the CLI and passed to everything that derives keys.
+#[derive(Clone, Default)]
+pub struct KeySource {
+    mnemonic: Option<String>,
+    /// Optional BIP39 passphrase (the "25th word"), it changes every derived key
+    bip39_passphrase: Option<String>,
+    scheme: DerivationScheme,
+}
+
+impl KeySource {
+    pub fn new(mnemonic: Option<String>, scheme: DerivationScheme) -> Self {
+        Self {
+            mnemonic,
+            bip39_passphrase: None,
+            scheme,
+        }
+    }
+
+    pub fn with_bip39_passphrase(mut self, bip39_passphrase: Option<String>) -> Self {
+        self.bip39_passphrase = bip39_passphrase;
+        self
+    }
+
+    pub fn mnemonic(&self) -> Option<&str> {
+        self.mnemonic.as_deref()
+    }
+
+    pub fn has_bip39_passphrase(&self) -> bool {
+        self.bip39_passphrase.is_some()
+    }
+
+    pub fn scheme(&self) -> DerivationScheme {
+        self.scheme
+    }
+
+    /// Derives the keys of a Twitter user with the configured scheme
+    pub fn derive_keys(&self, twitter_user_id: &str) -> Result<Keys> {
+        self.derive_keys_with_scheme(twitter_user_id, self.scheme)
+    }
+
+    /// Keys of the mnemonic's own identity, not tied to any Twitter user
+    ///
+    /// Always the first NIP-06 account ([`ROOT_DERIVATION_PATH`]) whatever the
+    /// scheme, so a wallet importing the mnemonic shows the same identity. The
+    /// bunker uses it on the relay connection and `update-relay-list` publishes
+    /// its relay list.
+    pub fn root_keys(&self) -> Result<Keys> {
+        let mnemonic = self.mnemonic().context(
+            "Mnemonic not provided. Please use --mnemonic flag or NOSTRWEET_MNEMONIC environment variable.",
+        )?;
+        derive_nip06_keys(mnemonic, self.bip39_passphrase.as_deref(), 0)
+    }
+
+    /// Derives the keys of a Twitter user with an explicit scheme, e.g. to migrate
+    pub fn derive_keys_with_scheme(
+        &self,
+        twitter_user_id: &str,
+        scheme: DerivationScheme,
+    ) -> Result<Keys> {
+        derive_key_for_twitter_user_with_scheme(
+            twitter_user_id,
+            self.mnemonic(),
+            self.bip39_passphrase.as_deref(),
+            scheme,
+        )
+    }
 }
 
-/// Creates a Keys instance by deriving from the Twitter user ID using NIP-06
-/// Requires mnemonic to be provided as a parameter
-pub fn get_keys_for_tweet(twitter_user_id: &str, mnemonic: Option<&str>) -> Result<Keys> {
-    debug!("Deriving NIP-06 compliant key for Twitter user {twitter_user_id}");
-    derive_key_for_twitter_user(twitter_user_id, mnemonic)
+/// Creates a Keys instance by deriving from the Twitter user ID
+///
+/// Key overrides are applied by [`crate::signer`], this always derives.
+pub fn get_keys_for_tweet(twitter_user_id: &str, keys: &KeySource) -> Result<Keys> {
+    debug!("Deriving key for Twitter user {twitter_user_id}");
+    keys.derive_keys(twitter_user_id)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn test_key_source(scheme: DerivationScheme) -> KeySource {
+        KeySource::new(Some(TEST_MNEMONIC.to_string()), scheme)
+    }
+
     #[test]
     fn test_derive_key_for_twitter_user() -> Result<()> {
-        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let source = test_key_source(DerivationScheme::default());
 
         // Test that same user ID produces same keys
-        let keys1 = derive_key_for_twitter_user_with_mnemonic("123456", Some(test_mnemonic), None)?;
-        let keys2 = derive_key_for_twitter_user_with_mnemonic("123456", Some(test_mnemonic), None)?;
+        let keys1 = source.derive_keys("123456")?;
+        let keys2 = source.derive_keys("123456")?;
         assert_eq!(keys1.public_key(), keys2.public_key());
 
         // Test that different user IDs produce different keys
-        let keys3 = derive_key_for_twitter_user_with_mnemonic("789012", Some(test_mnemonic), None)?;
+        let keys3 = source.derive_keys("789012")?;
         assert_ne!(keys1.public_key(), keys3.public_key());
 
         Ok(())
@@ -102,11 +231,11 @@ mod tests {
 
     #[test]
     fn test_get_keys_for_tweet() -> Result<()> {
-        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let source = test_key_source(DerivationScheme::default());
 
         // Test that both functions derive the same keys
-        let keys1 = derive_key_for_twitter_user_with_mnemonic("123456", Some(test_mnemonic), None)?;
-        let keys2 = derive_key_for_twitter_user_with_mnemonic("123456", Some(test_mnemonic), None)?;
+        let keys1 = get_keys_for_tweet("123456", &source)?;
+        let keys2 = source.derive_keys("123456")?;
         assert_eq!(keys1.public_key(), keys2.public_key());
 
         Ok(())
@@ -114,7 +243,7 @@ mod tests {
 
     #[test]
     fn test_deterministic_account_mapping() -> Result<()> {
-        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let source = test_key_source(DerivationScheme::default());
 
         // Test that numeric Twitter IDs map consistently
         let test_cases = vec![
@@ -123,8 +252,8 @@ mod tests {
         ];
 
         for (id1, id2) in test_cases {
-            let keys1 = derive_key_for_twitter_user_with_mnemonic(id1, Some(test_mnemonic), None)?;
-            let keys2 = derive_key_for_twitter_user_with_mnemonic(id2, Some(test_mnemonic), None)?;
+            let keys1 = source.derive_keys(id1)?;
+            let keys2 = source.derive_keys(id2)?;
 
             if id1 == id2 {
                 assert_eq!(keys1.public_key(), keys2.public_key());
@@ -135,4 +264,127 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_nip06_test_vectors() -> Result<()> {
+        // Test vectors from the NIP-06 specification (account 0)
+        let vectors = [
+            (
+                "leader monkey parrot ring guide accident before fence cannon height naive bean",
+                "7f7ff03d123792d6ac594bfa67bf6d0c0ab55b6b1fdb6249303fe861f1ccba9a",
+                "17162c921dc4d2518f9a101db33695df1afb56ab82f5ff3e5da6eec3ca5cd917",
+            ),
+            (
+                "what bleak badge arrange retreat wolf trade produce cricket blur garlic valid proud rude strong choose busy staff weather area salt hollow arm fade",
+                "c15d739894c81a2fcfd3a2df85a0d2c0dbc47a280d092799f144d73d7ae78add",
+                "d41b22899549e1f3d335a31002cfd382174006e166d3e658e3a5eecdb6463573",
+            ),
+        ];
+
+        for (mnemonic, secret_key, public_key) in vectors {
+            let keys = derive_nip06_keys(mnemonic, None, 0)?;
+            assert_eq!(keys.secret_key().to_secret_hex(), secret_key);
+            assert_eq!(keys.public_key().to_hex(), public_key);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schemes_produce_different_keys() -> Result<()> {
+        let legacy = derive_key_for_twitter_user_with_scheme(
+            "123456",
+            Some(TEST_MNEMONIC),
+            None,
+            DerivationScheme::Legacy,
+        )?;
+        let nip06 = derive_key_for_twitter_user_with_scheme(
+            "123456",
+            Some(TEST_MNEMONIC),
+            None,
+            DerivationScheme::Nip06,
+        )?;
+        assert_ne!(legacy.public_key(), nip06.public_key());
+
+        // The legacy scheme is still the default
+        let default = test_key_source(DerivationScheme::default()).derive_keys("123456")?;
+        assert_eq!(default.public_key(), legacy.public_key());
+
+        let configured = test_key_source(DerivationScheme::Nip06).derive_keys("123456")?;
+        assert_eq!(configured.public_key(), nip06.public_key());
+
+        // An explicit scheme wins over the configured one
+        let migrated = test_key_source(DerivationScheme::Legacy)
+            .derive_keys_with_scheme("123456", DerivationScheme::Nip06)?;
+        assert_eq!(migrated.public_key(), nip06.public_key());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bip39_passphrase_changes_keys() -> Result<()> {
+        let plain = test_key_source(DerivationScheme::default());
+        let with_passphrase = test_key_source(DerivationScheme::default())
+            .with_bip39_passphrase(Some("twenty-fifth word".to_string()));
+
+        assert_ne!(
+            plain.derive_keys("123456")?.public_key(),
+            with_passphrase.derive_keys("123456")?.public_key()
+        );
+        assert_ne!(
+            plain.root_keys()?.public_key(),
+            with_passphrase.root_keys()?.public_key()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_keys_use_first_nip06_account() -> Result<()> {
+        let expected = derive_nip06_keys(TEST_MNEMONIC, None, 0)?;
+        for scheme in [DerivationScheme::Legacy, DerivationScheme::Nip06] {
+            let root = test_key_source(scheme).root_keys()?;
+            assert_eq!(root.public_key(), expected.public_key());
+        }
+
+        // The root identity is not the key of an empty Twitter user ID
+        let empty_user = test_key_source(DerivationScheme::Legacy).derive_keys("")?;
+        assert_ne!(empty_user.public_key(), expected.public_key());
+
+        assert!(KeySource::default().root_keys().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nip06_uses_account_index() -> Result<()> {
+        let account = account_index_for_twitter_user("123456")?;
+        assert!(account < 0x80000000);
+
+        let derived = derive_key_for_twitter_user_with_scheme(
+            "123456",
+            Some(TEST_MNEMONIC),
+            None,
+            DerivationScheme::Nip06,
+        )?;
+        let expected = derive_nip06_keys(TEST_MNEMONIC, None, account)?;
+        assert_eq!(derived.public_key(), expected.public_key());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_account_index_collisions() -> Result<()> {
+        // Distinct IDs practically never collide, duplicates are not collisions
+        let collisions = find_account_index_collisions(["123456", "789012", "123456"])?;
+        assert!(collisions.is_empty());
+
+        // 69235 and 95303 share the first 31 bits of their SHA-256
+        let collisions = find_account_index_collisions(["69235", "123456", "95303"])?;
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(
+            collisions.get(&1092531686),
+            Some(&vec!["69235".to_string(), "95303".to_string()])
+        );
+
+        Ok(())
+    }
 }
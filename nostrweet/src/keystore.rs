@@ -0,0 +1,311 @@
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use bech32::{Bech32, Hrp};
+use bip39::{Language, Mnemonic};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use unicode_normalization::UnicodeNormalization;
+
+/// File inside the data directory that holds the encrypted mnemonic
+pub const KEYSTORE_FILENAME: &str = "keystore.json";
+
+/// Default scrypt cost (2^16 rounds, as recommended by NIP-49)
+pub const DEFAULT_LOG_N: u8 = 16;
+
+/// Lowest scrypt cost a keystore is written with, cheaper ones are easy to brute force
+pub const MIN_LOG_N: u8 = 16;
+
+const NCRYPTSEC_HRP: &str = "ncryptsec";
+const NCRYPTSEC_VERSION: u8 = 0x02;
+/// NIP-49 key security byte: the client does not track whether the key was handled insecurely
+const KEY_SECURITY_UNKNOWN: u8 = 0x02;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Version, log_n, salt, nonce and key security byte
+const HEADER_LEN: usize = 2 + SALT_LEN + NONCE_LEN + 1;
+
+/// Encrypted mnemonic stored in `keystore.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u8,
+    /// NIP-49 encoded BIP39 entropy
+    pub ncryptsec: String,
+    /// Number of words of the stored mnemonic
+    pub word_count: usize,
+    /// NIP-49 encoded BIP39 passphrase, if the mnemonic is used with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bip39_passphrase: Option<String>,
+    /// Creation time (UNIX timestamp)
+    pub created_at: u64,
+}
+
+pub fn keystore_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(KEYSTORE_FILENAME)
+}
+
+pub fn keystore_exists(data_dir: &Path) -> bool {
+    keystore_path(data_dir).exists()
+}
+
+/// Derives the symmetric key from the passphrase as NIP-49 does (NFKC, scrypt r=8 p=1)
+fn derive_symmetric_key(passphrase: &str, salt: &[u8], log_n: u8) -> Result<[u8; 32]> {
+    let passphrase: String = passphrase.nfkc().collect();
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| anyhow!("Invalid scrypt parameters (log_n = {log_n}): {e}"))?;
+
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key from passphrase: {e}"))?;
+    Ok(key)
+}
+
+/// Encrypts `payload` into an `ncryptsec1...` string
+///
+/// The layout matches NIP-49, a 32 byte payload yields a regular ncryptsec.
+pub fn encrypt_payload(payload: &[u8], passphrase: &str, log_n: u8) -> Result<String> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let key = derive_symmetric_key(passphrase, &salt, log_n)?;
+
+    let cipher = XChaCha20Poly1305::new_from_slice(&key)
+        .map_err(|e| anyhow!("Invalid symmetric key: {e}"))?;
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: payload,
+                aad: &[KEY_SECURITY_UNKNOWN],
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt keystore payload"))?;
+
+    let mut data = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    data.push(NCRYPTSEC_VERSION);
+    data.push(log_n);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.push(KEY_SECURITY_UNKNOWN);
+    data.extend_from_slice(&ciphertext);
+
+    let hrp = Hrp::parse(NCRYPTSEC_HRP).context("Invalid ncryptsec prefix")?;
+    bech32::encode::<Bech32>(hrp, &data).context("Failed to encode ncryptsec")
+}
+
+/// Decrypts an `ncryptsec1...` string back into its payload
+pub fn decrypt_payload(ncryptsec: &str, passphrase: &str) -> Result<Vec<u8>> {
+    let (hrp, data) = bech32::decode(ncryptsec.trim()).context("Invalid ncryptsec encoding")?;
+    ensure!(
+        hrp.as_str() == NCRYPTSEC_HRP,
+        "Expected an ncryptsec, got prefix {hrp}"
+    );
+    ensure!(data.len() > HEADER_LEN, "ncryptsec is too short");
+
+    let version = data[0];
+    if version != NCRYPTSEC_VERSION {
+        bail!("Unsupported ncryptsec version {version}");
+    }
+
+    let log_n = data[1];
+    let salt = &data[2..2 + SALT_LEN];
+    let nonce = &data[2 + SALT_LEN..2 + SALT_LEN + NONCE_LEN];
+    let key_security = data[HEADER_LEN - 1];
+    let ciphertext = &data[HEADER_LEN..];
+
+    debug!("Decrypting ncryptsec with log_n = {log_n}");
+    let key = derive_symmetric_key(passphrase, salt, log_n)?;
+
+    let cipher = XChaCha20Poly1305::new_from_slice(&key)
+        .map_err(|e| anyhow!("Invalid symmetric key: {e}"))?;
+    cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &[key_security],
+            },
+        )
+        .map_err(|_| anyhow!("Failed to decrypt keystore, wrong passphrase?"))
+}
+
+/// Encrypts a BIP39 mnemonic and its optional BIP39 passphrase
+///
+/// Only the entropy of the mnemonic is stored.
+pub fn encrypt_mnemonic(
+    mnemonic: &str,
+    bip39_passphrase: Option<&str>,
+    passphrase: &str,
+    log_n: u8,
+) -> Result<Keystore> {
+    ensure!(
+        log_n >= MIN_LOG_N,
+        "Keystore scrypt cost log_n = {log_n} is too low, use at least {MIN_LOG_N}"
+    );
+    let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)
+        .context("Failed to parse mnemonic phrase. Please provide a valid BIP39 mnemonic.")?;
+
+    Ok(Keystore {
+        version: 1,
+        ncryptsec: encrypt_payload(&mnemonic.to_entropy(), passphrase, log_n)?,
+        word_count: mnemonic.word_count(),
+        bip39_passphrase: bip39_passphrase
+            .map(|bip39_passphrase| encrypt_payload(bip39_passphrase.as_bytes(), passphrase, log_n))
+            .transpose()?,
+        created_at: chrono::Utc::now().timestamp() as u64,
+    })
+}
+
+/// Secrets decrypted from a keystore
+pub struct UnlockedKeystore {
+    pub mnemonic: String,
+    pub bip39_passphrase: Option<String>,
+}
+
+/// Decrypts the mnemonic and BIP39 passphrase stored in a keystore
+pub fn decrypt_keystore(keystore: &Keystore, passphrase: &str) -> Result<UnlockedKeystore> {
+    let entropy = decrypt_payload(&keystore.ncryptsec, passphrase)?;
+    let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy)
+        .context("Keystore does not contain valid BIP39 entropy")?;
+
+    let bip39_passphrase = match &keystore.bip39_passphrase {
+        Some(ncryptsec) => Some(
+            String::from_utf8(decrypt_payload(ncryptsec, passphrase)?)
+                .context("Keystore BIP39 passphrase is not valid UTF-8")?,
+        ),
+        None => None,
+    };
+
+    Ok(UnlockedKeystore {
+        mnemonic: mnemonic.to_string(),
+        bip39_passphrase,
+    })
+}
+
+pub fn save_keystore(keystore: &Keystore, data_dir: &Path) -> Result<()> {
+    let path = keystore_path(data_dir);
+    let json = serde_json::to_string_pretty(keystore).context("Failed to serialize keystore")?;
+    std::fs::write(&path, json)
+        .with_context(|| format!("Failed to write keystore to {path}", path = path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).with_context(
+            || {
+                format!(
+                    "Failed to restrict permissions of {path}",
+                    path = path.display()
+                )
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn load_keystore(data_dir: &Path) -> Result<Keystore> {
+    let path = keystore_path(data_dir);
+    let json = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read keystore from {path}", path = path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse keystore {path}", path = path.display()))
+}
+
+/// Reads a passphrase from the given file descriptor, or prompts for it on the terminal
+pub fn read_passphrase(prompt: &str, passphrase_fd: Option<i32>) -> Result<String> {
+    let Some(fd) = passphrase_fd else {
+        return rpassword::prompt_password(prompt).context("Failed to read passphrase");
+    };
+
+    let contents = std::fs::read_to_string(format!("/dev/fd/{fd}"))
+        .with_context(|| format!("Failed to read passphrase from file descriptor {fd}"))?;
+    let passphrase = contents
+        .lines()
+        .next()
+        .with_context(|| format!("No passphrase read from file descriptor {fd}"))?
+        .to_string();
+    ensure!(
+        !passphrase.is_empty(),
+        "Empty passphrase read from file descriptor {fd}"
+    );
+    Ok(passphrase)
+}
+
+/// Loads the keystore from the data directory and decrypts it
+pub fn unlock(data_dir: &Path, passphrase_fd: Option<i32>) -> Result<UnlockedKeystore> {
+    let keystore = load_keystore(data_dir)?;
+    let passphrase = read_passphrase("Keystore passphrase: ", passphrase_fd)?;
+    decrypt_keystore(&keystore, &passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_decrypt_nip49_vector() -> Result<()> {
+        // Test vector from NIP-49
+        let ncryptsec = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+        let secret = decrypt_payload(ncryptsec, "nostr")?;
+        assert_eq!(
+            hex::encode(secret),
+            "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mnemonic_roundtrip() -> Result<()> {
+        let keystore = encrypt_mnemonic(TEST_MNEMONIC, None, "correct horse", MIN_LOG_N)?;
+        assert_eq!(keystore.word_count, 12);
+        assert!(keystore.ncryptsec.starts_with("ncryptsec1"));
+        assert!(keystore.bip39_passphrase.is_none());
+
+        let unlocked = decrypt_keystore(&keystore, "correct horse")?;
+        assert_eq!(unlocked.mnemonic, TEST_MNEMONIC);
+        assert!(unlocked.bip39_passphrase.is_none());
+        assert!(decrypt_keystore(&keystore, "wrong horse").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bip39_passphrase_roundtrip() -> Result<()> {
+        let keystore = encrypt_mnemonic(
+            TEST_MNEMONIC,
+            Some("twenty-fifth word"),
+            "correct horse",
+            MIN_LOG_N,
+        )?;
+        let unlocked = decrypt_keystore(&keystore, "correct horse")?;
+        assert_eq!(
+            unlocked.bip39_passphrase.as_deref(),
+            Some("twenty-fifth word")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_weak_scrypt_cost_is_rejected() {
+        assert!(encrypt_mnemonic(TEST_MNEMONIC, None, "correct horse", MIN_LOG_N - 1).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_keystore() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        assert!(!keystore_exists(temp_dir.path()));
+
+        let keystore = encrypt_mnemonic(TEST_MNEMONIC, None, "passphrase", MIN_LOG_N)?;
+        save_keystore(&keystore, temp_dir.path())?;
+        assert!(keystore_exists(temp_dir.path()));
+
+        let loaded = load_keystore(temp_dir.path())?;
+        assert_eq!(
+            decrypt_keystore(&loaded, "passphrase")?.mnemonic,
+            TEST_MNEMONIC
+        );
+        Ok(())
+    }
+}
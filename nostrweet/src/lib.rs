@@ -1,11 +1,36 @@
+pub mod archive;
+pub mod backfill;
+pub mod blob_ledger;
+pub mod blossom;
+#[cfg(test)]
+mod blossom_mock;
+pub mod content_templates;
 pub mod datetime_utils;
+pub mod deletions;
+pub mod edits;
 pub mod error_utils;
 pub mod filename_utils;
+pub mod key_overrides;
 pub mod keys;
+pub mod keystore;
 pub mod media;
+pub mod media_backend;
+pub mod media_events;
+pub mod media_metadata;
+pub mod mirror_options;
+pub mod nip05;
+pub mod nip96;
 pub mod nostr;
 pub mod nostr_linking;
 pub mod nostr_profile;
 pub mod profile_collector;
+pub mod remote_signer;
+pub mod repost;
+pub mod signer;
 pub mod storage;
+#[cfg(test)]
+mod test_utils;
+pub mod thread;
+pub mod tweet_event;
+pub mod tweet_metadata;
 pub mod twitter;
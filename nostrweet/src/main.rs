@@ -5,17 +5,42 @@ use std::path::PathBuf;
 use tracing::{debug, info};
 use tracing_subscriber::{filter::EnvFilter, fmt, prelude::*};
 
+mod archive;
+mod backfill;
+mod blob_ledger;
+mod blossom;
+#[cfg(test)]
+mod blossom_mock;
 mod commands;
+mod content_templates;
 mod datetime_utils;
+mod deletions;
+mod edits;
 mod error_utils;
 mod filename_utils;
+mod key_overrides;
 mod keys;
+mod keystore;
 mod media;
+mod media_backend;
+mod media_events;
+mod media_metadata;
+mod mirror_options;
+mod nip05;
+mod nip96;
 mod nostr;
 mod nostr_linking;
 mod nostr_profile;
 mod profile_collector;
+mod remote_signer;
+mod repost;
+mod signer;
 mod storage;
+#[cfg(test)]
+mod test_utils;
+mod thread;
+mod tweet_event;
+mod tweet_metadata;
 mod twitter;
 
 #[derive(Parser, Debug)]
@@ -35,10 +60,88 @@ struct Cli {
     #[arg(long, env = "TWITTER_BEARER_TOKEN", global = true)]
     bearer_token: Option<String>,
 
+    /// Twitter API base URL, e.g. a fake Twitter server for offline testing
+    #[arg(long, env = "TWITTER_API_BASE", global = true)]
+    twitter_api_base: Option<String>,
+
     /// BIP39 mnemonic phrase for deriving Nostr keys
     #[arg(short = 'm', long, env = "NOSTRWEET_MNEMONIC", global = true)]
     mnemonic: Option<String>,
 
+    /// Key derivation scheme: legacy (v1) or nip06 (v2)
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = keys::DerivationScheme::Legacy,
+        env = "NOSTRWEET_DERIVATION_SCHEME",
+        global = true
+    )]
+    derivation_scheme: keys::DerivationScheme,
+
+    /// Prompt for a BIP39 passphrase applied to the mnemonic when deriving keys
+    /// (`keystore init` stores it in the keystore instead)
+    #[arg(long, global = true)]
+    bip39_passphrase: bool,
+
+    /// Read the keystore passphrase from this file descriptor instead of prompting
+    #[arg(long, env = "NOSTRWEET_PASSPHRASE_FD", global = true)]
+    passphrase_fd: Option<i32>,
+
+    /// Domain mirrored accounts are identified under (nip05 = username@domain)
+    #[arg(long, env = "NOSTRWEET_NIP05_DOMAIN", global = true)]
+    nip05_domain: Option<String>,
+
+    /// Sign through a NIP-46 remote signer (bunker://...) instead of the local mnemonic
+    #[arg(long, env = "NOSTRWEET_BUNKER", global = true)]
+    bunker: Option<String>,
+
+    /// Publish plain retweets as NIP-18 reposts (kind 6, or 16 for non-text originals) of the original author's mirrored note
+    #[arg(long, env = "NOSTRWEET_NATIVE_REPOSTS", global = true)]
+    native_reposts: bool,
+
+    /// How new versions of edited tweets that were already mirrored are published
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = edits::EditMode::Off,
+        env = "NOSTRWEET_EDITS",
+        global = true
+    )]
+    edits: edits::EditMode,
+
+    /// Where tweet media is uploaded before posting: blossom, nip96 or none (link Twitter's copy)
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = media_backend::MediaBackend::Blossom,
+        env = "NOSTRWEET_MEDIA_BACKEND",
+        global = true
+    )]
+    media_backend: media_backend::MediaBackend,
+
+    /// NIP-96 file storage servers for `--media-backend nip96` (comma-separated)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        env = "NOSTRWEET_NIP96_SERVERS",
+        global = true
+    )]
+    nip96_servers: Vec<String>,
+
+    /// How tweets with only images or only videos are published: text-only, auto (NIP-68/71 media event instead of the note) or always-dual (both)
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = media_events::MediaEventPolicy::TextOnly,
+        env = "NOSTRWEET_MEDIA_EVENTS",
+        global = true
+    )]
+    media_events: media_events::MediaEventPolicy,
+
+    /// Leave out the "Original tweet:" link at the end of notes (the NIP-48 proxy tag still names the tweet)
+    #[arg(long, env = "NOSTRWEET_NO_TWEET_LINK", global = true)]
+    no_tweet_link: bool,
+
     /// Verbose output
     #[arg(short, long, global = true)]
     verbose: bool,
@@ -86,6 +189,32 @@ enum Commands {
         skip_profiles: bool,
     },
 
+    /// Download a user's full timeline history, resuming from the last checkpoint
+    Backfill {
+        /// Twitter username (with or without @ symbol)
+        #[arg(required = true)]
+        username: String,
+
+        /// Stop after this many tweets (the API returns at most 3200)
+        #[arg(long, default_value_t = backfill::TIMELINE_HISTORY_LIMIT)]
+        max_tweets: u32,
+
+        /// Discard the saved checkpoint and start again from the newest tweet
+        #[arg(long)]
+        restart: bool,
+    },
+
+    /// Import tweets and media from a Twitter data archive, no API access needed
+    ImportArchive {
+        /// Archive zip file or the directory it was extracted to
+        #[arg(required = true)]
+        path: PathBuf,
+
+        /// Only import tweets, don't copy media files out of the archive
+        #[arg(long)]
+        skip_media: bool,
+    },
+
     /// List all downloaded tweets in the cache
     ListTweets,
 
@@ -233,6 +362,115 @@ enum Commands {
         /// Seconds between polling cycles
         #[arg(short, long, default_value = "300")]
         poll_interval: u64,
+
+        /// Backfill the history of every user while no user is due for polling
+        #[arg(long)]
+        backfill: bool,
+
+        /// Hours between rechecks of the monitored users' mirrored tweets for deletions
+        #[arg(long, value_name = "HOURS")]
+        verify_deletions: Option<u64>,
+
+        /// Log deletions found by --verify-deletions without publishing them
+        #[arg(long, requires = "verify_deletions")]
+        deletions_dry_run: bool,
+    },
+
+    /// Publish handover notes from legacy-scheme keys to their NIP-06 successors
+    MigrateKeys {
+        /// Nostr relay addresses to post to (comma-separated)
+        #[arg(
+            short,
+            long,
+            required = true,
+            value_delimiter = ',',
+            env = "NOSTRWEET_RELAYS"
+        )]
+        relays: Vec<String>,
+
+        /// Only migrate these Twitter usernames (default: every cached profile)
+        #[arg(short, long = "user", action = clap::ArgAction::Append)]
+        users: Vec<String>,
+
+        /// Print the old and new keys without publishing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Publish again even if a handover was already recorded
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Recheck mirrored tweets and publish NIP-09 deletions for those deleted on Twitter
+    VerifyDeletions {
+        /// Nostr relay addresses to post to (comma-separated)
+        #[arg(
+            short,
+            long,
+            required = true,
+            value_delimiter = ',',
+            env = "NOSTRWEET_RELAYS"
+        )]
+        relays: Vec<String>,
+
+        /// Honour deletions of these Twitter usernames (without it, every account is only reported)
+        #[arg(short, long = "user", action = clap::ArgAction::Append)]
+        users: Vec<String>,
+
+        /// Report deleted tweets without publishing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Serve NIP-05 identifiers (/.well-known/nostr.json) for every cached profile
+    ServeNip05 {
+        /// Address to listen on
+        #[arg(
+            short,
+            long,
+            default_value = "127.0.0.1:8080",
+            env = "NOSTRWEET_NIP05_BIND"
+        )]
+        bind: std::net::SocketAddr,
+
+        /// Relays advertised for every account (comma-separated)
+        #[arg(short, long, value_delimiter = ',', env = "NOSTRWEET_RELAYS")]
+        relays: Vec<String>,
+    },
+
+    /// Run a local NIP-46 bunker that signs for mirrored users with the mnemonic
+    Bunker {
+        /// Nostr relay addresses to listen on (comma-separated)
+        #[arg(
+            short,
+            long,
+            required = true,
+            value_delimiter = ',',
+            env = "NOSTRWEET_RELAYS"
+        )]
+        relays: Vec<String>,
+
+        /// Secret clients must present when connecting (random if not set)
+        #[arg(long, env = "NOSTRWEET_BUNKER_SECRET")]
+        secret: Option<String>,
+    },
+
+    /// Manage media stored on Blossom servers
+    Blossom {
+        #[command(subcommand)]
+        command: BlossomCommands,
+    },
+
+    /// Manage the encrypted mnemonic keystore in the data directory
+    Keystore {
+        #[command(subcommand)]
+        command: KeystoreCommands,
+    },
+
+    /// Inspect the Nostr identities derived for cached Twitter users
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommands,
     },
 
     /// Utility commands for Nostr operations
@@ -242,6 +480,90 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum KeysCommands {
+    /// List the npub, pubkey and account index of every cached Twitter user
+    List {
+        /// Output format (json or pretty)
+        #[arg(short = 'f', long, default_value = "pretty")]
+        format: String,
+    },
+
+    /// Show the derived identity of a single Twitter user
+    Show {
+        /// Twitter username (with or without @ symbol)
+        #[arg(required = true)]
+        username: String,
+
+        /// Output format (json or pretty)
+        #[arg(short = 'f', long, default_value = "pretty")]
+        format: String,
+    },
+
+    /// Export every derived identity as JSON
+    Export {
+        /// Include secret keys (nsec) in the export
+        #[arg(long)]
+        include_nsec: bool,
+
+        /// Skip the confirmation prompt when exporting secret keys
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Write the export to this file instead of stdout, readable by the owner only
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BlossomCommands {
+    /// Mirror uploaded media until each blob is on enough of the given servers
+    Repair {
+        /// Blossom servers to keep copies on (comma-separated)
+        #[arg(
+            long,
+            required = true,
+            value_delimiter = ',',
+            env = "NOSTRWEET_BLOSSOM_SERVERS"
+        )]
+        blossom_servers: Vec<String>,
+
+        /// Copies each blob should have (default: one on every server)
+        #[arg(long)]
+        replicas: Option<usize>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeystoreCommands {
+    /// Encrypt the mnemonic (from --mnemonic, a prompt, or --generate) into keystore.json
+    Init {
+        /// Generate a new 24-word mnemonic instead of encrypting an existing one
+        #[arg(long)]
+        generate: bool,
+
+        /// scrypt cost as a power of two, at least 16
+        #[arg(
+            long,
+            default_value_t = keystore::DEFAULT_LOG_N,
+            value_parser = clap::value_parser!(u8).range(i64::from(keystore::MIN_LOG_N)..)
+        )]
+        log_n: u8,
+
+        /// Overwrite an existing keystore
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Check the passphrase by decrypting the keystore and showing the root identity
+    Unlock,
+}
+
 #[derive(Subcommand, Debug)]
 enum UtilsCommands {
     /// Query events from Nostr relays
@@ -305,6 +627,11 @@ async fn main() -> Result<()> {
         debug!("Verbose mode enabled");
     }
 
+    debug!(
+        "Using {scheme} key derivation",
+        scheme = args.derivation_scheme
+    );
+
     // Get data directory, ensuring it's provided (check old env var for backwards compatibility)
     let data_dir = args.data_dir
         .or_else(|| std::env::var("NOSTRWEET_OUTPUT_DIR").ok().map(PathBuf::from))
@@ -324,7 +651,9 @@ async fn main() -> Result<()> {
         Commands::FetchProfile { .. }
             | Commands::FetchTweet { .. }
             | Commands::UserTweets { .. }
+            | Commands::Backfill { .. }
             | Commands::Daemon { .. }
+            | Commands::VerifyDeletions { .. }
     );
 
     // Get bearer token if needed
@@ -335,11 +664,103 @@ async fn main() -> Result<()> {
     } else {
         args.bearer_token
     };
+    let twitter_api = bearer_token.map(|bearer_token| match &args.twitter_api_base {
+        Some(api_base) => {
+            info!("Using Twitter API at {api_base}");
+            twitter::TwitterApi::new(&bearer_token).with_api_base(api_base)
+        }
+        None => twitter::TwitterApi::new(&bearer_token),
+    });
+
+    // Unlock the keystore when a command needs the mnemonic and none was given
+    let needs_mnemonic = matches!(
+        &args.command,
+        Commands::PostTweetToNostr { .. }
+            | Commands::PostUserToNostr { .. }
+            | Commands::PostTweet { .. }
+            | Commands::PostProfileToNostr { .. }
+            | Commands::UpdateRelayList { .. }
+            | Commands::ShowTweet(_)
+            | Commands::Daemon { .. }
+            | Commands::MigrateKeys { .. }
+            | Commands::VerifyDeletions { .. }
+            | Commands::Blossom { .. }
+            | Commands::Bunker { .. }
+            | Commands::ServeNip05 { .. }
+            | Commands::Keys { .. }
+    );
+
+    // Connect to the remote signer for commands that sign events or show their keys
+    let needs_signer = matches!(
+        &args.command,
+        Commands::PostTweetToNostr { .. }
+            | Commands::PostUserToNostr { .. }
+            | Commands::PostTweet { .. }
+            | Commands::PostProfileToNostr { .. }
+            | Commands::UpdateRelayList { .. }
+            | Commands::Daemon { .. }
+            | Commands::VerifyDeletions { .. }
+            | Commands::Blossom { .. }
+            | Commands::Keys { .. }
+    );
+
+    let remote = match &args.bunker {
+        Some(uri) if needs_signer => {
+            let uri = remote_signer::BunkerUri::parse(uri)?;
+            let remote = remote_signer::RemoteSigner::connect(&uri, &data_dir)
+                .await
+                .context("Failed to connect to remote signer")?;
+            info!(
+                "Signing through remote signer {pubkey}",
+                pubkey = uri.remote_public_key
+            );
+            Some(remote)
+        }
+        _ => None,
+    };
+
+    let (mnemonic, stored_bip39_passphrase) = match args.mnemonic {
+        Some(mnemonic) => (Some(mnemonic), None),
+        None if needs_mnemonic && remote.is_none() && keystore::keystore_exists(&data_dir) => {
+            debug!("Unlocking mnemonic keystore");
+            let unlocked = keystore::unlock(&data_dir, args.passphrase_fd)?;
+            (Some(unlocked.mnemonic), unlocked.bip39_passphrase)
+        }
+        None => (None, None),
+    };
+    let bip39_passphrase = match stored_bip39_passphrase {
+        Some(bip39_passphrase) => Some(bip39_passphrase),
+        None if args.bip39_passphrase && needs_mnemonic && mnemonic.is_some() => {
+            Some(keystore::read_passphrase("BIP39 passphrase: ", None)?)
+        }
+        None => None,
+    };
+    let mut signers = signer::Signers::new(
+        keys::KeySource::new(mnemonic, args.derivation_scheme)
+            .with_bip39_passphrase(bip39_passphrase),
+    )
+    .with_key_overrides(key_overrides::KeyOverrides::load(&data_dir)?);
+    if let Some(remote) = remote {
+        signers = signers.with_remote_signer(remote);
+    }
+
+    let mirror_options = mirror_options::MirrorOptions {
+        nip05_domain: args.nip05_domain,
+        native_reposts: args.native_reposts,
+        edit_mode: args.edits,
+        media_backend: args.media_backend,
+        nip96_servers: args.nip96_servers,
+        media_event_policy: args.media_events,
+        content_templates: content_templates::ContentTemplates::load(
+            &data_dir,
+            !args.no_tweet_link,
+        )?,
+    };
 
     // Handle subcommands
     match args.command {
         Commands::FetchProfile { username } => {
-            commands::fetch_profile::execute(&username, &data_dir, bearer_token.as_deref().unwrap())
+            commands::fetch_profile::execute(&username, &data_dir, twitter_api.as_ref().unwrap())
                 .await?
         }
         Commands::FetchTweet {
@@ -350,7 +771,7 @@ async fn main() -> Result<()> {
                 &tweet_url_or_id,
                 &data_dir,
                 skip_profiles,
-                bearer_token.as_deref().unwrap(),
+                twitter_api.as_ref().unwrap(),
             )
             .await?
         }
@@ -366,10 +787,27 @@ async fn main() -> Result<()> {
                 Some(count),
                 days,
                 skip_profiles,
-                bearer_token.as_deref().unwrap(),
+                twitter_api.as_ref().unwrap(),
             )
             .await?
         }
+        Commands::Backfill {
+            username,
+            max_tweets,
+            restart,
+        } => {
+            commands::backfill::execute(
+                &username,
+                &data_dir,
+                max_tweets,
+                restart,
+                twitter_api.as_ref().unwrap(),
+            )
+            .await?
+        }
+        Commands::ImportArchive { path, skip_media } => {
+            commands::import_archive::execute(&path, &data_dir, skip_media).await?
+        }
         Commands::ListTweets => commands::list_tweets::execute(&data_dir).await?,
         Commands::ClearCache { force } => commands::clear_cache::execute(&data_dir, force).await?,
         Commands::PostTweetToNostr {
@@ -386,8 +824,9 @@ async fn main() -> Result<()> {
                 &data_dir,
                 force,
                 skip_profiles,
-                args.mnemonic.as_deref(),
-                bearer_token.as_deref(),
+                &signers,
+                &mirror_options,
+                twitter_api.as_ref(),
             )
             .await?
         }
@@ -405,7 +844,8 @@ async fn main() -> Result<()> {
                 &data_dir,
                 force,
                 skip_profiles,
-                args.mnemonic.as_deref(),
+                &signers,
+                &mirror_options,
             )
             .await?
         }
@@ -423,8 +863,9 @@ async fn main() -> Result<()> {
                 &data_dir,
                 force,
                 skip_profiles,
-                args.mnemonic.as_deref(),
-                bearer_token.as_deref(),
+                &signers,
+                &mirror_options,
+                twitter_api.as_ref(),
             )
             .await?
         }
@@ -433,15 +874,16 @@ async fn main() -> Result<()> {
                 &username,
                 &relays,
                 &data_dir,
-                args.mnemonic.as_deref(),
+                &signers,
+                &mirror_options,
             )
             .await?
         }
         Commands::UpdateRelayList { relays } => {
-            commands::update_relay_list::execute(&relays, args.mnemonic.as_deref()).await?
+            commands::update_relay_list::execute(&relays, &signers).await?
         }
         Commands::ShowTweet(cmd) => {
-            cmd.execute(&data_dir, bearer_token.as_deref(), args.mnemonic.as_deref())
+            cmd.execute(&data_dir, twitter_api.as_ref(), &signers, &mirror_options)
                 .await?
         }
         Commands::Daemon {
@@ -449,18 +891,107 @@ async fn main() -> Result<()> {
             relays,
             blossom_servers,
             poll_interval,
+            backfill,
+            verify_deletions,
+            deletions_dry_run,
         } => {
             commands::daemon::execute(
                 users,
                 relays,
                 blossom_servers,
                 poll_interval,
+                backfill,
+                verify_deletions,
+                deletions_dry_run,
                 &data_dir,
-                args.mnemonic.as_deref(),
-                bearer_token.as_deref().unwrap(),
+                signers,
+                mirror_options,
+                twitter_api.unwrap(),
             )
             .await?
         }
+        Commands::MigrateKeys {
+            relays,
+            users,
+            dry_run,
+            force,
+        } => {
+            commands::migrate_keys::execute(&relays, &users, &data_dir, &signers, dry_run, force)
+                .await?
+        }
+        Commands::VerifyDeletions {
+            relays,
+            users,
+            dry_run,
+        } => {
+            commands::verify_deletions::execute(
+                &relays,
+                &users,
+                &data_dir,
+                &signers,
+                dry_run,
+                twitter_api.as_ref().unwrap(),
+            )
+            .await?
+        }
+        Commands::ServeNip05 { bind, relays } => {
+            commands::serve_nip05::execute(
+                bind,
+                &relays,
+                &data_dir,
+                &signers,
+                mirror_options.nip05_domain.as_deref(),
+            )
+            .await?
+        }
+        Commands::Bunker { relays, secret } => {
+            commands::bunker::execute(&relays, signers.keys(), secret).await?
+        }
+        Commands::Blossom { command } => match command {
+            BlossomCommands::Repair {
+                blossom_servers,
+                replicas,
+            } => commands::blossom::repair(&blossom_servers, replicas, &data_dir, &signers).await?,
+        },
+        Commands::Keystore { command } => match command {
+            KeystoreCommands::Init {
+                generate,
+                log_n,
+                force,
+            } => {
+                commands::keystore::init(
+                    &data_dir,
+                    signers.keys().mnemonic(),
+                    args.bip39_passphrase,
+                    generate,
+                    log_n,
+                    force,
+                    args.passphrase_fd,
+                )
+                .await?
+            }
+            KeystoreCommands::Unlock => {
+                commands::keystore::unlock(&data_dir, args.passphrase_fd, args.derivation_scheme)
+                    .await?
+            }
+        },
+        Commands::Keys { command } => match command {
+            KeysCommands::List { format } => {
+                commands::keys::list(&data_dir, &signers, &format).await?
+            }
+            KeysCommands::Show { username, format } => {
+                commands::keys::show(&username, &data_dir, &signers, &format).await?
+            }
+            KeysCommands::Export {
+                include_nsec,
+                yes,
+                output,
+                force,
+            } => {
+                commands::keys::export(&data_dir, &signers, include_nsec, yes, output, force)
+                    .await?
+            }
+        },
         Commands::Utils { command } => match command {
             UtilsCommands::QueryEvents {
                 relays,
@@ -20,32 +20,67 @@ pub struct MediaResult {
     pub from_cache: bool,
 }
 
-/// Extract all media URLs from a tweet's includes.media section and entities.urls
-pub fn extract_media_urls_from_tweet(tweet: &Tweet) -> Vec<String> {
-    let mut media_urls = Vec::new();
+/// URL of a media item on Twitter, the best quality variant for videos and GIFs
+pub fn media_item_url(media: &TwitterMedia) -> Option<String> {
+    // Handle photos (direct URL in url field)
+    if let Some(url) = &media.url {
+        return Some(url.clone());
+    }
 
-    // Helper function to process a single media item
-    fn process_media_item(media: &TwitterMedia) -> Option<String> {
-        // Handle photos (direct URL in url field)
-        if let Some(url) = &media.url {
-            return Some(url.clone());
-        }
+    // Handle videos and GIFs (URLs in variants)
+    if (media.type_field == "video" || media.type_field == "animated_gif")
+        && let Some(variants) = &media.variants
+    {
+        // Find the variant with the highest bitrate (highest quality)
+        return variants
+            .iter()
+            .filter_map(|v| v.bit_rate.map(|br| (br, &v.url)))
+            .max_by_key(|&(br, _)| br)
+            .map(|(_, url)| url.clone());
+    }
 
-        // Handle videos and GIFs (URLs in variants)
-        if (media.type_field == "video" || media.type_field == "animated_gif")
-            && let Some(variants) = &media.variants
-        {
-            // Find the variant with the highest bitrate (highest quality)
-            return variants
+    // Fallback to preview image if available
+    media.preview_image_url.clone()
+}
+
+/// Finds the media item of a tweet, or of a tweet it references, a local file holds
+///
+/// Matches the names `download_media` saves files under as well as files named
+/// after their URL.
+pub fn media_item_for_file<'a>(tweet: &'a Tweet, file_path: &Path) -> Option<&'a TwitterMedia> {
+    let file_name = file_path.file_name()?;
+    let referenced = tweet
+        .referenced_tweets
+        .iter()
+        .flatten()
+        .filter_map(|ref_tweet| ref_tweet.data.as_deref());
+
+    std::iter::once(tweet)
+        .chain(referenced)
+        .flat_map(|owner| {
+            owner
+                .includes
                 .iter()
-                .filter_map(|v| v.bit_rate.map(|br| (br, &v.url)))
-                .max_by_key(|&(br, _)| br)
-                .map(|(_, url)| url.clone());
-        }
+                .flat_map(|includes| includes.media.iter().flatten())
+                .map(move |media| (owner, media))
+        })
+        .find(|(owner, media)| {
+            let downloaded_name = media_filename(
+                &owner.author.username,
+                &media.media_key,
+                get_file_extension(&media.type_field),
+            );
+            sanitized_file_path(Path::new(""), &downloaded_name).as_os_str() == file_name
+                || media_item_url(media).is_some_and(|url| {
+                    url.split('/').next_back().map(std::ffi::OsStr::new) == Some(file_name)
+                })
+        })
+        .map(|(_, media)| media)
+}
 
-        // Fallback to preview image if available
-        media.preview_image_url.clone()
-    }
+/// Extract all media URLs from a tweet's includes.media section and entities.urls
+pub fn extract_media_urls_from_tweet(tweet: &Tweet) -> Vec<String> {
+    let mut media_urls = Vec::new();
 
     // Extract possible video URLs from entities.urls (needed when Twitter doesn't include media variants)
     fn extract_video_urls_from_entities(
@@ -78,7 +113,7 @@ pub fn extract_media_urls_from_tweet(tweet: &Tweet) -> Vec<String> {
         && let Some(media_items) = &includes.media
     {
         for media in media_items {
-            if let Some(url) = process_media_item(media) {
+            if let Some(url) = media_item_url(media) {
                 media_urls.push(url);
             }
         }
@@ -0,0 +1,200 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::blossom;
+use crate::media;
+use crate::media_metadata;
+use crate::mirror_options::MirrorOptions;
+use crate::nip96::Nip96Client;
+use crate::nostr::{self, UploadedMedia};
+use crate::signer::UserSigner;
+use crate::twitter::Tweet;
+
+/// Where downloaded tweet media is uploaded before posting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MediaBackend {
+    /// Blossom servers (`--blossom-servers`)
+    #[default]
+    Blossom,
+    /// NIP-96 HTTP file storage servers (`--nip96-servers`)
+    Nip96,
+    /// Don't upload, link the media on Twitter
+    None,
+}
+
+impl std::fmt::Display for MediaBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MediaBackend::Blossom => write!(f, "blossom"),
+            MediaBackend::Nip96 => write!(f, "nip96"),
+            MediaBackend::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Uploads media files through the backend selected in `mirror_options`
+///
+/// Returns nothing when the backend is disabled or has no servers, so the
+/// media on Twitter is linked instead.
+pub async fn upload_media(
+    media_files: &[PathBuf],
+    blossom_servers: &[String],
+    signer: &UserSigner,
+    data_dir: &Path,
+    mirror_options: &MirrorOptions,
+) -> Result<Vec<UploadedMedia>> {
+    if media_files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let nip96_servers = mirror_options.nip96_servers.as_slice();
+    match mirror_options.media_backend {
+        MediaBackend::Blossom if !blossom_servers.is_empty() => {
+            info!(
+                "Uploading {count} media files to Blossom servers",
+                count = media_files.len()
+            );
+            nostr::upload_media_to_blossom(media_files, blossom_servers, signer, data_dir).await
+        }
+        MediaBackend::Nip96 if !nip96_servers.is_empty() => {
+            info!(
+                "Uploading {count} media files to NIP-96 servers",
+                count = media_files.len()
+            );
+            upload_media_to_nip96(media_files, nip96_servers, signer).await
+        }
+        backend => {
+            debug!("No {backend} media servers configured, linking media on Twitter");
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Describes the media of a tweet for its NIP-92 `imeta` tags
+///
+/// `uploaded_media` holds the uploads of `media_files`, in order. Without
+/// uploads, the files of `tweet_media_urls` are described under their URL on
+/// Twitter. Dimensions and blurhash are computed from the local files, alt
+/// text and video thumbnails come from the tweet.
+pub async fn describe_media(
+    tweet: &Tweet,
+    media_files: &[PathBuf],
+    tweet_media_urls: &[String],
+    uploaded_media: Vec<UploadedMedia>,
+) -> Vec<UploadedMedia> {
+    let mut described = Vec::new();
+    if !uploaded_media.is_empty() {
+        for (media_file, mut upload) in media_files.iter().zip(uploaded_media) {
+            let visual = media_metadata::inspect(media_file, &upload.mime_type).await;
+            upload.describe(visual, media::media_item_for_file(tweet, media_file));
+            described.push(upload);
+        }
+        return described;
+    }
+
+    for media_file in media_files {
+        let Some(item) = media::media_item_for_file(tweet, media_file) else {
+            continue;
+        };
+        let Some(url) = media::media_item_url(item).filter(|url| tweet_media_urls.contains(url))
+        else {
+            continue;
+        };
+        match describe_linked_file(media_file, url).await {
+            Ok(mut linked) => {
+                let visual = media_metadata::inspect(media_file, &linked.mime_type).await;
+                linked.describe(visual, Some(item));
+                described.push(linked);
+            }
+            Err(e) => warn!("{e:#}"),
+        }
+    }
+    described
+}
+
+/// Media left on Twitter, with the hash and size of its local copy
+async fn describe_linked_file(media_file: &Path, url: String) -> Result<UploadedMedia> {
+    let mime_type = nostr::mime_type_from_path(media_file)?;
+    let sha256 = blossom::sha256_file(media_file).await?;
+    let size = tokio::fs::metadata(media_file)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to read metadata of media file {path}",
+                path = media_file.display()
+            )
+        })?
+        .len();
+    Ok(UploadedMedia::new(url, sha256, size, mime_type))
+}
+
+/// Upload media files to NIP-96 servers, trying them in order for each file
+pub async fn upload_media_to_nip96(
+    media_files: &[PathBuf],
+    nip96_servers: &[String],
+    signer: &UserSigner,
+) -> Result<Vec<UploadedMedia>> {
+    let mut clients = Vec::new();
+    for server in nip96_servers {
+        match Nip96Client::discover(server).await {
+            Ok(client) => clients.push(client),
+            Err(e) => warn!("{e:#}"),
+        }
+    }
+    if clients.is_empty() {
+        bail!("None of the NIP-96 servers could be reached");
+    }
+
+    let mut uploaded = Vec::new();
+    for media_file in media_files {
+        let mime_type = nostr::mime_type_from_path(media_file)?;
+        let sha256 = blossom::sha256_file(media_file).await?;
+        let size = tokio::fs::metadata(media_file)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to read metadata of media file {path}",
+                    path = media_file.display()
+                )
+            })?
+            .len();
+
+        let mut media = None;
+        for client in &clients {
+            let event = match client
+                .upload_file(media_file, &sha256, &mime_type, signer)
+                .await
+            {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("{e:#}");
+                    continue;
+                }
+            };
+            match UploadedMedia::from_nip94(&event, &sha256, size, &mime_type) {
+                Ok(uploaded) => {
+                    debug!(
+                        "Uploaded {path} to {server}",
+                        path = media_file.display(),
+                        server = client.server()
+                    );
+                    media = Some(uploaded);
+                    break;
+                }
+                Err(e) => warn!("{e:#}"),
+            }
+        }
+
+        let Some(media) = media else {
+            bail!(
+                "Failed to upload media file {path} to any NIP-96 server",
+                path = media_file.display()
+            );
+        };
+        uploaded.push(media);
+    }
+
+    Ok(uploaded)
+}
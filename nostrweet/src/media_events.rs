@@ -0,0 +1,439 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use nostr_sdk::{EventBuilder, Kind, PublicKey, Tag, Timestamp};
+use std::collections::BTreeSet;
+use tracing::debug;
+
+use crate::content_templates::ContentTemplates;
+use crate::datetime_utils::parse_rfc3339;
+use crate::media;
+use crate::nostr::{self, UploadedMedia};
+use crate::nostr_linking::NostrLinkResolver;
+use crate::tweet_metadata;
+use crate::twitter::Tweet;
+
+/// Kind of NIP-68 picture events
+pub const PICTURE_KIND: u16 = 20;
+
+/// Kind of NIP-71 video events for landscape videos
+pub const VIDEO_KIND: u16 = 21;
+
+/// Kind of NIP-71 video events for short portrait videos
+pub const SHORT_VIDEO_KIND: u16 = 22;
+
+/// Longest title taken from a tweet's text, in characters
+const MAX_TITLE_CHARS: usize = 80;
+
+/// Which events tweets with only images or only videos are published as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MediaEventPolicy {
+    /// Kind 1 text notes only
+    #[default]
+    TextOnly,
+    /// A picture or video event instead of the text note
+    Auto,
+    /// A picture or video event next to the text note
+    AlwaysDual,
+}
+
+impl std::fmt::Display for MediaEventPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MediaEventPolicy::TextOnly => write!(f, "text-only"),
+            MediaEventPolicy::Auto => write!(f, "auto"),
+            MediaEventPolicy::AlwaysDual => write!(f, "always-dual"),
+        }
+    }
+}
+
+impl MediaEventPolicy {
+    /// Whether a tweet published as a media event gets no text note
+    pub fn replaces_text_note(self) -> bool {
+        self == MediaEventPolicy::Auto
+    }
+}
+
+/// Every kind a tweet can be mirrored as
+pub fn mirrored_kinds() -> [Kind; 4] {
+    [
+        Kind::TextNote,
+        Kind::Custom(PICTURE_KIND),
+        Kind::Custom(VIDEO_KIND),
+        Kind::Custom(SHORT_VIDEO_KIND),
+    ]
+}
+
+/// The media event kind for a tweet and the attachments it shows
+///
+/// Only tweets whose own media are all images or all videos, each described
+/// by an attachment, qualify. Retweets and quotes stay text notes, as their
+/// media belongs to another tweet.
+fn media_event_kind<'a>(
+    tweet: &Tweet,
+    attachments: &'a [UploadedMedia],
+) -> Option<(Kind, Vec<&'a UploadedMedia>)> {
+    if tweet
+        .referenced_tweets
+        .iter()
+        .flatten()
+        .any(|rt| matches!(rt.type_field.as_str(), "retweeted" | "quoted"))
+    {
+        return None;
+    }
+
+    let media_urls = media::extract_media_urls_from_tweet(tweet);
+    if media_urls.is_empty() {
+        return None;
+    }
+    let shown = media_urls
+        .iter()
+        .map(|url| {
+            attachments
+                .iter()
+                .find(|a| a.url == *url || a.source.as_ref() == Some(url))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if shown.iter().all(|a| a.mime_type.starts_with("image/")) {
+        return Some((Kind::Custom(PICTURE_KIND), shown));
+    }
+    if !shown.iter().all(|a| a.mime_type.starts_with("video/")) {
+        return None;
+    }
+
+    // NIP-71 keeps short portrait videos apart
+    let portrait = shown
+        .first()?
+        .dim
+        .as_deref()
+        .and_then(|dim| dim.split_once('x'))
+        .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+        .is_some_and(|(width, height)| height > width);
+    let kind = if portrait {
+        SHORT_VIDEO_KIND
+    } else {
+        VIDEO_KIND
+    };
+    Some((Kind::Custom(kind), shown))
+}
+
+/// First line of the tweet's text without links, shortened to a title
+fn title(tweet: &Tweet, kind: Kind) -> String {
+    let text = tweet
+        .note_tweet
+        .as_ref()
+        .map_or(tweet.text.as_str(), |note| note.text.as_str());
+    let first_line = text
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .filter(|word| !word.starts_with("https://t.co/"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .find(|line| !line.is_empty());
+
+    match first_line {
+        Some(line) if line.chars().count() > MAX_TITLE_CHARS => {
+            let shortened: String = line.chars().take(MAX_TITLE_CHARS - 1).collect();
+            format!("{shortened}…", shortened = shortened.trim_end())
+        }
+        Some(line) => line,
+        None if kind == Kind::Custom(PICTURE_KIND) => {
+            format!("Picture by @{username}", username = tweet.author.username)
+        }
+        None => format!("Video by @{username}", username = tweet.author.username),
+    }
+}
+
+/// Builds the NIP-68 picture or NIP-71 video event of a tweet, if the policy
+/// and the tweet's media call for one
+///
+/// The content is the tweet formatted like its text note, without the media
+/// URLs the `imeta` tags carry. `links` are the thread, quote and edit tags
+/// of the text note, so replies and edits stay linked without it.
+pub fn media_event_builder(
+    tweet: &Tweet,
+    attachments: &[UploadedMedia],
+    links: &[Tag],
+    resolver: &mut NostrLinkResolver,
+    policy: MediaEventPolicy,
+    templates: &ContentTemplates,
+) -> Result<Option<EventBuilder>> {
+    if policy == MediaEventPolicy::TextOnly {
+        return Ok(None);
+    }
+    let Some((kind, shown)) = media_event_kind(tweet, attachments) else {
+        debug!(
+            "Tweet {id} has no media for a picture or video event",
+            id = tweet.id
+        );
+        return Ok(None);
+    };
+
+    let created_at = parse_rfc3339(&tweet.created_at)
+        .with_context(|| format!("Tweet {id} has an invalid date", id = tweet.id))?
+        .timestamp() as u64;
+    let (content, mentioned_pubkeys) =
+        nostr::format_tweet_as_nostr_content_with_mentions(tweet, &[], resolver, templates)?;
+
+    let tags = media_event_tags(tweet, kind, &shown, &mentioned_pubkeys, links, created_at)?;
+    Ok(Some(
+        EventBuilder::new(kind, content)
+            .custom_created_at(Timestamp::from(created_at))
+            .tags(tags),
+    ))
+}
+
+fn media_event_tags(
+    tweet: &Tweet,
+    kind: Kind,
+    shown: &[&UploadedMedia],
+    mentioned_pubkeys: &[PublicKey],
+    links: &[Tag],
+    created_at: u64,
+) -> Result<Vec<Tag>> {
+    let twitter_url = nostr::build_twitter_status_url(&tweet.id);
+    let mut tags = vec![
+        Tag::parse(vec!["title".to_string(), title(tweet, kind)])?,
+        Tag::parse(vec!["r", twitter_url.as_str()])?,
+        nostr::tweet_proxy_tag(&tweet.id)?,
+    ];
+    for attachment in shown {
+        tags.push(attachment.imeta_tag()?);
+    }
+
+    if kind == Kind::Custom(PICTURE_KIND) {
+        // NIP-68 lets clients filter pictures by type and hash
+        let mime_types: BTreeSet<&str> = shown.iter().map(|a| a.mime_type.as_str()).collect();
+        for mime_type in mime_types {
+            tags.push(Tag::parse(vec!["m", mime_type])?);
+        }
+        for attachment in shown {
+            tags.push(Tag::parse(vec!["x", attachment.sha256.as_str()])?);
+        }
+    } else {
+        tags.push(Tag::parse(vec![
+            "published_at".to_string(),
+            created_at.to_string(),
+        ])?);
+    }
+
+    // Hashtags, language, location and content warning, as on the text note
+    tags.extend(tweet_metadata::metadata_tags(tweet)?);
+
+    // NIP-31 summary for clients that don't know the kind
+    let single_alt = match shown {
+        [single] => single.alt.clone(),
+        _ => None,
+    };
+    let alt = single_alt.unwrap_or_else(|| {
+        let what = if kind == Kind::Custom(PICTURE_KIND) {
+            "Pictures"
+        } else {
+            "Video"
+        };
+        format!(
+            "{what} posted by @{username} on Twitter",
+            username = tweet.author.username
+        )
+    });
+    tags.push(Tag::parse(vec!["alt".to_string(), alt])?);
+
+    for pubkey in mentioned_pubkeys {
+        tags.push(Tag::parse(vec!["p", &pubkey.to_hex()])?);
+    }
+    tags.extend(links.iter().cloned());
+    tags.push(Tag::parse(vec!["client", "nostrweet"])?);
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    fn media_tweet(media: serde_json::Value) -> Result<Tweet> {
+        test_tweet(
+            "3000",
+            "photographer",
+            serde_json::json!({
+                "text": "Sunset over the bay https://t.co/abc",
+                "includes": { "media": media }
+            }),
+        )
+    }
+
+    fn attachment(source: &str, mime_type: &str, dim: &str) -> UploadedMedia {
+        let mut media = UploadedMedia::new(
+            format!("https://blossom.example.com/{sha}", sha = "ab".repeat(32)),
+            "ab".repeat(32),
+            1000,
+            mime_type.to_string(),
+        );
+        media.source = Some(source.to_string());
+        media.dim = Some(dim.to_string());
+        media.alt = Some("The sun setting".to_string());
+        media
+    }
+
+    fn tag_values(builder: EventBuilder) -> Result<(Kind, Vec<Vec<String>>)> {
+        let event = builder.sign_with_keys(&nostr_sdk::Keys::generate())?;
+        let tags = event.tags.iter().map(|t| t.clone().to_vec()).collect();
+        Ok((event.kind, tags))
+    }
+
+    #[test]
+    fn test_picture_event() -> Result<()> {
+        let tweet = media_tweet(serde_json::json!([{
+            "media_key": "3_1",
+            "type": "photo",
+            "url": "https://pbs.twimg.com/media/sunset.jpg"
+        }]))?;
+        let attachments = vec![attachment(
+            "https://pbs.twimg.com/media/sunset.jpg",
+            "image/jpeg",
+            "1200x800",
+        )];
+
+        let (kind, shown) =
+            media_event_kind(&tweet, &attachments).context("Expected a picture event")?;
+        assert_eq!(kind, Kind::Custom(PICTURE_KIND));
+        let tags = media_event_tags(&tweet, kind, &shown, &[], &[], 1705764600)?;
+        let builder = EventBuilder::new(kind, "").tags(tags);
+        let (kind, tags) = tag_values(builder)?;
+        assert_eq!(kind, Kind::Custom(PICTURE_KIND));
+        assert!(tags.contains(&vec![
+            "title".to_string(),
+            "Sunset over the bay".to_string()
+        ]));
+        assert!(tags.contains(&vec!["m".to_string(), "image/jpeg".to_string()]));
+        assert!(tags.contains(&vec!["alt".to_string(), "The sun setting".to_string()]));
+        assert!(tags.iter().any(|t| t[0] == "imeta"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_media_only_self_reply_keeps_thread_links() -> Result<()> {
+        let mut tweet = media_tweet(serde_json::json!([{
+            "media_key": "3_1",
+            "type": "photo",
+            "url": "https://pbs.twimg.com/media/sunset.jpg"
+        }]))?;
+        tweet.text = "https://t.co/abc".to_string();
+        tweet.conversation_id = Some("2999".to_string());
+        tweet.in_reply_to_user_id = Some("42".to_string());
+        tweet.referenced_tweets = Some(vec![crate::twitter::ReferencedTweet {
+            id: "2999".to_string(),
+            type_field: "replied_to".to_string(),
+            data: None,
+        }]);
+        let attachments = vec![attachment(
+            "https://pbs.twimg.com/media/sunset.jpg",
+            "image/jpeg",
+            "1200x800",
+        )];
+
+        let temp_dir = tempfile::tempdir()?;
+        let parent_event = "01".repeat(32);
+        let parent = nostr::NostrEventInfo {
+            tweet_id: "2999".to_string(),
+            event_id: parent_event.clone(),
+            pubkey: "cd".repeat(32),
+            created_at: 1705764000,
+            media_urls: Vec::new(),
+            relays: vec!["wss://relay.example.com".to_string()],
+            event_json: None,
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        };
+        nostr::save_nostr_event_info(&parent, temp_dir.path()).await?;
+        let links = crate::thread::reference_event_tags(&tweet, temp_dir.path()).await?;
+
+        let (kind, shown) =
+            media_event_kind(&tweet, &attachments).context("Expected a picture event")?;
+        let tags = media_event_tags(&tweet, kind, &shown, &[], &links, 1705764600)?;
+        let (kind, tags) = tag_values(EventBuilder::new(kind, "").tags(tags))?;
+        assert_eq!(kind, Kind::Custom(PICTURE_KIND));
+        assert!(
+            tags.iter()
+                .any(|t| t[0] == "e" && t[1] == parent_event && t[3] == "root")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_portrait_video_is_short() -> Result<()> {
+        let tweet = media_tweet(serde_json::json!([{
+            "media_key": "7_1",
+            "type": "video",
+            "variants": [{
+                "bit_rate": 2176000,
+                "content_type": "video/mp4",
+                "url": "https://video.twimg.com/clip.mp4"
+            }]
+        }]))?;
+        let portrait = vec![attachment(
+            "https://video.twimg.com/clip.mp4",
+            "video/mp4",
+            "720x1280",
+        )];
+        let landscape = vec![attachment(
+            "https://video.twimg.com/clip.mp4",
+            "video/mp4",
+            "1280x720",
+        )];
+
+        assert_eq!(
+            media_event_kind(&tweet, &portrait).map(|(kind, _)| kind),
+            Some(Kind::Custom(SHORT_VIDEO_KIND))
+        );
+        assert_eq!(
+            media_event_kind(&tweet, &landscape).map(|(kind, _)| kind),
+            Some(Kind::Custom(VIDEO_KIND))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_or_undescribed_media_stays_text() -> Result<()> {
+        let tweet = media_tweet(serde_json::json!([
+            { "media_key": "3_1", "type": "photo", "url": "https://pbs.twimg.com/media/a.jpg" },
+            { "media_key": "3_2", "type": "photo", "url": "https://pbs.twimg.com/media/b.jpg" }
+        ]))?;
+        let one_described = vec![attachment(
+            "https://pbs.twimg.com/media/a.jpg",
+            "image/jpeg",
+            "100x100",
+        )];
+        assert!(media_event_kind(&tweet, &one_described).is_none());
+
+        let mixed = vec![
+            attachment("https://pbs.twimg.com/media/a.jpg", "image/jpeg", "100x100"),
+            attachment("https://pbs.twimg.com/media/b.jpg", "video/mp4", "100x100"),
+        ];
+        assert!(media_event_kind(&tweet, &mixed).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_title() -> Result<()> {
+        let mut tweet = media_tweet(serde_json::json!([]))?;
+        assert_eq!(
+            title(&tweet, Kind::Custom(PICTURE_KIND)),
+            "Sunset over the bay"
+        );
+
+        tweet.text = "https://t.co/abc".to_string();
+        assert_eq!(
+            title(&tweet, Kind::Custom(VIDEO_KIND)),
+            "Video by @photographer"
+        );
+
+        tweet.text = "word ".repeat(30);
+        let long = title(&tweet, Kind::Custom(PICTURE_KIND));
+        assert_eq!(long.chars().count(), MAX_TITLE_CHARS);
+        assert!(long.ends_with('…'));
+        Ok(())
+    }
+}
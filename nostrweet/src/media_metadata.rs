@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use image::ImageReader;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use tracing::debug;
+
+/// Blurhash components along the width and height of an image
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// Images are scaled down to fit this square before computing their blurhash
+const BLURHASH_THUMBNAIL_SIZE: u32 = 64;
+
+/// Size of an MP4 box header with a 32-bit size
+const MP4_BOX_HEADER_LEN: u64 = 8;
+
+/// What clients need to lay out an image or video before loading it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisualMetadata {
+    /// Dimensions (`<width>x<height>`)
+    pub dim: Option<String>,
+    pub blurhash: Option<String>,
+}
+
+/// Computes the dimensions and, for images, the blurhash of a media file
+///
+/// Files that can't be decoded are logged and described as far as possible,
+/// as the metadata is optional.
+pub async fn inspect(path: &Path, mime_type: &str) -> VisualMetadata {
+    let owned_path = path.to_path_buf();
+    let result = if mime_type.starts_with("image/") {
+        tokio::task::spawn_blocking(move || inspect_image(&owned_path)).await
+    } else if mime_type == "video/mp4" || mime_type == "video/quicktime" {
+        tokio::task::spawn_blocking(move || inspect_video(&owned_path)).await
+    } else {
+        return VisualMetadata::default();
+    };
+
+    match result.context("Media inspection task failed") {
+        Ok(Ok(metadata)) => metadata,
+        Ok(Err(e)) | Err(e) => {
+            debug!(
+                "Failed to inspect media file {path}: {e:#}",
+                path = path.display()
+            );
+            VisualMetadata::default()
+        }
+    }
+}
+
+fn inspect_image(path: &Path) -> Result<VisualMetadata> {
+    let image = ImageReader::open(path)
+        .with_context(|| format!("Failed to open {path}", path = path.display()))?
+        .with_guessed_format()
+        .with_context(|| format!("Failed to read {path}", path = path.display()))?
+        .decode()
+        .with_context(|| format!("Failed to decode {path}", path = path.display()))?;
+
+    let thumbnail = image
+        .thumbnail(BLURHASH_THUMBNAIL_SIZE, BLURHASH_THUMBNAIL_SIZE)
+        .to_rgba8();
+    let (components_x, components_y) = BLURHASH_COMPONENTS;
+    let blurhash = blurhash::encode(
+        components_x,
+        components_y,
+        thumbnail.width(),
+        thumbnail.height(),
+        thumbnail.as_raw(),
+    )
+    .inspect_err(|e| debug!("Failed to compute blurhash: {e}"))
+    .ok();
+
+    Ok(VisualMetadata {
+        dim: Some(format!(
+            "{width}x{height}",
+            width = image.width(),
+            height = image.height()
+        )),
+        blurhash,
+    })
+}
+
+/// Reads the dimensions of the first video track from the `moov` box
+fn inspect_video(path: &Path) -> Result<VisualMetadata> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open {path}", path = path.display()))?;
+    let file_len = file
+        .metadata()
+        .with_context(|| format!("Failed to read metadata of {path}", path = path.display()))?
+        .len();
+
+    // Walk the top-level boxes without reading the media data in between
+    let mut offset = 0;
+    while offset + MP4_BOX_HEADER_LEN <= file_len {
+        let mut header = [0; 16];
+        file.seek(SeekFrom::Start(offset))?;
+        let available = (file_len - offset).min(16) as usize;
+        file.read_exact(&mut header[..available])?;
+
+        let (box_type, header_len, box_len) =
+            mp4_box_header(&header[..available], file_len - offset)
+                .context("Malformed MP4 box header")?;
+        if &box_type == b"moov" {
+            let mut moov = vec![0; (box_len - header_len) as usize];
+            file.seek(SeekFrom::Start(offset + header_len))?;
+            file.read_exact(&mut moov)?;
+            return Ok(VisualMetadata {
+                dim: mp4_track_dimensions(&moov).map(|(width, height)| format!("{width}x{height}")),
+                blurhash: None,
+            });
+        }
+        offset += box_len;
+    }
+
+    Ok(VisualMetadata::default())
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Type, header length and total length of the MP4 box starting `data`
+fn mp4_box_header(data: &[u8], remaining: u64) -> Option<([u8; 4], u64, u64)> {
+    let box_type: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+    let (header_len, box_len) = match be_u32(data, 0)? {
+        // The box extends to the end of the file
+        0 => (MP4_BOX_HEADER_LEN, remaining),
+        // A 64-bit size follows the type
+        1 => (MP4_BOX_HEADER_LEN + 8, be_u64(data, 8)?),
+        size => (MP4_BOX_HEADER_LEN, u64::from(size)),
+    };
+    (box_len >= header_len && box_len <= remaining).then_some((box_type, header_len, box_len))
+}
+
+/// The payloads of the MP4 boxes of a given type directly inside `data`
+fn mp4_child_boxes<'a>(mut data: &'a [u8], wanted: &[u8; 4]) -> Vec<&'a [u8]> {
+    let mut boxes = Vec::new();
+    while let Some((box_type, header_len, box_len)) = mp4_box_header(data, data.len() as u64) {
+        let (current, rest) = data.split_at(box_len as usize);
+        if &box_type == wanted {
+            boxes.push(&current[header_len as usize..]);
+        }
+        data = rest;
+    }
+    boxes
+}
+
+/// Width and height of the first track with a picture, from its `tkhd` box
+fn mp4_track_dimensions(moov: &[u8]) -> Option<(u32, u32)> {
+    mp4_child_boxes(moov, b"trak")
+        .into_iter()
+        .flat_map(|trak| mp4_child_boxes(trak, b"tkhd"))
+        .find_map(|tkhd| {
+            // Version 1 headers have 64-bit times and duration
+            let width_offset = if tkhd.first()? == &1 { 88 } else { 76 };
+            // Both are 16.16 fixed-point numbers
+            let width = be_u32(tkhd, width_offset)? >> 16;
+            let height = be_u32(tkhd, width_offset + 4)? >> 16;
+            (width > 0 && height > 0).then_some((width, height))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn tkhd(width: u32, height: u32) -> Vec<u8> {
+        let mut payload = vec![0; 76];
+        payload.extend_from_slice(&(width << 16).to_be_bytes());
+        payload.extend_from_slice(&(height << 16).to_be_bytes());
+        mp4_box(b"tkhd", &payload)
+    }
+
+    #[tokio::test]
+    async fn test_inspect_image() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("image.png");
+        image::RgbaImage::from_fn(40, 30, |x, _| image::Rgba([(x * 6) as u8, 80, 160, 255]))
+            .save(&path)?;
+
+        let metadata = inspect(&path, "image/png").await;
+        assert_eq!(metadata.dim.as_deref(), Some("40x30"));
+        assert!(metadata.blurhash.is_some_and(|hash| !hash.is_empty()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inspect_video() -> Result<()> {
+        // An audio track without dimensions comes before the video track
+        let trak_audio = mp4_box(b"trak", &tkhd(0, 0));
+        let trak_video = mp4_box(b"trak", &tkhd(1280, 720));
+        let mut moov_payload = mp4_box(b"mvhd", &[0; 100]);
+        moov_payload.extend(trak_audio);
+        moov_payload.extend(trak_video);
+
+        let mut data = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        data.extend(mp4_box(b"mdat", &[0; 64]));
+        data.extend(mp4_box(b"moov", &moov_payload));
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("video.mp4");
+        std::fs::write(&path, data)?;
+
+        let metadata = inspect(&path, "video/mp4").await;
+        assert_eq!(metadata.dim.as_deref(), Some("1280x720"));
+        assert_eq!(metadata.blurhash, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inspect_undecodable_file() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("broken.jpg");
+        std::fs::write(&path, b"not a jpeg")?;
+
+        assert_eq!(
+            inspect(&path, "image/jpeg").await,
+            VisualMetadata::default()
+        );
+        Ok(())
+    }
+}
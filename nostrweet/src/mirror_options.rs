@@ -0,0 +1,24 @@
+use crate::content_templates::ContentTemplates;
+use crate::edits::EditMode;
+use crate::media_backend::MediaBackend;
+use crate::media_events::MediaEventPolicy;
+
+/// How accounts and tweets are mirrored, built once in `main` from the command line
+/// and the data directory
+#[derive(Debug, Clone)]
+pub struct MirrorOptions {
+    /// Domain mirrored accounts are identified under (nip05 = username@domain)
+    pub nip05_domain: Option<String>,
+    /// Publish plain retweets as NIP-18 reposts instead of copied notes
+    pub native_reposts: bool,
+    /// How new versions of already mirrored edited tweets are published
+    pub edit_mode: EditMode,
+    /// Where downloaded tweet media is uploaded before posting
+    pub media_backend: MediaBackend,
+    /// NIP-96 servers tried in order when the NIP-96 backend is selected
+    pub nip96_servers: Vec<String>,
+    /// Which events tweets with only images or only videos are published as
+    pub media_event_policy: MediaEventPolicy,
+    /// Layouts of the mirrored notes, from `content_templates.json` or built in
+    pub content_templates: ContentTemplates,
+}
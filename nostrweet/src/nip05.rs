@@ -0,0 +1,340 @@
+use anyhow::{Context, Result, anyhow};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode, header};
+use hyper_util::rt::TokioIo;
+use nostr_sdk::Metadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+
+use crate::signer::{self, Signers};
+use crate::storage;
+
+/// Path NIP-05 clients query on the identifier's domain
+pub const NIP05_PATH: &str = "/.well-known/nostr.json";
+
+/// NIP-05 local part for a Twitter username (NIP-05 names are lowercase)
+pub fn nip05_name(username: &str) -> String {
+    username.trim_start_matches('@').to_lowercase()
+}
+
+/// Full `name@domain` identifier of a mirrored account
+pub fn nip05_identifier(username: &str, domain: &str) -> String {
+    format!("{name}@{domain}", name = nip05_name(username))
+}
+
+/// Identifies profile metadata as `username@domain` when a domain is given
+pub fn with_nip05(metadata: Metadata, username: &str, domain: Option<&str>) -> Metadata {
+    match domain {
+        Some(domain) => metadata.nip05(nip05_identifier(username, domain)),
+        None => metadata,
+    }
+}
+
+/// Body of `/.well-known/nostr.json`
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Nip05Document {
+    /// NIP-05 name -> hex pubkey
+    pub names: BTreeMap<String, String>,
+    /// Hex pubkey -> relays the account publishes to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub relays: BTreeMap<String, Vec<String>>,
+}
+
+/// How often the server re-reads cached profiles, picking up newly fetched accounts
+pub const NIP05_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Serves NIP-05 documents from the cached profiles in the data directory
+///
+/// The name to pubkey map is built when loading and on [`Nip05Directory::refresh`],
+/// requests never read profiles or derive keys.
+pub struct Nip05Directory {
+    data_dir: PathBuf,
+    relays: Vec<String>,
+    signers: Signers,
+    /// NIP-05 name -> hex pubkey
+    names: RwLock<BTreeMap<String, String>>,
+}
+
+impl Nip05Directory {
+    /// Builds the directory from the profiles currently cached in `data_dir`
+    pub fn load(data_dir: &Path, relays: &[String], signers: &Signers) -> Result<Self> {
+        let directory = Self {
+            data_dir: data_dir.to_path_buf(),
+            relays: relays.to_vec(),
+            signers: signers.clone(),
+            names: RwLock::default(),
+        };
+        directory.refresh()?;
+        Ok(directory)
+    }
+
+    /// Re-reads the cached profiles, so accounts fetched by a running daemon show up
+    pub fn refresh(&self) -> Result<()> {
+        let mut names = BTreeMap::new();
+
+        for user in storage::list_cached_user_profiles(&self.data_dir)? {
+            let name = nip05_name(&user.username);
+            let Some(pubkey) = signer::known_public_key(&user.id, &user.username, &self.signers)
+                .with_context(|| format!("Failed to derive key for @{name}"))?
+            else {
+                debug!("Pubkey for @{name} is not known yet, leaving it out");
+                continue;
+            };
+            names.insert(name, pubkey.to_hex());
+        }
+
+        debug!("Loaded {count} NIP-05 names", count = names.len());
+        *self
+            .names
+            .write()
+            .map_err(|_| anyhow!("NIP-05 names lock poisoned"))? = names;
+        Ok(())
+    }
+
+    /// Builds the document for `name`, or for every cached account without one
+    pub fn document(&self, name: Option<&str>) -> Result<Nip05Document> {
+        let names = self
+            .names
+            .read()
+            .map_err(|_| anyhow!("NIP-05 names lock poisoned"))?;
+        let mut document = Nip05Document::default();
+
+        let entries: Vec<(&String, &String)> = match name.map(nip05_name) {
+            Some(wanted) => names.get_key_value(wanted.as_str()).into_iter().collect(),
+            None => names.iter().collect(),
+        };
+        for (name, pubkey) in entries {
+            if !self.relays.is_empty() {
+                document.relays.insert(pubkey.clone(), self.relays.clone());
+            }
+            document.names.insert(name.clone(), pubkey.clone());
+        }
+
+        Ok(document)
+    }
+}
+
+fn response(status: StatusCode, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    // NIP-05 requires CORS so web clients can verify identifiers
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        header::HeaderValue::from_static("*"),
+    );
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Answers one HTTP request against the directory
+pub fn handle_request(
+    directory: &Nip05Directory,
+    method: &Method,
+    path: &str,
+    query: Option<&str>,
+) -> Response<Full<Bytes>> {
+    if path != NIP05_PATH {
+        return response(StatusCode::NOT_FOUND, r#"{"error":"not found"}"#);
+    }
+    if method == Method::OPTIONS {
+        return response(StatusCode::NO_CONTENT, Bytes::new());
+    }
+    if method != Method::GET {
+        return response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            r#"{"error":"method not allowed"}"#,
+        );
+    }
+
+    let name = url::form_urlencoded::parse(query.unwrap_or("").as_bytes())
+        .find(|(key, _)| key == "name")
+        .map(|(_, value)| value.into_owned());
+
+    let result = directory
+        .document(name.as_deref())
+        .and_then(|document| serde_json::to_vec(&document).context("Failed to serialize"));
+    match result {
+        Ok(body) => response(StatusCode::OK, body),
+        Err(e) => {
+            warn!("Failed to build NIP-05 document: {e:#}");
+            response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                r#"{"error":"internal error"}"#,
+            )
+        }
+    }
+}
+
+/// Accepts connections on `listener` until `shutdown` completes
+pub async fn serve(
+    listener: TcpListener,
+    directory: Arc<Nip05Directory>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = &mut shutdown => return Ok(()),
+            accepted = listener.accept() => accepted.context("Failed to accept connection")?,
+        };
+
+        let directory = directory.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request: Request<Incoming>| {
+                let directory = directory.clone();
+                async move {
+                    debug!(
+                        "{method} {uri}",
+                        method = request.method(),
+                        uri = request.uri()
+                    );
+                    Ok::<_, Infallible>(handle_request(
+                        &directory,
+                        request.method(),
+                        request.uri().path(),
+                        request.uri().query(),
+                    ))
+                }
+            });
+
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Connection from {peer} failed: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_signers;
+    use crate::twitter::User;
+
+    fn directory_with_profile(temp_dir: &tempfile::TempDir) -> Result<Nip05Directory> {
+        let user = User {
+            id: "123456".to_string(),
+            username: "TestUser".to_string(),
+            name: Some("Test User".to_string()),
+            ..Default::default()
+        };
+        storage::save_user_profile(&user, temp_dir.path())?;
+
+        Nip05Directory::load(
+            temp_dir.path(),
+            &["wss://relay.example.com".to_string()],
+            &test_signers(),
+        )
+    }
+
+    #[test]
+    fn test_nip05_identifier() {
+        assert_eq!(
+            nip05_identifier("@JackDorsey", "example.com"),
+            "jackdorsey@example.com"
+        );
+    }
+
+    #[test]
+    fn test_with_nip05() {
+        let metadata = with_nip05(Metadata::new(), "TestUser", Some("mirror.example.com"));
+        assert_eq!(
+            metadata.nip05.as_deref(),
+            Some("testuser@mirror.example.com")
+        );
+
+        let metadata = with_nip05(Metadata::new(), "TestUser", None);
+        assert!(metadata.nip05.is_none());
+    }
+
+    #[test]
+    fn test_refresh_picks_up_new_profiles() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let directory = Nip05Directory::load(temp_dir.path(), &[], &test_signers())?;
+        assert!(directory.document(Some("testuser"))?.names.is_empty());
+
+        let user = User {
+            id: "123456".to_string(),
+            username: "TestUser".to_string(),
+            ..Default::default()
+        };
+        storage::save_user_profile(&user, temp_dir.path())?;
+        // Requests are served from the map, not the profiles on disk
+        assert!(directory.document(Some("testuser"))?.names.is_empty());
+
+        directory.refresh()?;
+        let document = directory.document(None)?;
+        assert!(document.names.contains_key("testuser"));
+        assert!(document.relays.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_serve_nip05_document() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let directory = Arc::new(directory_with_profile(&temp_dir)?);
+        let expected_pubkey = test_signers()
+            .keys()
+            .derive_keys("123456")?
+            .public_key()
+            .to_hex();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, directory, async {
+            let _ = shutdown_rx.await;
+        }));
+
+        let http = reqwest::Client::new();
+        let response = http
+            .get(format!("http://{addr}{NIP05_PATH}?name=testuser"))
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get("access-control-allow-origin")
+                .and_then(|v| v.to_str().ok()),
+            Some("*")
+        );
+        let document: Nip05Document = response.json().await?;
+        assert_eq!(document.names.get("testuser"), Some(&expected_pubkey));
+        assert_eq!(
+            document.relays.get(&expected_pubkey),
+            Some(&vec!["wss://relay.example.com".to_string()])
+        );
+
+        let unknown: Nip05Document = http
+            .get(format!("http://{addr}{NIP05_PATH}?name=nobody"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert!(unknown.names.is_empty());
+
+        let not_found = http.get(format!("http://{addr}/other")).send().await?;
+        assert_eq!(not_found.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let _ = shutdown_tx.send(());
+        server.await??;
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::{Context, Result, bail, ensure};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use nostr_sdk::{EventBuilder, Kind, Tag};
+use reqwest::StatusCode;
+use reqwest::header::AUTHORIZATION;
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_util::io::ReaderStream;
+use tracing::debug;
+
+use crate::signer::UserSigner;
+
+/// Where NIP-96 servers describe their API
+const WELL_KNOWN_PATH: &str = "/.well-known/nostr/nip96.json";
+
+/// Kind of NIP-98 HTTP authorization events
+const HTTP_AUTH_KIND: u16 = 27235;
+
+/// Polls of `processing_url` before giving up on a delayed upload
+const MAX_PROCESSING_POLLS: usize = 30;
+
+/// Wait between polls of `processing_url`
+const PROCESSING_POLL_DELAY: Duration = Duration::from_secs(2);
+
+/// The part of `/.well-known/nostr/nip96.json` needed for uploading
+#[derive(Debug, Clone, Deserialize)]
+struct ServerInfo {
+    #[serde(default)]
+    api_url: String,
+    /// Another server handling uploads for this one
+    delegated_to_url: Option<String>,
+}
+
+/// NIP-94 file metadata returned for an upload, as a list of tags
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Nip94Event {
+    #[serde(default)]
+    pub tags: Vec<Vec<String>>,
+    #[serde(default)]
+    pub content: String,
+}
+
+impl Nip94Event {
+    /// First value of a tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.first().map(String::as_str) == Some(name))
+            .and_then(|tag| tag.get(1))
+            .map(String::as_str)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    status: String,
+    #[serde(default)]
+    message: String,
+    processing_url: Option<String>,
+    nip94_event: Option<Nip94Event>,
+}
+
+/// Signs a NIP-98 authorization for one request and encodes it as an `Authorization` header value
+pub async fn http_auth_header(
+    signer: &UserSigner,
+    url: &str,
+    method: &str,
+    payload_sha256: Option<&str>,
+) -> Result<String> {
+    let mut builder = EventBuilder::new(Kind::Custom(HTTP_AUTH_KIND), "")
+        .tag(Tag::parse(vec!["u", url])?)
+        .tag(Tag::parse(vec!["method", method])?);
+    if let Some(payload) = payload_sha256 {
+        builder = builder.tag(Tag::parse(vec!["payload", payload])?);
+    }
+    let event = signer
+        .sign_event(builder)
+        .await
+        .context("Failed to sign NIP-98 authorization")?;
+    let json = serde_json::to_string(&event).context("Failed to serialize NIP-98 authorization")?;
+    Ok(format!("Nostr {token}", token = STANDARD.encode(json)))
+}
+
+/// Client for the upload API of one NIP-96 server
+pub struct Nip96Client {
+    http: reqwest::Client,
+    /// Server URL as configured, without trailing slash
+    server: String,
+    /// Upload endpoint announced by the server
+    api_url: String,
+}
+
+impl Nip96Client {
+    /// Looks up the upload endpoint in the server's `nip96.json`, following one delegation
+    pub async fn discover(server: &str) -> Result<Self> {
+        let server = server.trim_end_matches('/');
+        ensure!(
+            server.starts_with("http://") || server.starts_with("https://"),
+            "NIP-96 server URL must start with http:// or https://, got {server}"
+        );
+        let http = reqwest::Client::new();
+
+        let mut info = fetch_server_info(&http, server).await?;
+        if info.api_url.is_empty()
+            && let Some(delegate) = &info.delegated_to_url
+        {
+            debug!("NIP-96 server {server} delegates uploads to {delegate}");
+            info = fetch_server_info(&http, delegate.trim_end_matches('/')).await?;
+        }
+        ensure!(
+            !info.api_url.is_empty(),
+            "NIP-96 server {server} announces no api_url"
+        );
+
+        Ok(Self {
+            http,
+            server: server.to_string(),
+            api_url: info.api_url,
+        })
+    }
+
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// Uploads a file, streaming it from disk, and returns its NIP-94 metadata
+    ///
+    /// Uploads the server processes in the background are polled until done.
+    pub async fn upload_file(
+        &self,
+        path: &Path,
+        sha256: &str,
+        mime_type: &str,
+        signer: &UserSigner,
+    ) -> Result<Nip94Event> {
+        let size = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("Failed to read metadata of {path}", path = path.display()))?
+            .len();
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .context("Invalid file name")?;
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {path}", path = path.display()))?;
+
+        let part =
+            Part::stream_with_length(reqwest::Body::wrap_stream(ReaderStream::new(file)), size)
+                .file_name(file_name.to_string())
+                .mime_str(mime_type)
+                .context("Invalid MIME type")?;
+        let form = Form::new()
+            .part("file", part)
+            .text("size", size.to_string())
+            .text("content_type", mime_type.to_string());
+        let auth = http_auth_header(signer, &self.api_url, "POST", Some(sha256)).await?;
+
+        debug!(
+            "Uploading {size} byte file {path} to {api_url}",
+            path = path.display(),
+            api_url = self.api_url
+        );
+        let response = self
+            .http
+            .post(&self.api_url)
+            .header(AUTHORIZATION, auth)
+            .multipart(form)
+            .send()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to reach NIP-96 server {server}",
+                    server = self.server
+                )
+            })?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .context("Failed to read NIP-96 response")?;
+        let upload: UploadResponse = serde_json::from_str(&body).with_context(|| {
+            format!(
+                "NIP-96 server {server} answered {status} with unexpected body: {body}",
+                server = self.server
+            )
+        })?;
+        if !status.is_success() || upload.status == "error" {
+            bail!(
+                "NIP-96 server {server} rejected the upload ({status}): {message}",
+                server = self.server,
+                message = upload.message
+            );
+        }
+
+        match (upload.nip94_event, upload.processing_url) {
+            (Some(event), _) => Ok(event),
+            (None, Some(processing_url)) => self.wait_for_processing(&processing_url).await,
+            (None, None) => bail!(
+                "NIP-96 server {server} returned no nip94_event",
+                server = self.server
+            ),
+        }
+    }
+
+    async fn wait_for_processing(&self, processing_url: &str) -> Result<Nip94Event> {
+        for _ in 0..MAX_PROCESSING_POLLS {
+            sleep(PROCESSING_POLL_DELAY).await;
+            let response = self
+                .http
+                .get(processing_url)
+                .send()
+                .await
+                .with_context(|| {
+                    format!("Failed to poll NIP-96 processing URL {processing_url}")
+                })?;
+            let status = response.status();
+            let progress: UploadResponse = response
+                .json()
+                .await
+                .context("Failed to parse NIP-96 processing status")?;
+            if progress.status == "error" {
+                bail!(
+                    "NIP-96 server {server} failed to process the upload: {message}",
+                    server = self.server,
+                    message = progress.message
+                );
+            }
+            if status == StatusCode::CREATED
+                && let Some(event) = progress.nip94_event
+            {
+                return Ok(event);
+            }
+            debug!("NIP-96 upload still processing at {processing_url}");
+        }
+        bail!(
+            "NIP-96 server {server} is still processing the upload after {MAX_PROCESSING_POLLS} polls",
+            server = self.server
+        )
+    }
+}
+
+async fn fetch_server_info(http: &reqwest::Client, server: &str) -> Result<ServerInfo> {
+    let url = format!("{server}{WELL_KNOWN_PATH}");
+    let response = http
+        .get(&url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {url}"))?;
+    ensure!(
+        response.status().is_success(),
+        "Failed to fetch {url}: {status}",
+        status = response.status()
+    );
+    response
+        .json()
+        .await
+        .with_context(|| format!("Failed to parse {url}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_discover_and_upload() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let api_url = format!("{url}/api/v2/media", url = server.url());
+        server
+            .mock("GET", WELL_KNOWN_PATH)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({ "api_url": api_url }).to_string())
+            .create_async()
+            .await;
+        let upload = server
+            .mock("POST", "/api/v2/media")
+            .match_header("authorization", mockito::Matcher::Regex("^Nostr ".to_string()))
+            .match_header(
+                "content-type",
+                mockito::Matcher::Regex("^multipart/form-data".to_string()),
+            )
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"status":"success","message":"Upload successful.","nip94_event":{"tags":[["url","https://cdn.example.com/abc.png"],["ox","def"],["x","abc"],["m","image/png"],["dim","800x600"]],"content":""}}"#,
+            )
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("image.png");
+        std::fs::write(&path, b"png bytes")?;
+        let signer = UserSigner::Local(nostr_sdk::Keys::generate());
+
+        let client = Nip96Client::discover(&server.url()).await?;
+        let event = client
+            .upload_file(&path, "def", "image/png", &signer)
+            .await?;
+        upload.assert_async().await;
+        assert_eq!(event.tag("url"), Some("https://cdn.example.com/abc.png"));
+        assert_eq!(event.tag("dim"), Some("800x600"));
+        assert_eq!(event.tag("blurhash"), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_error() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let api_url = format!("{url}/upload", url = server.url());
+        server
+            .mock("GET", WELL_KNOWN_PATH)
+            .with_body(serde_json::json!({ "api_url": api_url }).to_string())
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/upload")
+            .with_status(413)
+            .with_body(r#"{"status":"error","message":"File too large"}"#)
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("video.mp4");
+        std::fs::write(&path, b"mp4 bytes")?;
+        let signer = UserSigner::Local(nostr_sdk::Keys::generate());
+
+        let client = Nip96Client::discover(&server.url()).await?;
+        let error = client
+            .upload_file(&path, "abc", "video/mp4", &signer)
+            .await
+            .expect_err("upload should fail");
+        assert!(error.to_string().contains("File too large"));
+        Ok(())
+    }
+}
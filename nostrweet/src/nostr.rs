@@ -1,21 +1,23 @@
+use crate::blob_ledger::{self, BlobLedger};
+use crate::blossom::{self, BlossomClient};
+use crate::content_templates::{ContentTemplates, TweetType};
+use crate::media_metadata::VisualMetadata;
+use crate::nip96::Nip94Event;
 use crate::nostr_linking::NostrLinkResolver;
+use crate::signer::UserSigner;
+use crate::twitter::Media as TwitterMedia;
 use anyhow::{Context, Result, bail};
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD;
 use nostr_sdk::ToBech32;
 use nostr_sdk::nips::nip65::RelayMetadata;
 use nostr_sdk::{
-    Alphabet, Client, Event, EventBuilder, Filter, Keys, Kind, PublicKey, RelayUrl,
-    SingleLetterTag, SubscriptionId, Tag, Timestamp, Url,
+    Alphabet, Client, Event, EventBuilder, Filter, Kind, PublicKey, RelayUrl, SingleLetterTag,
+    SubscriptionId, Tag, Url,
 };
-use reqwest::{StatusCode, header::RETRY_AFTER};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
-use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs;
-use tokio::time::sleep;
 use tokio::time::timeout;
 use tracing::{debug, info, warn};
 use url::Url as UrlParser;
@@ -159,6 +161,21 @@ pub fn build_twitter_status_url(tweet_id: &str) -> String {
     format!("https://twitter.com/i/status/{tweet_id}")
 }
 
+/// NIP-48 tag marking an event as bridged from a tweet
+///
+/// Events derived from the same tweet (notes, reposts, media events,
+/// deletions) share the URL, so other bridges can recognize them.
+pub fn tweet_proxy_tag(tweet_id: &str) -> Result<Tag> {
+    let url = format!("https://x.com/i/status/{tweet_id}");
+    Ok(Tag::parse(vec!["proxy", url.as_str(), "web"])?)
+}
+
+/// NIP-48 tag marking a profile as bridged from a Twitter account
+pub fn profile_proxy_tag(username: &str) -> Result<Tag> {
+    let url = format!("https://x.com/{username}");
+    Ok(Tag::parse(vec!["proxy", url.as_str(), "web"])?)
+}
+
 /// Structure to track Nostr event details
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NostrEventInfo {
@@ -176,189 +193,365 @@ pub struct NostrEventInfo {
     pub relays: Vec<String>,
     /// Complete Nostr event JSON
     pub event_json: Option<String>,
+    /// NIP-68 picture and NIP-71 video events published for the tweet
+    #[serde(default)]
+    pub media_event_ids: Vec<String>,
+    /// Kinds of the picture and video events, in the order of `media_event_ids`
+    #[serde(default)]
+    pub media_event_kinds: Vec<u16>,
+}
+
+impl NostrEventInfo {
+    /// Record of an event published for a tweet
+    pub fn from_event(
+        tweet_id: &str,
+        event: &Event,
+        media_urls: Vec<String>,
+        relays: &[String],
+    ) -> Result<Self> {
+        Ok(Self {
+            tweet_id: tweet_id.to_string(),
+            event_id: event.id.to_hex(),
+            pubkey: event.pubkey.to_hex(),
+            created_at: event.created_at.as_u64(),
+            media_urls,
+            relays: relays.to_vec(),
+            event_json: Some(
+                serde_json::to_string_pretty(event)
+                    .context("Failed to serialize Nostr event to JSON")?,
+            ),
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        })
+    }
+
+    /// IDs of every event published for the tweet, starting with `event_id`
+    pub fn event_ids(&self) -> Vec<&str> {
+        let mut ids = vec![self.event_id.as_str()];
+        for id in &self.media_event_ids {
+            if !ids.contains(&id.as_str()) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Kind of the recorded event, records without the event JSON are taken to be text notes
+    pub fn kind(&self) -> u16 {
+        self.event_json
+            .as_deref()
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(json).ok())
+            .and_then(|json| json.get("kind").and_then(serde_json::Value::as_u64))
+            .and_then(|kind| u16::try_from(kind).ok())
+            .unwrap_or(1)
+    }
+
+    /// Distinct kinds of every event published for the tweet, starting with `kind`
+    pub fn event_kinds(&self) -> Vec<u16> {
+        let mut kinds = vec![self.kind()];
+        for kind in &self.media_event_kinds {
+            if !kinds.contains(kind) {
+                kinds.push(*kind);
+            }
+        }
+        kinds
+    }
+}
+
+/// A media file attached to a note, stored on a media server (Blossom or
+/// NIP-96) or left on Twitter
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadedMedia {
+    /// URL on the first server holding the file
+    pub url: String,
+    /// URL of the file on Twitter
+    pub source: Option<String>,
+    /// URLs of the copies on the other servers
+    pub fallbacks: Vec<String>,
+    /// SHA-256 of the file as served
+    pub sha256: String,
+    /// SHA-256 of the uploaded file, when the server transformed it
+    pub original_sha256: Option<String>,
+    pub size: u64,
+    pub mime_type: String,
+    /// Dimensions (`<width>x<height>`)
+    pub dim: Option<String>,
+    pub blurhash: Option<String>,
+    /// Description of the media for the visually impaired
+    pub alt: Option<String>,
+    /// Preview image of a video
+    pub image: Option<String>,
+}
+
+impl UploadedMedia {
+    /// Media stored unchanged, as on Blossom servers
+    pub fn new(url: String, sha256: String, size: u64, mime_type: String) -> Self {
+        Self {
+            url,
+            source: None,
+            fallbacks: Vec::new(),
+            sha256,
+            original_sha256: None,
+            size,
+            mime_type,
+            dim: None,
+            blurhash: None,
+            alt: None,
+            image: None,
+        }
+    }
+
+    /// Adds what is known about the file locally and on Twitter
+    ///
+    /// Metadata returned by the media server takes precedence over the
+    /// dimensions and blurhash computed locally.
+    pub fn describe(&mut self, visual: VisualMetadata, media: Option<&TwitterMedia>) {
+        self.dim = self.dim.take().or(visual.dim);
+        self.blurhash = self.blurhash.take().or(visual.blurhash);
+
+        let Some(media) = media else {
+            return;
+        };
+        self.source = crate::media::media_item_url(media);
+        self.alt = media
+            .alt_text
+            .as_deref()
+            .map(str::trim)
+            .filter(|alt| !alt.is_empty())
+            .map(str::to_string);
+        if matches!(media.type_field.as_str(), "video" | "animated_gif") {
+            self.image = media.preview_image_url.clone();
+        }
+    }
+
+    /// Media described by the NIP-94 metadata a server returned for an upload
+    ///
+    /// The local hash, size and type are kept for fields the server left out.
+    pub fn from_nip94(
+        event: &Nip94Event,
+        sha256: &str,
+        size: u64,
+        mime_type: &str,
+    ) -> Result<Self> {
+        let url = event.tag("url").context("NIP-94 metadata has no url")?;
+        let mut media = Self::new(
+            url.to_string(),
+            sha256.to_string(),
+            size,
+            mime_type.to_string(),
+        );
+        media.merge_nip94(event);
+        Ok(media)
+    }
+
+    /// Takes over the fields a server's NIP-94 metadata describes
+    pub fn merge_nip94(&mut self, event: &Nip94Event) {
+        if let Some(x) = event.tag("x")
+            && x != self.sha256
+        {
+            self.original_sha256 = Some(event.tag("ox").unwrap_or(&self.sha256).to_string());
+            self.sha256 = x.to_string();
+        }
+        if let Some(size) = event.tag("size").and_then(|s| s.parse().ok()) {
+            self.size = size;
+        }
+        if let Some(mime_type) = event.tag("m") {
+            self.mime_type = mime_type.to_string();
+        }
+        if let Some(dim) = event.tag("dim") {
+            self.dim = Some(dim.to_string());
+        }
+        if let Some(blurhash) = event.tag("blurhash") {
+            self.blurhash = Some(blurhash.to_string());
+        }
+    }
+
+    /// NIP-92 `imeta` tag describing the file and where else to find it
+    pub fn imeta_tag(&self) -> Result<Tag> {
+        let mut values = vec![
+            "imeta".to_string(),
+            format!("url {url}", url = self.url),
+            format!("m {mime_type}", mime_type = self.mime_type),
+            format!("x {sha256}", sha256 = self.sha256),
+            format!("size {size}", size = self.size),
+        ];
+        if let Some(original_sha256) = &self.original_sha256 {
+            values.push(format!("ox {original_sha256}"));
+        }
+        if let Some(dim) = &self.dim {
+            values.push(format!("dim {dim}"));
+        }
+        if let Some(blurhash) = &self.blurhash {
+            values.push(format!("blurhash {blurhash}"));
+        }
+        if let Some(alt) = &self.alt {
+            values.push(format!("alt {alt}"));
+        }
+        if let Some(image) = &self.image {
+            values.push(format!("image {image}"));
+        }
+        values.extend(
+            self.fallbacks
+                .iter()
+                .map(|fallback| format!("fallback {fallback}")),
+        );
+        Ok(Tag::parse(values)?)
+    }
 }
 
 /// Upload media files to Blossom servers
+///
+/// Each file is uploaded to the first server accepting it and then mirrored
+/// (BUD-04) to every other server, falling back to a direct upload where
+/// mirroring fails. Blobs a server already has, according to the blob ledger
+/// and `HEAD /<sha256>`, are not uploaded again.
 pub async fn upload_media_to_blossom(
     media_files: &[PathBuf],
     blossom_servers: &[String],
-    keys: &Keys,
-) -> Result<Vec<String>> {
+    signer: &UserSigner,
+    data_dir: &Path,
+) -> Result<Vec<UploadedMedia>> {
     if blossom_servers.is_empty() {
         bail!("No Blossom servers provided for media upload");
     }
 
-    let mut uploaded_urls = Vec::new();
-    let client = reqwest::Client::new();
-
+    let clients = blossom_servers
+        .iter()
+        .map(|server| BlossomClient::new(server))
+        .collect::<Result<Vec<_>>>()?;
+    let mut uploaded = Vec::new();
     for media_file in media_files {
-        let file_name = media_file
-            .file_name()
-            .and_then(|n| n.to_str())
-            .context("Invalid file name")?;
-
-        // Try to determine MIME type from extension
-        let mime_type = mime_type_from_path(media_file)?;
-
-        debug!("Uploading media file: {file_name} ({mime_type})");
-
-        // Try upload with retries on 429 Too Many Requests
-        let mut upload_success = false;
-        let mut upload_url = String::new();
-        const MAX_RETRIES: usize = 3;
-        const RETRY_DELAY_MS: u64 = 500;
-        for blossom_server in blossom_servers {
-            let server_url = if blossom_server.ends_with('/') {
-                blossom_server.clone()
-            } else {
-                format!("{blossom_server}/")
-            };
+        uploaded.push(upload_file_to_blossom(media_file, &clients, signer, data_dir).await?);
+    }
 
-            // Read file content
-            let file_content = fs::read(media_file).await.with_context(|| {
-                format!(
-                    "Failed to read media file {path}",
-                    path = media_file.display()
-                )
+    Ok(uploaded)
+}
+
+/// Makes sure one media file is on every Blossom server
+async fn upload_file_to_blossom(
+    media_file: &Path,
+    clients: &[BlossomClient],
+    signer: &UserSigner,
+    data_dir: &Path,
+) -> Result<UploadedMedia> {
+    let file_name = media_file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("Invalid file name")?;
+    let mime_type = mime_type_from_path(media_file)?;
+    let size = fs::metadata(media_file)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to read metadata of media file {path}",
+                path = media_file.display()
+            )
+        })?
+        .len();
+
+    let known_sha256 = BlobLedger::load(data_dir)?
+        .file_sha256(file_name, size)
+        .map(str::to_string);
+    let sha256 = match known_sha256 {
+        Some(sha256) => sha256,
+        None => {
+            let sha256 = blossom::sha256_file(media_file).await?;
+            BlobLedger::update(data_dir, |ledger| {
+                ledger.record_file(file_name, &sha256, size)
             })?;
+            sha256
+        }
+    };
 
-            // Compute SHA-256 for HEAD
-            let mut hasher = Sha256::new();
-            hasher.update(&file_content);
-            let sha256_hex = format!("{:x}", hasher.finalize());
-
-            // Initial HEAD request to get invoice or authorization challenge
-            let head_resp = client
-                .head(format!("{server_url}upload"))
-                .header("X-Content-Length", file_content.len().to_string())
-                .header("X-Content-Type", mime_type.clone())
-                .header("X-SHA-256", sha256_hex.clone())
-                .send()
-                .await?;
-            let invoice_opt = head_resp
-                .headers()
-                .get("X-Lightning")
-                .and_then(|v| v.to_str().ok())
-                .map(String::from);
-            // Build Nostr auth event
-            let now: Timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)?
-                .as_secs()
-                .into();
-            let auth_builder = EventBuilder::new(Kind::Custom(27235), String::new())
-                .custom_created_at(now)
-                .tag(Tag::parse(vec!["u", &format!("{server_url}upload")])?)
-                .tag(Tag::parse(vec!["method", "PUT"])?);
-            let auth_event = auth_builder.sign(keys).await?;
-            let auth_header = format!(
-                "Nostr {}",
-                STANDARD.encode(serde_json::to_string(&auth_event)?)
-            );
+    let mut urls: Vec<String> = Vec::new();
+    // Metadata some servers return for uploads (BUD-08)
+    let mut nip94 = None;
+    for client in clients {
+        let server = client.server();
+        let mirrored = match urls.first() {
+            Some(source_url) => blob_ledger::mirror_to_server(
+                client,
+                source_url,
+                &sha256,
+                size,
+                Some(&mime_type),
+                signer,
+                data_dir,
+            )
+            .await
+            .inspect_err(|e| debug!("Mirroring {file_name} to {server} failed: {e:#}"))
+            .ok(),
+            None => None,
+        };
+        if let Some(url) = mirrored {
+            urls.push(url);
+            continue;
+        }
 
-            // Upload with retry on HTTP 429
-            let mut attempts = 0;
-            while attempts < MAX_RETRIES {
-                attempts += 1;
-                let mut req = client
-                    .put(format!("{server_url}upload"))
-                    .header("Content-Type", mime_type.clone())
-                    .header("Authorization", auth_header.clone());
-                if let Some(invoice) = &invoice_opt {
-                    req = req.header("X-Lightning", invoice);
-                }
-                let resp = req.body(file_content.clone()).send().await;
-                match resp {
-                    Ok(r) if r.status() == StatusCode::TOO_MANY_REQUESTS => {
-                        warn!(
-                            "429 Too Many Requests from {server_url}, retry {}/{}",
-                            attempts, MAX_RETRIES
-                        );
-                        if attempts < MAX_RETRIES {
-                            // respect Retry-After header if provided
-                            let wait_dur = if let Some(val) = r.headers().get(RETRY_AFTER) {
-                                if let Ok(s) = val.to_str() {
-                                    if let Ok(secs) = s.parse::<u64>() {
-                                        Duration::from_secs(secs)
-                                    } else {
-                                        Duration::from_millis(RETRY_DELAY_MS)
-                                    }
-                                } else {
-                                    Duration::from_millis(RETRY_DELAY_MS)
-                                }
-                            } else {
-                                Duration::from_millis(RETRY_DELAY_MS)
-                            };
-                            warn!("Waiting {wait_dur:?} before retry");
-                            sleep(wait_dur).await;
-                            continue;
-                        } else {
-                            warn!("Exceeded retries for {server_url}");
-                            break;
-                        }
-                    }
-                    Ok(r) => {
-                        if r.status().is_success() {
-                            // Parse response JSON once
-                            let json: serde_json::Value = r.json().await.with_context(|| {
-                                format!("Failed to parse Blossom response JSON for {server_url}")
-                            })?;
-                            // Try top-level "url" or fallback to nip94_event tags
-                            let url_opt = json
-                                .get("url")
-                                .and_then(|u| u.as_str())
-                                .map(String::from)
-                                .or_else(|| {
-                                    json.get("nip94_event")
-                                        .and_then(|ev| ev.get("tags"))
-                                        .and_then(|tags| tags.as_array())
-                                        .and_then(|tags_arr| {
-                                            tags_arr.iter().find_map(|tag| {
-                                                tag.as_array().and_then(|arr| {
-                                                    if arr.first()?.as_str()? == "url" {
-                                                        arr.get(1)?.as_str().map(String::from)
-                                                    } else {
-                                                        None
-                                                    }
-                                                })
-                                            })
-                                        })
-                                });
-                            if let Some(u) = url_opt {
-                                upload_url = u;
-                                upload_success = true;
-                                debug!("Successfully uploaded to Blossom server: {server_url}");
-                            } else {
-                                warn!(
-                                    "Blossom response missing URL field and nip94_event url tag: {json}"
-                                );
-                            }
-                        } else {
-                            warn!("Blossom server error {status}", status = r.status());
-                        }
-                        break;
-                    }
-                    Err(e) => {
-                        warn!("Upload error to Blossom server {server_url}: {e}");
-                        break;
-                    }
-                }
+        match blob_ledger::find_on_server(
+            client,
+            &sha256,
+            size,
+            Some(&mime_type),
+            Some(&signer.public_key()),
+            data_dir,
+        )
+        .await
+        {
+            Ok(Some(url)) => {
+                debug!("{server} already has {file_name}, skipping upload");
+                urls.push(url);
+                continue;
             }
-            if !upload_success {
-                bail!(
-                    "Failed to upload media file {file_name} to any Blossom server",
-                    file_name = media_file.display()
-                );
+            Ok(None) => {}
+            Err(e) => {
+                warn!("{e:#}");
+                continue;
             }
         }
 
-        uploaded_urls.push(upload_url);
+        debug!("Uploading media file {file_name} ({mime_type}) to {server}");
+        match client
+            .upload_file(media_file, &sha256, &mime_type, signer)
+            .await
+        {
+            Ok(descriptor) => {
+                BlobLedger::update(data_dir, |ledger| {
+                    ledger.record_blob(server, &descriptor, Some(&signer.public_key()))
+                })?;
+                if urls.is_empty() {
+                    nip94 = descriptor.nip94_event();
+                }
+                urls.push(descriptor.url);
+            }
+            Err(e) => warn!("{e:#}"),
+        }
+    }
+
+    let Some((url, fallbacks)) = urls.split_first() else {
+        bail!(
+            "Failed to upload media file {path} to any Blossom server",
+            path = media_file.display()
+        );
+    };
+    if urls.len() < clients.len() {
+        warn!(
+            "{file_name} is only on {count} of {total} Blossom servers, run `blossom repair` later",
+            count = urls.len(),
+            total = clients.len()
+        );
     }
 
-    Ok(uploaded_urls)
+    let mut media = UploadedMedia::new(url.clone(), sha256, size, mime_type);
+    media.fallbacks = fallbacks.to_vec();
+    if let Some(nip94) = nip94 {
+        media.merge_nip94(&nip94);
+    }
+    Ok(media)
 }
 
 /// Determine MIME type from file path
-fn mime_type_from_path(path: &Path) -> Result<String> {
+pub fn mime_type_from_path(path: &Path) -> Result<String> {
     // Extract extension and strip any query parameters (e.g. mp4?tag=12)
     let ext_raw = path
         .extension()
@@ -380,11 +573,12 @@ fn mime_type_from_path(path: &Path) -> Result<String> {
     Ok(mime_type.to_string())
 }
 
-pub async fn initialize_nostr_client(keys: &Keys, relays: &[String]) -> Result<Client> {
-    // In nostr-sdk 0.42, Client::new() requires a signer
-    let client = Client::new(keys.clone());
-
-    // Keys are already set in the constructor
+pub async fn initialize_nostr_client(signer: &UserSigner, relays: &[String]) -> Result<Client> {
+    // Events are signed before publishing, local keys are only attached for relay auth
+    let client = match signer.keys() {
+        Some(keys) => Client::new(keys.clone()),
+        None => Client::default(),
+    };
 
     // Add relays
     for relay_url in relays {
@@ -409,21 +603,24 @@ pub async fn check_existing_nostr_event(
     data_dir: &Path,
     tweet_id: &str,
 ) -> Result<Option<NostrEventInfo>> {
-    let nostr_dir = data_dir.join("nostr");
-    let event_info_path = nostr_dir.join(format!("{tweet_id}.json"));
+    load_nostr_event_info(data_dir, tweet_id)
+}
 
-    if event_info_path.exists() {
-        let content = fs::read_to_string(&event_info_path)
-            .await
-            .with_context(|| format!("Failed to read Nostr event info for tweet {tweet_id}"))?;
+/// Read the `nostr/<tweet_id>.json` record for a mirrored tweet, if there is one
+///
+/// Synchronous so the link resolver can use it while formatting content.
+pub fn load_nostr_event_info(data_dir: &Path, tweet_id: &str) -> Result<Option<NostrEventInfo>> {
+    let event_info_path = data_dir.join("nostr").join(format!("{tweet_id}.json"));
+    if !event_info_path.exists() {
+        return Ok(None);
+    }
 
-        let event_info: NostrEventInfo = serde_json::from_str(&content)
-            .with_context(|| format!("Failed to parse Nostr event info for tweet {tweet_id}"))?;
+    let content = std::fs::read_to_string(&event_info_path)
+        .with_context(|| format!("Failed to read Nostr event info for tweet {tweet_id}"))?;
+    let event_info: NostrEventInfo = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse Nostr event info for tweet {tweet_id}"))?;
 
-        Ok(Some(event_info))
-    } else {
-        Ok(None)
-    }
+    Ok(Some(event_info))
 }
 
 /// Save Nostr event information to file
@@ -462,10 +659,8 @@ pub async fn save_nostr_event_info(
 pub async fn find_existing_event(
     client: &Client,
     tweet_id: &str,
-    keys: &Keys,
+    pubkey: PublicKey,
 ) -> Result<Option<Event>> {
-    // Get the public key directly from the keys
-    let pubkey = keys.public_key();
     let subscription_id = SubscriptionId::generate();
 
     // Look for the author's events (us) that reference the tweet URL
@@ -477,7 +672,7 @@ pub async fn find_existing_event(
     // Use custom_tag to filter by the 'r' tag with the specific Twitter URL
     let filter = Filter::new()
         .author(pubkey)
-        .kind(Kind::TextNote)
+        .kinds(crate::media_events::mirrored_kinds())
         .custom_tag(SingleLetterTag::lowercase(Alphabet::R), twitter_url.clone())
         .limit(10);
 
@@ -791,28 +986,27 @@ fn extract_additional_mentions(
 }
 
 /// Format a tweet as Nostr content with mention resolution
+///
+/// The layout comes from the content template of the tweet's type.
 pub fn format_tweet_as_nostr_content_with_mentions(
     tweet: &crate::twitter::Tweet,
     media_urls: &[String],
     resolver: &mut NostrLinkResolver,
+    templates: &ContentTemplates,
 ) -> Result<(String, Vec<PublicKey>)> {
-    let mut content = String::new();
     let mut all_mentioned_pubkeys = Vec::new();
 
     let (is_simple_retweet, rt_username) = analyze_retweet(tweet);
+    let tweet_type = TweetType::of(tweet);
+    let template = templates.for_type(tweet_type);
 
-    add_author_info(&mut content, tweet, is_simple_retweet);
-    let (used_media_urls, mentioned_pubkeys) = add_tweet_content_with_mentions(
-        &mut content,
-        tweet,
-        is_simple_retweet,
-        media_urls,
-        resolver,
-    )?;
+    let (text, used_media_urls, mentioned_pubkeys) =
+        tweet_text_with_mentions(tweet, is_simple_retweet, media_urls, resolver)?;
     all_mentioned_pubkeys.extend(mentioned_pubkeys);
 
+    let mut references = String::new();
     let ref_mentioned_pubkeys = add_referenced_tweets_with_mentions(
-        &mut content,
+        &mut references,
         tweet,
         is_simple_retweet,
         &rt_username,
@@ -821,16 +1015,54 @@ pub fn format_tweet_as_nostr_content_with_mentions(
     all_mentioned_pubkeys.extend(ref_mentioned_pubkeys);
 
     // For simple retweets, don't add media URLs since they belong to the retweeted content
+    let mut media = String::new();
     if !is_simple_retweet {
-        add_media_urls(&mut content, media_urls, &used_media_urls);
+        add_media_urls(&mut media, media_urls, &used_media_urls);
     }
-    add_original_tweet_url(&mut content, &tweet.id);
+
+    let mut values = BTreeMap::from([
+        ("author", author_label(tweet)),
+        ("text", text),
+        ("references", references),
+        ("media", media),
+        ("links", tweet_links(tweet)),
+        ("date", tweet_date(tweet)),
+        ("tweet_url", build_twitter_status_url(&tweet.id)),
+    ]);
+
+    if ["quoted_author", "quoted_text", "quoted_url"]
+        .iter()
+        .any(|variable| template.uses(variable))
+        && let Some(ref_tweet) = tweet_type.quoted_reference().and_then(|type_field| {
+            tweet
+                .referenced_tweets
+                .iter()
+                .flatten()
+                .find(|r| r.type_field == type_field)
+        })
+    {
+        values.insert("quoted_url", build_twitter_status_url(&ref_tweet.id));
+        if let Some(ref_data) = &ref_tweet.data {
+            let (quoted_author, quoted_text, quoted_pubkeys) =
+                quoted_tweet_with_mentions(ref_data, resolver)?;
+            values.insert("quoted_author", quoted_author);
+            values.insert("quoted_text", quoted_text);
+            for pubkey in quoted_pubkeys {
+                if !all_mentioned_pubkeys.contains(&pubkey) {
+                    all_mentioned_pubkeys.push(pubkey);
+                }
+            }
+        }
+    }
+
+    let mut content = template.render(&values);
+    content.truncate(content.trim_end().len());
 
     Ok((content, all_mentioned_pubkeys))
 }
 
 /// Check if a tweet is a simple retweet and extract username if possible
-fn analyze_retweet(tweet: &crate::twitter::Tweet) -> (bool, Option<String>) {
+pub fn analyze_retweet(tweet: &crate::twitter::Tweet) -> (bool, Option<String>) {
     let Some(ref_tweets) = &tweet.referenced_tweets else {
         return (false, None);
     };
@@ -867,35 +1099,83 @@ fn analyze_retweet(tweet: &crate::twitter::Tweet) -> (bool, Option<String>) {
     (is_rt, username)
 }
 
-/// Add author information to the content
-fn add_author_info(content: &mut String, tweet: &crate::twitter::Tweet, is_simple_retweet: bool) {
-    if is_simple_retweet {
-        return;
-    }
-
+/// How the author is named in the `author` template variable
+fn author_label(tweet: &crate::twitter::Tweet) -> String {
     if !tweet.author.username.is_empty() {
-        content.push_str(&format!(
-            "🐦 @{username}: ",
-            username = tweet.author.username
-        ));
+        format!("@{username}", username = tweet.author.username)
     } else if let Some(author_id) = &tweet.author_id {
-        content.push_str(&format!("🐦 User {author_id}: "));
+        format!("User {author_id}")
     } else {
-        content.push_str("🐦 Tweet: ");
+        // Notes of tweets without any author said "Tweet:" before templates
+        "Tweet".to_string()
     }
 }
 
-/// Add the main tweet content with mention resolution
-/// Returns the list of media URLs that were used inline and mentioned pubkeys
-fn add_tweet_content_with_mentions(
-    content: &mut String,
+/// Expanded links of a tweet that aren't its media, one per line
+fn tweet_links(tweet: &crate::twitter::Tweet) -> String {
+    let Some(urls) = tweet.entities.as_ref().and_then(|e| e.urls.as_ref()) else {
+        return String::new();
+    };
+    urls.iter()
+        .filter_map(|url_entity| url_entity.expanded_url.as_ref().map(|e| (url_entity, e)))
+        .filter(|(url_entity, expanded_url)| {
+            is_valid_url(expanded_url)
+                && !is_twitter_media_url(expanded_url, &url_entity.display_url)
+        })
+        .map(|(_, expanded_url)| format!("{expanded_url}\n"))
+        .collect()
+}
+
+/// Posting date of a tweet for the `date` template variable
+fn tweet_date(tweet: &crate::twitter::Tweet) -> String {
+    match crate::datetime_utils::parse_rfc3339(&tweet.created_at) {
+        Ok(date) => format!(
+            "{date} UTC",
+            date = crate::datetime_utils::format_for_display(&date)
+        ),
+        Err(_) => tweet.created_at.clone(),
+    }
+}
+
+/// Author and text of a referenced tweet for the `quoted_*` template variables
+fn quoted_tweet_with_mentions(
+    ref_data: &crate::twitter::Tweet,
+    resolver: &mut NostrLinkResolver,
+) -> Result<(String, String, Vec<PublicKey>)> {
+    let mut mentioned_pubkeys = Vec::new();
+    resolver.add_known_user(&ref_data.author.username, &ref_data.author.id)?;
+
+    let author = if let Some(pubkey) = resolver.resolve_username(&ref_data.author.username)? {
+        mentioned_pubkeys.push(pubkey);
+        let npub = pubkey
+            .to_bech32()
+            .map_err(|e| anyhow::anyhow!("Failed to convert to bech32: {e}"))?;
+        format!("nostr:{npub}")
+    } else {
+        format!("@{username}", username = ref_data.author.username)
+    };
+
+    let enriched_tweet = EnrichedTweet::from(ref_data.clone());
+    let mut formatter = TweetFormatter {
+        enriched_tweet: &enriched_tweet,
+        resolver,
+    };
+    let formatted = formatter.process_content_with_mentions()?;
+    mentioned_pubkeys.extend(formatted.mentioned_pubkeys);
+
+    Ok((author, formatted.text, mentioned_pubkeys))
+}
+
+/// The main tweet text with mention resolution
+/// Returns the text, the list of media URLs that were used inline and mentioned pubkeys
+fn tweet_text_with_mentions(
     tweet: &crate::twitter::Tweet,
     is_simple_retweet: bool,
     media_urls: &[String],
     resolver: &mut NostrLinkResolver,
-) -> Result<(Vec<String>, Vec<PublicKey>)> {
+) -> Result<(String, Vec<String>, Vec<PublicKey>)> {
     if is_simple_retweet {
-        return Ok((Vec::new(), Vec::new()));
+        return Ok((String::new(), Vec::new(), Vec::new()));
     }
 
     // Add the tweet author to the resolver so mentions can find them
@@ -920,10 +1200,7 @@ fn add_tweet_content_with_mentions(
     let (text_with_mentions, mentioned_pubkeys) =
         process_mentions_in_text(&expanded_text, tweet.entities.as_ref(), resolver)?;
 
-    content.push_str(&text_with_mentions);
-    content.push_str("\n\n");
-
-    Ok((used_media_urls, mentioned_pubkeys))
+    Ok((text_with_mentions, used_media_urls, mentioned_pubkeys))
 }
 
 /// Format a reply tweet with mention resolution
@@ -935,6 +1212,12 @@ fn format_reply_tweet_with_mentions(
 ) -> Result<Vec<PublicKey>> {
     let mut mentioned_pubkeys = Vec::new();
 
+    // A mirrored parent is linked with NIP-10 reply tags, clients show it natively
+    if let Some(parent) = resolver.resolve_tweet_event(&ref_tweet.id)? {
+        mentioned_pubkeys.push(parent.author);
+        return Ok(mentioned_pubkeys);
+    }
+
     if let Some(ref_data) = &ref_tweet.data {
         // Add the referenced tweet author to resolver
         resolver.add_known_user(&ref_data.author.username, &ref_data.author.id)?;
@@ -1000,6 +1283,13 @@ fn format_quote_tweet_with_mentions(
 ) -> Result<Vec<PublicKey>> {
     let mut mentioned_pubkeys = Vec::new();
 
+    // A mirrored quote is embedded by reference (NIP-18), next to its `q` tag
+    if let Some(quoted) = resolver.resolve_tweet_event(&ref_tweet.id)? {
+        content.push_str(&format!("nostr:{nevent}\n", nevent = quoted.to_nevent()?));
+        mentioned_pubkeys.push(quoted.author);
+        return Ok(mentioned_pubkeys);
+    }
+
     if let Some(ref_data) = &ref_tweet.data {
         // Add the quoted tweet author to resolver
         resolver.add_known_user(&ref_data.author.username, &ref_data.author.id)?;
@@ -1232,15 +1522,6 @@ fn add_media_urls(content: &mut String, media_urls: &[String], used_media_urls:
     }
 }
 
-/// Add original tweet URL to the content
-fn add_original_tweet_url(content: &mut String, tweet_id: &str) {
-    // Add link to original tweet
-    content.push_str(&format!(
-        "\nOriginal tweet: {}",
-        build_twitter_status_url(tweet_id)
-    ));
-}
-
 /// Publish a Nostr event to the specified relays
 pub async fn publish_nostr_event(client: &Client, event: &Event) -> Result<()> {
     let event_id_hex = event.id.to_hex();
@@ -1259,7 +1540,11 @@ pub async fn publish_nostr_event(client: &Client, event: &Event) -> Result<()> {
 }
 
 /// Update the user's relay list on Nostr (Kind 10002)
-pub async fn update_relay_list(client: &Client, keys: &Keys, relays: &[String]) -> Result<()> {
+pub async fn update_relay_list(
+    client: &Client,
+    signer: &UserSigner,
+    relays: &[String],
+) -> Result<()> {
     info!("Updating Nostr relay list");
 
     let relay_list: Vec<(RelayUrl, Option<RelayMetadata>)> = relays
@@ -1277,7 +1562,9 @@ pub async fn update_relay_list(client: &Client, keys: &Keys, relays: &[String])
         bail!("No valid relay URLs found to update list.");
     }
 
-    let event = EventBuilder::relay_list(relay_list).sign(keys).await?;
+    let event = signer
+        .sign_event(EventBuilder::relay_list(relay_list))
+        .await?;
 
     publish_nostr_event(client, &event).await?;
 
@@ -1289,11 +1576,11 @@ pub async fn update_relay_list(client: &Client, keys: &Keys, relays: &[String])
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::signer::Signers;
+    use crate::test_utils::test_signers;
     use crate::twitter::{Entities, ReferencedTweet, Tweet, UrlEntity, User};
     use pretty_assertions::assert_eq;
 
-    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
-
     fn create_test_tweet_with_mentions() -> Tweet {
         Tweet {
             id: "123456789".to_string(),
@@ -1317,6 +1604,7 @@ mod tests {
                     display_url: "example.com/article".to_string(),
                 }]),
                 hashtags: None,
+                cashtags: None,
                 mentions: Some(vec![
                     crate::twitter::Mention {
                         username: "alice".to_string(),
@@ -1329,6 +1617,13 @@ mod tests {
             includes: None,
             author_id: Some("987654321".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         }
     }
 
@@ -1340,6 +1635,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_proxy_tags() -> Result<()> {
+        assert_eq!(
+            tweet_proxy_tag("123456789")?.to_vec(),
+            vec!["proxy", "https://x.com/i/status/123456789", "web"]
+        );
+        assert_eq!(
+            profile_proxy_tag("testuser")?.to_vec(),
+            vec!["proxy", "https://x.com/testuser", "web"]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_decode_html_entities() {
         // Test common HTML entities
@@ -1372,7 +1680,7 @@ mod tests {
     #[test]
     fn test_process_mentions_in_text() -> Result<()> {
         let tweet = create_test_tweet_with_mentions();
-        let mut resolver = NostrLinkResolver::new(None, Some(TEST_MNEMONIC.to_string()));
+        let mut resolver = NostrLinkResolver::new(None, test_signers());
 
         // Add known users to resolver
         resolver.add_known_user("alice", "111111")?;
@@ -1395,7 +1703,7 @@ mod tests {
     #[test]
     fn test_process_mentions_unknown_users() -> Result<()> {
         let tweet = create_test_tweet_with_mentions();
-        let mut resolver = NostrLinkResolver::new(None, None);
+        let mut resolver = NostrLinkResolver::new(None, Signers::default());
 
         // Don't add users to resolver - they should remain as @mentions
         let (processed_text, mentioned_pubkeys) =
@@ -1415,7 +1723,7 @@ mod tests {
     #[test]
     fn test_format_tweet_with_mentions() -> Result<()> {
         let tweet = create_test_tweet_with_mentions();
-        let mut resolver = NostrLinkResolver::new(None, Some(TEST_MNEMONIC.to_string()));
+        let mut resolver = NostrLinkResolver::new(None, test_signers());
 
         // Add known users - need to pre-populate before passing to format function
         // because it will internally call add_known_user which uses env vars
@@ -1423,8 +1731,12 @@ mod tests {
         resolver.add_known_user("bob", "222222")?;
         resolver.add_known_user("testuser", "987654321")?; // Pre-add tweet author
 
-        let (content, mentioned_pubkeys) =
-            format_tweet_as_nostr_content_with_mentions(&tweet, &[], &mut resolver)?;
+        let (content, mentioned_pubkeys) = format_tweet_as_nostr_content_with_mentions(
+            &tweet,
+            &[],
+            &mut resolver,
+            &ContentTemplates::builtin(true)?,
+        )?;
 
         // Check content structure
         assert!(content.contains("🐦 @testuser:"));
@@ -1461,6 +1773,13 @@ mod tests {
             includes: None,
             author_id: Some("333333".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         main_tweet.referenced_tweets = Some(vec![ReferencedTweet {
@@ -1469,15 +1788,19 @@ mod tests {
             data: Some(Box::new(reply_tweet)),
         }]);
 
-        let mut resolver = NostrLinkResolver::new(None, Some(TEST_MNEMONIC.to_string()));
+        let mut resolver = NostrLinkResolver::new(None, test_signers());
 
         // Add known users including the replied-to user
         resolver.add_known_user("alice", "111111")?;
         resolver.add_known_user("replyuser", "333333")?;
         resolver.add_known_user("testuser", "987654321")?; // The main tweet author
 
-        let (content, mentioned_pubkeys) =
-            format_tweet_as_nostr_content_with_mentions(&main_tweet, &[], &mut resolver)?;
+        let (content, mentioned_pubkeys) = format_tweet_as_nostr_content_with_mentions(
+            &main_tweet,
+            &[],
+            &mut resolver,
+            &ContentTemplates::builtin(true)?,
+        )?;
 
         // Check that reply header contains nostr link
         assert!(content.contains("↩️ Reply to nostr:npub"));
@@ -1488,6 +1811,123 @@ mod tests {
         Ok(())
     }
 
+    fn template_test_tweet(referenced_tweets: serde_json::Value) -> Result<Tweet> {
+        crate::test_utils::test_tweet(
+            "100",
+            "alice",
+            serde_json::json!({
+                "text": "Look at this",
+                "referenced_tweets": referenced_tweets
+            }),
+        )
+    }
+
+    #[test]
+    fn test_default_template_keeps_layout() -> Result<()> {
+        let tweet = template_test_tweet(serde_json::json!([]))?;
+        let mut resolver = NostrLinkResolver::new(None, test_signers());
+
+        let (content, _) = format_tweet_as_nostr_content_with_mentions(
+            &tweet,
+            &["https://example.com/a.jpg".to_string()],
+            &mut resolver,
+            &ContentTemplates::builtin(true)?,
+        )?;
+        assert_eq!(
+            content,
+            "🐦 @alice: Look at this\n\nhttps://example.com/a.jpg\n\nOriginal tweet: https://twitter.com/i/status/100"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_quote_template() -> Result<()> {
+        let tweet = template_test_tweet(serde_json::json!([{
+            "type": "quoted",
+            "id": "200",
+            "data": {
+                "id": "200",
+                "text": "Quoted words",
+                "created_at": "2024-01-20T15:00:00.000Z",
+                "author": { "id": "2", "username": "bob" }
+            }
+        }]))?;
+        let templates = ContentTemplates::parse(
+            r#"{ "quote": "{text}\n\n> {quoted_text}\n{date} {tweet_url}" }"#,
+            true,
+        )?;
+        let mut resolver = NostrLinkResolver::new(None, test_signers());
+
+        let (content, mentioned_pubkeys) =
+            format_tweet_as_nostr_content_with_mentions(&tweet, &[], &mut resolver, &templates)?;
+        assert_eq!(
+            content,
+            "Look at this\n\n> Quoted words\n2024-01-20 15:30:00 UTC https://twitter.com/i/status/100"
+        );
+        // Quoted in both the references and the quoted_* variables, tagged once
+        assert_eq!(mentioned_pubkeys.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_format_links_mirrored_references() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let parent_author = nostr_sdk::Keys::generate().public_key();
+        for (tweet_id, event_id) in [("987654321", "01"), ("555444333", "02")] {
+            let info = NostrEventInfo {
+                tweet_id: tweet_id.to_string(),
+                event_id: event_id.repeat(32),
+                pubkey: parent_author.to_hex(),
+                created_at: 1672531200,
+                media_urls: Vec::new(),
+                relays: vec!["wss://relay.example.com".to_string()],
+                event_json: None,
+                media_event_ids: Vec::new(),
+                media_event_kinds: Vec::new(),
+            };
+            save_nostr_event_info(&info, temp_dir.path()).await?;
+        }
+
+        let mut tweet = create_test_tweet_with_mentions();
+        tweet.referenced_tweets = Some(vec![
+            ReferencedTweet {
+                type_field: "replied_to".to_string(),
+                id: "987654321".to_string(),
+                data: Some(Box::new(Tweet {
+                    id: "987654321".to_string(),
+                    text: "Original tweet text".to_string(),
+                    ..create_test_tweet_with_mentions()
+                })),
+            },
+            ReferencedTweet {
+                type_field: "quoted".to_string(),
+                id: "555444333".to_string(),
+                data: None,
+            },
+        ]);
+
+        let mut resolver = NostrLinkResolver::new(
+            Some(temp_dir.path().to_string_lossy().to_string()),
+            test_signers(),
+        );
+        let (content, mentioned_pubkeys) = format_tweet_as_nostr_content_with_mentions(
+            &tweet,
+            &[],
+            &mut resolver,
+            &ContentTemplates::builtin(true)?,
+        )?;
+
+        // The mirrored parent is referenced by tags, not copied
+        assert!(!content.contains("Original tweet text"));
+        assert!(!content.contains("Reply to"));
+        // The quote is embedded as a nevent
+        assert!(content.contains("nostr:nevent1"));
+        assert!(!content.contains("Quote of"));
+        assert!(mentioned_pubkeys.contains(&parent_author));
+
+        Ok(())
+    }
+
     #[test]
     fn test_expand_urls_in_text() {
         let text = "Check this out: https://t.co/abc123 and https://t.co/xyz789";
@@ -1505,6 +1945,7 @@ mod tests {
                 },
             ]),
             hashtags: None,
+            cashtags: None,
             mentions: None,
         };
 
@@ -1528,6 +1969,13 @@ mod tests {
             includes: None,
             author_id: Some("456".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let (expanded, _) = expand_urls_in_text(text, Some(&entities), &[], &test_tweet);
@@ -1561,6 +2009,13 @@ mod tests {
             includes: None,
             author_id: Some("456".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
         let (expanded, _) = expand_urls_in_text(text, None, &[], &test_tweet);
         assert_eq!(expanded, text);
@@ -1583,4 +2038,134 @@ mod tests {
             "application/octet-stream"
         );
     }
+
+    #[tokio::test]
+    async fn test_upload_media_to_blossom_skips_known_blobs() -> Result<()> {
+        let server = crate::blossom_mock::MockBlossom::start().await?;
+        let temp_dir = tempfile::tempdir()?;
+        let media_file = temp_dir.path().join("photo.jpg");
+        std::fs::write(&media_file, b"jpeg bytes")?;
+        let signer = UserSigner::Local(nostr_sdk::Keys::generate());
+        let servers = vec![server.url()];
+
+        let first =
+            upload_media_to_blossom(&[media_file.clone()], &servers, &signer, temp_dir.path())
+                .await?;
+        let second =
+            upload_media_to_blossom(&[media_file.clone()], &servers, &signer, temp_dir.path())
+                .await?;
+        assert_eq!(first, second);
+        assert_eq!(server.uploads()?, 1);
+
+        // Blobs the ledger doesn't know about are found with HEAD
+        std::fs::remove_file(crate::blob_ledger::ledger_path(temp_dir.path()))?;
+        let third =
+            upload_media_to_blossom(&[media_file], &servers, &signer, temp_dir.path()).await?;
+        assert_eq!(server.uploads()?, 1);
+        assert!(
+            third[0]
+                .url
+                .contains(&crate::blossom::sha256_hex(b"jpeg bytes"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_media_to_blossom_mirrors_to_every_server() -> Result<()> {
+        let primary = crate::blossom_mock::MockBlossom::start().await?;
+        let secondary = crate::blossom_mock::MockBlossom::start().await?;
+        let temp_dir = tempfile::tempdir()?;
+        let media_file = temp_dir.path().join("clip.mp4");
+        std::fs::write(&media_file, b"mp4 bytes")?;
+        let signer = UserSigner::Local(nostr_sdk::Keys::generate());
+        let servers = vec![primary.url(), secondary.url()];
+
+        let uploaded =
+            upload_media_to_blossom(&[media_file], &servers, &signer, temp_dir.path()).await?;
+        assert_eq!(primary.uploads()?, 1);
+        assert_eq!(secondary.uploads()?, 0);
+        assert_eq!(secondary.mirrors()?, 1);
+        assert!(uploaded[0].url.starts_with(&primary.url()));
+        assert_eq!(uploaded[0].fallbacks.len(), 1);
+        assert!(uploaded[0].fallbacks[0].starts_with(&secondary.url()));
+
+        let imeta = uploaded[0].imeta_tag()?.to_vec();
+        assert_eq!(imeta[0], "imeta");
+        assert!(imeta.contains(&"m video/mp4".to_string()));
+        assert!(imeta.contains(&format!("fallback {url}", url = uploaded[0].fallbacks[0])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_uploaded_media_from_nip94() -> Result<()> {
+        let event = Nip94Event {
+            tags: vec![
+                vec!["url".into(), "https://cdn.example.com/abc.webp".into()],
+                vec!["ox".into(), "original".into()],
+                vec!["x".into(), "abc".into()],
+                vec!["m".into(), "image/webp".into()],
+                vec!["dim".into(), "1280x720".into()],
+            ],
+            content: String::new(),
+        };
+        let media = UploadedMedia::from_nip94(&event, "original", 1000, "image/jpeg")?;
+        assert_eq!(media.url, "https://cdn.example.com/abc.webp");
+        assert_eq!(media.sha256, "abc");
+        assert_eq!(media.original_sha256.as_deref(), Some("original"));
+        assert_eq!(media.mime_type, "image/webp");
+        // The server didn't report a size, the local one is kept
+        assert_eq!(media.size, 1000);
+
+        let imeta = media.imeta_tag()?.to_vec();
+        assert!(imeta.contains(&"ox original".to_string()));
+        assert!(imeta.contains(&"dim 1280x720".to_string()));
+
+        assert!(UploadedMedia::from_nip94(&Nip94Event::default(), "x", 1, "image/png").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_uploaded_media_describe() -> Result<()> {
+        let video = TwitterMedia {
+            media_key: "7_123".to_string(),
+            type_field: "video".to_string(),
+            url: None,
+            preview_image_url: Some("https://pbs.twimg.com/thumb/123.jpg".to_string()),
+            alt_text: Some("A cat jumping ".to_string()),
+            variants: Some(vec![crate::twitter::MediaVariant {
+                bit_rate: Some(2_176_000),
+                content_type: "video/mp4".to_string(),
+                url: "https://video.twimg.com/vid/123.mp4".to_string(),
+            }]),
+        };
+        let mut media = UploadedMedia::new(
+            "https://blossom.example.com/abc.mp4".to_string(),
+            "abc".to_string(),
+            1000,
+            "video/mp4".to_string(),
+        );
+        media.dim = Some("1920x1080".to_string());
+        media.describe(
+            VisualMetadata {
+                dim: Some("1280x720".to_string()),
+                blurhash: Some("LEHV6nWB2yk8".to_string()),
+            },
+            Some(&video),
+        );
+
+        // The server's dimensions win over the local ones
+        assert_eq!(media.dim.as_deref(), Some("1920x1080"));
+        assert_eq!(media.blurhash.as_deref(), Some("LEHV6nWB2yk8"));
+        assert_eq!(
+            media.source.as_deref(),
+            Some("https://video.twimg.com/vid/123.mp4")
+        );
+
+        let imeta = media.imeta_tag()?.to_vec();
+        assert!(imeta.contains(&"x abc".to_string()));
+        assert!(imeta.contains(&"size 1000".to_string()));
+        assert!(imeta.contains(&"alt A cat jumping".to_string()));
+        assert!(imeta.contains(&"image https://pbs.twimg.com/thumb/123.jpg".to_string()));
+        Ok(())
+    }
 }
@@ -1,11 +1,56 @@
-use crate::keys::derive_key_for_twitter_user;
+use crate::nostr::{self, NostrEventInfo};
+use crate::signer::{Signers, known_public_key};
 use crate::storage::{find_latest_user_profile, load_user_from_file};
-use anyhow::{Context, Result};
-use nostr_sdk::PublicKey;
+use anyhow::{Context, Result, bail};
+use nostr_sdk::nips::nip19::Nip19Event;
+use nostr_sdk::{EventId, PublicKey, RelayUrl, ToBech32};
 use std::collections::HashMap;
 use std::path::Path;
 use tracing::debug;
 
+/// A tweet that was already mirrored as a Nostr event
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirroredEvent {
+    pub event_id: EventId,
+    pub author: PublicKey,
+    /// First relay the event was sent to, as a hint for clients
+    pub relay: Option<String>,
+}
+
+impl MirroredEvent {
+    pub fn from_info(info: &NostrEventInfo) -> Result<Self> {
+        Ok(Self {
+            event_id: EventId::from_hex(&info.event_id).with_context(|| {
+                format!(
+                    "Invalid event ID recorded for tweet {tweet_id}",
+                    tweet_id = info.tweet_id
+                )
+            })?,
+            author: PublicKey::parse(&info.pubkey).with_context(|| {
+                format!(
+                    "Invalid pubkey recorded for tweet {tweet_id}",
+                    tweet_id = info.tweet_id
+                )
+            })?,
+            relay: info.relays.first().cloned(),
+        })
+    }
+
+    /// NIP-19 `nevent` identifier with author and relay hint
+    pub fn to_nevent(&self) -> Result<String> {
+        let mut nevent = Nip19Event::new(self.event_id);
+        nevent.author = Some(self.author);
+        nevent.relays = self
+            .relay
+            .iter()
+            .filter_map(|relay| RelayUrl::parse(relay).ok())
+            .collect();
+        nevent
+            .to_bech32()
+            .map_err(|e| anyhow::anyhow!("Failed to encode nevent: {e}"))
+    }
+}
+
 /// Cache for Twitter username to Nostr pubkey mappings
 /// This helps avoid repeated lookups and key derivations
 pub struct NostrLinkResolver {
@@ -13,20 +58,23 @@ pub struct NostrLinkResolver {
     username_to_pubkey: HashMap<String, PublicKey>,
     /// Maps Twitter user ID to Nostr public key
     user_id_to_pubkey: HashMap<String, PublicKey>,
+    /// Maps tweet ID to the event it was mirrored as, if any
+    tweet_events: HashMap<String, Option<MirroredEvent>>,
     /// Data directory to search for user profiles
     data_dir: Option<String>,
-    /// Mnemonic for deriving keys
-    mnemonic: Option<String>,
+    /// Where mentioned users' keys come from
+    signers: Signers,
 }
 
 impl NostrLinkResolver {
-    /// Create a new resolver with optional data directory and mnemonic
-    pub fn new(data_dir: Option<String>, mnemonic: Option<String>) -> Self {
+    /// Create a new resolver with optional data directory
+    pub fn new(data_dir: Option<String>, signers: Signers) -> Self {
         Self {
             username_to_pubkey: HashMap::new(),
             user_id_to_pubkey: HashMap::new(),
+            tweet_events: HashMap::new(),
             data_dir,
-            mnemonic,
+            signers,
         }
     }
 
@@ -39,6 +87,13 @@ impl NostrLinkResolver {
             return Ok(Some(*pubkey));
         }
 
+        // Accounts with their own Nostr identity resolve to it, even without a cached profile
+        if let Some(pubkey) = self.signers.override_public_key_for_username(username)? {
+            debug!("Resolved @{username} through its key override");
+            self.username_to_pubkey.insert(username.to_string(), pubkey);
+            return Ok(Some(pubkey));
+        }
+
         // Try to find the user profile in cache
         if let Some(data_dir) = &self.data_dir {
             let data_path = Path::new(data_dir);
@@ -51,11 +106,14 @@ impl NostrLinkResolver {
                 // Load the user profile to get the user ID
                 if let Ok(user) = load_user_from_file(&profile_path) {
                     // Derive the Nostr key from the Twitter user ID
-                    let keys = derive_key_for_twitter_user(&user.id, self.mnemonic.as_deref())
+                    let Some(pubkey) = known_public_key(&user.id, &user.username, &self.signers)
                         .with_context(|| {
                             format!("Failed to derive key for Twitter user {username}")
-                        })?;
-                    let pubkey = keys.public_key();
+                        })?
+                    else {
+                        debug!("Pubkey for @{username} is not known to the remote signer yet");
+                        return Ok(None);
+                    };
 
                     // Cache both username and user ID mappings
                     self.username_to_pubkey.insert(username.to_string(), pubkey);
@@ -79,10 +137,12 @@ impl NostrLinkResolver {
             return Ok(*pubkey);
         }
 
-        // Derive the key
-        let keys = derive_key_for_twitter_user(user_id, self.mnemonic.as_deref())
-            .with_context(|| format!("Failed to derive key for Twitter user ID {user_id}"))?;
-        let pubkey = keys.public_key();
+        // Derive the key, without a username overrides only match by ID
+        let Some(pubkey) = known_public_key(user_id, "", &self.signers)
+            .with_context(|| format!("Failed to derive key for Twitter user ID {user_id}"))?
+        else {
+            bail!("Pubkey for Twitter user ID {user_id} is not known to the remote signer");
+        };
 
         // Cache the mapping
         self.user_id_to_pubkey.insert(user_id.to_string(), pubkey);
@@ -90,6 +150,24 @@ impl NostrLinkResolver {
         Ok(pubkey)
     }
 
+    /// Find the event a tweet was mirrored as, from the `nostr/<tweet_id>.json` records
+    pub fn resolve_tweet_event(&mut self, tweet_id: &str) -> Result<Option<MirroredEvent>> {
+        if let Some(event) = self.tweet_events.get(tweet_id) {
+            return Ok(event.clone());
+        }
+
+        let Some(data_dir) = &self.data_dir else {
+            return Ok(None);
+        };
+        let event = nostr::load_nostr_event_info(Path::new(data_dir), tweet_id)?
+            .map(|info| MirroredEvent::from_info(&info))
+            .transpose()?;
+
+        self.tweet_events
+            .insert(tweet_id.to_string(), event.clone());
+        Ok(event)
+    }
+
     /// Add a known mapping between Twitter username and user ID
     /// This is useful when processing tweets where we know the author
     pub fn add_known_user(&mut self, username: &str, user_id: &str) -> Result<()> {
@@ -103,9 +181,12 @@ impl NostrLinkResolver {
             return Ok(());
         }
 
-        let keys = derive_key_for_twitter_user(user_id, self.mnemonic.as_deref())
-            .with_context(|| format!("Failed to derive key for Twitter user {username}"))?;
-        let pubkey = keys.public_key();
+        let Some(pubkey) = known_public_key(user_id, username, &self.signers)
+            .with_context(|| format!("Failed to derive key for Twitter user {username}"))?
+        else {
+            debug!("Pubkey for @{username} is not known to the remote signer yet");
+            return Ok(());
+        };
 
         self.username_to_pubkey.insert(username.to_string(), pubkey);
         self.user_id_to_pubkey.insert(user_id.to_string(), pubkey);
@@ -117,15 +198,14 @@ impl NostrLinkResolver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::test_signers;
     use crate::twitter::User;
     use std::fs;
     use tempfile::TempDir;
 
-    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
-
     #[test]
     fn test_nostr_link_resolver() -> Result<()> {
-        let mut resolver = NostrLinkResolver::new(None, Some(TEST_MNEMONIC.to_string()));
+        let mut resolver = NostrLinkResolver::new(None, test_signers());
 
         // Test user ID resolution
         let pubkey1 = resolver.resolve_user_id("12345")?;
@@ -161,7 +241,7 @@ mod tests {
         fs::write(&file_path, json)?;
 
         // Create resolver with data directory
-        let mut resolver = NostrLinkResolver::new(Some(data_dir), Some(TEST_MNEMONIC.to_string()));
+        let mut resolver = NostrLinkResolver::new(Some(data_dir), test_signers());
 
         // Resolve username should find cached profile
         let pubkey = resolver.resolve_username("cacheduser")?;
@@ -176,8 +256,8 @@ mod tests {
 
     #[test]
     fn test_deterministic_key_derivation() -> Result<()> {
-        let mut resolver1 = NostrLinkResolver::new(None, Some(TEST_MNEMONIC.to_string()));
-        let mut resolver2 = NostrLinkResolver::new(None, Some(TEST_MNEMONIC.to_string()));
+        let mut resolver1 = NostrLinkResolver::new(None, test_signers());
+        let mut resolver2 = NostrLinkResolver::new(None, test_signers());
 
         // Same user ID should produce same pubkey across different resolvers
         let pubkey1 = resolver1.resolve_user_id("555555")?;
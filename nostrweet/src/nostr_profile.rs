@@ -1,11 +1,13 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use nostr_sdk::{Filter, Kind, Metadata, prelude::*};
 use std::collections::HashSet;
 use std::path::Path;
 use std::time::Duration;
 use tracing::{debug, info};
 
-use crate::{keys, nostr, storage, twitter};
+use crate::mirror_options::MirrorOptions;
+use crate::signer::{self, Signers};
+use crate::{nip05, nostr, storage, twitter};
 
 /// Generate the profile disclaimer text for a given username
 fn get_profile_disclaimer(username: &str) -> String {
@@ -15,7 +17,11 @@ fn get_profile_disclaimer(username: &str) -> String {
 }
 
 /// Build Nostr metadata from a Twitter user
-pub fn build_nostr_metadata_from_user(user: &twitter::User, username: &str) -> Metadata {
+pub fn build_nostr_metadata_from_user(
+    user: &twitter::User,
+    username: &str,
+    nip05_domain: Option<&str>,
+) -> Metadata {
     let mut metadata = Metadata::new();
 
     if let Some(name) = &user.name {
@@ -41,7 +47,7 @@ pub fn build_nostr_metadata_from_user(user: &twitter::User, username: &str) -> M
         metadata = metadata.website(url);
     }
 
-    metadata
+    nip05::with_nip05(metadata, username, nip05_domain)
 }
 
 /// Posts a Twitter user profile to Nostr as metadata
@@ -49,7 +55,8 @@ async fn post_single_profile(
     username: &str,
     client: &nostr_sdk::Client,
     data_dir: &Path,
-    mnemonic: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
 ) -> Result<EventId> {
     debug!("Attempting to post profile for @{username} to Nostr");
 
@@ -66,15 +73,21 @@ async fn post_single_profile(
     let user = storage::load_user_from_file(&profile_path)
         .with_context(|| format!("Failed to load profile for @{username}"))?;
 
-    // Get Nostr keys for this user
-    let user_keys = keys::get_keys_for_tweet(&user.id, mnemonic)?;
+    // Get the Nostr signer for this user
+    let user_signer = signer::signer_for_user(&user, signers).await?;
+    if !user_signer.can_sign() {
+        bail!("@{username} has a pubkey-only key override, not posting their profile");
+    }
 
     // Create metadata using the shared function
-    let metadata = build_nostr_metadata_from_user(&user, username);
+    let metadata =
+        build_nostr_metadata_from_user(&user, username, mirror_options.nip05_domain.as_deref());
 
     // Build the event
-    let event = EventBuilder::metadata(&metadata)
-        .sign(&user_keys)
+    let event = user_signer
+        .sign_event(
+            EventBuilder::metadata(&metadata).tag(nostr::profile_proxy_tag(&user.username)?),
+        )
         .await
         .context("Failed to build metadata event")?;
 
@@ -101,7 +114,7 @@ async fn post_relay_list_for_user(
     username: &str,
     client: &nostr_sdk::Client,
     data_dir: &Path,
-    mnemonic: Option<&str>,
+    signers: &Signers,
     relays: &[String],
 ) -> Result<EventId> {
     debug!("Posting relay list for @{username}");
@@ -111,10 +124,13 @@ async fn post_relay_list_for_user(
         .ok_or_else(|| anyhow::anyhow!("No profile found for user '{username}'"))?;
 
     let user = storage::load_user_from_file(&profile_path)?;
-    let user_keys = keys::get_keys_for_tweet(&user.id, mnemonic)?;
+    let user_signer = signer::signer_for_user(&user, signers).await?;
+    if !user_signer.can_sign() {
+        bail!("@{username} has a pubkey-only key override, not posting their relay list");
+    }
 
     // Use the existing update_relay_list function from the nostr module
-    nostr::update_relay_list(client, &user_keys, relays)
+    nostr::update_relay_list(client, &user_signer, relays)
         .await
         .with_context(|| format!("Failed to update relay list for @{username}"))?;
 
@@ -131,7 +147,8 @@ pub async fn post_referenced_profiles(
     usernames: &HashSet<String>,
     client: &nostr_sdk::Client,
     data_dir: &Path,
-    mnemonic: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
 ) -> Result<usize> {
     if usernames.is_empty() {
         return Ok(0);
@@ -146,7 +163,7 @@ pub async fn post_referenced_profiles(
     let mut failed_count = 0;
 
     for username in usernames {
-        match post_single_profile(username, client, data_dir, mnemonic).await {
+        match post_single_profile(username, client, data_dir, signers, mirror_options).await {
             Ok(event_id) => {
                 debug!("Posted profile for @{username} with event ID: {event_id:?}");
                 posted_count += 1;
@@ -176,7 +193,7 @@ pub async fn check_profile_exists(
     username: &str,
     client: &nostr_sdk::Client,
     data_dir: &Path,
-    mnemonic: Option<&str>,
+    signers: &Signers,
 ) -> Result<bool> {
     debug!("Checking if profile exists for @{username}");
 
@@ -191,8 +208,7 @@ pub async fn check_profile_exists(
 
     // Load the user profile to get the user ID
     let user = storage::load_user_from_file(&profile_path)?;
-    let user_keys = keys::get_keys_for_tweet(&user.id, mnemonic)?;
-    let pubkey = user_keys.public_key();
+    let pubkey = signer::signer_for_user(&user, signers).await?.public_key();
 
     // Query for metadata events (Kind 0) from this pubkey
     let filter = Filter::new().author(pubkey).kind(Kind::Metadata).limit(1);
@@ -217,7 +233,7 @@ pub async fn filter_profiles_to_post(
     client: &nostr_sdk::Client,
     data_dir: &Path,
     force: bool,
-    mnemonic: Option<&str>,
+    signers: &Signers,
 ) -> Result<HashSet<String>> {
     if force {
         // If force flag is set, post all profiles
@@ -238,8 +254,12 @@ pub async fn filter_profiles_to_post(
             .ok_or_else(|| anyhow::anyhow!("Profile path disappeared for {username}"))?;
 
         let user = storage::load_user_from_file(&profile_path)?;
-        let user_keys = keys::get_keys_for_tweet(&user.id, mnemonic)?;
-        let pubkey = user_keys.public_key();
+        let user_signer = signer::signer_for_user(&user, signers).await?;
+        if !user_signer.can_sign() {
+            debug!("@{username} has a pubkey-only key override, skipping");
+            continue;
+        }
+        let pubkey = user_signer.public_key();
 
         // Check if we've already posted a profile for this user
         // Query for metadata events (Kind 0) from this pubkey
@@ -267,17 +287,19 @@ pub async fn post_user_profile_with_relay_list(
     username: &str,
     client: &nostr_sdk::Client,
     data_dir: &Path,
-    mnemonic: Option<&str>,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
     relays: &[String],
 ) -> Result<()> {
     info!("Posting profile and relay list for @{username}");
 
     // First post the profile
-    let profile_event_id = post_single_profile(username, client, data_dir, mnemonic).await?;
+    let profile_event_id =
+        post_single_profile(username, client, data_dir, signers, mirror_options).await?;
     info!("Posted profile for @{username} with event ID: {profile_event_id:?}");
 
     // Then post the relay list
-    post_relay_list_for_user(username, client, data_dir, mnemonic, relays).await?;
+    post_relay_list_for_user(username, client, data_dir, signers, relays).await?;
     info!("Posted relay list for @{username}");
 
     Ok(())
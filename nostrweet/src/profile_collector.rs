@@ -123,6 +123,13 @@ mod tests {
             includes: None,
             author_id: Some("alice_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let usernames = collect_usernames_from_tweet(&tweet);
@@ -152,10 +159,18 @@ mod tests {
                     },
                 ]),
                 hashtags: None,
+                cashtags: None,
             }),
             includes: None,
             author_id: Some("alice_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let usernames = collect_usernames_from_tweet(&tweet);
@@ -182,10 +197,18 @@ mod tests {
                     username: "david".to_string(),
                 }]),
                 hashtags: None,
+                cashtags: None,
             }),
             includes: None,
             author_id: Some("eve_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let main_tweet = Tweet {
@@ -203,6 +226,13 @@ mod tests {
             includes: None,
             author_id: Some("alice_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let usernames = collect_usernames_from_tweet(&main_tweet);
@@ -227,6 +257,13 @@ mod tests {
             includes: None,
             author_id: Some("alice_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let tweet2 = Tweet {
@@ -242,10 +279,18 @@ mod tests {
                     username: "bob".to_string(),
                 }]),
                 hashtags: None,
+                cashtags: None,
             }),
             includes: None,
             author_id: Some("charlie_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let tweets = vec![tweet1, tweet2];
@@ -280,10 +325,18 @@ mod tests {
                     },
                 ]),
                 hashtags: None,
+                cashtags: None,
             }),
             includes: None,
             author_id: Some("alice_id".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let usernames = collect_usernames_from_tweet(&tweet);
@@ -0,0 +1,724 @@
+use anyhow::{Context, Result, bail, ensure};
+use nostr_sdk::nips::nip44::{self, Version};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+use crate::keys::{self, KeySource};
+
+/// How long to wait for the remote signer to answer a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Event kinds nostrweet publishes, the only ones the bunker signs
+///
+/// Metadata, notes, deletions, reposts, generic reposts, pictures, videos, short
+/// videos, Blossom and NIP-98 upload authorizations.
+const SIGNABLE_KINDS: [u16; 10] = [0, 1, 5, 6, 16, 20, 21, 22, 24242, 27235];
+
+/// NIP-46 JSON-RPC request, sent NIP-44 encrypted inside a kind 24133 event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nip46Request {
+    pub id: String,
+    pub method: String,
+    pub params: Vec<String>,
+}
+
+/// NIP-46 JSON-RPC response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nip46Response {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Nip46Response {
+    fn ok(id: &str, result: String) -> Self {
+        Self {
+            id: id.to_string(),
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn err(id: &str, error: String) -> Self {
+        Self {
+            id: id.to_string(),
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Parsed `bunker://<remote-pubkey>?relay=<url>&secret=<secret>` connection string
+#[derive(Debug, Clone, PartialEq)]
+pub struct BunkerUri {
+    pub remote_public_key: PublicKey,
+    pub relays: Vec<String>,
+    pub secret: Option<String>,
+}
+
+impl BunkerUri {
+    pub fn parse(uri: &str) -> Result<Self> {
+        let url = url::Url::parse(uri).with_context(|| format!("Invalid bunker URI: {uri}"))?;
+        ensure!(
+            url.scheme() == "bunker",
+            "Bunker URI must start with bunker://, got {uri}"
+        );
+
+        let host = url
+            .host_str()
+            .with_context(|| format!("Bunker URI is missing the remote signer pubkey: {uri}"))?;
+        let remote_public_key = PublicKey::parse(host)
+            .with_context(|| format!("Invalid remote signer pubkey in bunker URI: {host}"))?;
+
+        let mut relays = Vec::new();
+        let mut secret = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "relay" => relays.push(value.to_string()),
+                "secret" => secret = Some(value.to_string()),
+                _ => debug!("Ignoring unknown bunker URI parameter: {key}"),
+            }
+        }
+        ensure!(!relays.is_empty(), "Bunker URI has no relay parameter");
+
+        Ok(Self {
+            remote_public_key,
+            relays,
+            secret,
+        })
+    }
+}
+
+impl std::fmt::Display for BunkerUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for relay in &self.relays {
+            query.append_pair("relay", relay);
+        }
+        if let Some(secret) = &self.secret {
+            query.append_pair("secret", secret);
+        }
+        write!(
+            f,
+            "bunker://{pubkey}?{query}",
+            pubkey = self.remote_public_key.to_hex(),
+            query = query.finish()
+        )
+    }
+}
+
+/// Encrypts a NIP-46 payload and wraps it in a signed kind 24133 event
+fn build_nip46_event<T: Serialize>(
+    sender: &Keys,
+    receiver: &PublicKey,
+    payload: &T,
+) -> Result<Event> {
+    let json = serde_json::to_string(payload).context("Failed to serialize NIP-46 message")?;
+    let content = nip44::encrypt(sender.secret_key(), receiver, json, Version::V2)
+        .context("Failed to encrypt NIP-46 message")?;
+    EventBuilder::new(Kind::NostrConnect, content)
+        .tag(Tag::public_key(*receiver))
+        .sign_with_keys(sender)
+        .context("Failed to sign NIP-46 event")
+}
+
+/// Decrypts the content of a kind 24133 event addressed to `receiver`
+fn decrypt_nip46_event<T: for<'de> Deserialize<'de>>(receiver: &Keys, event: &Event) -> Result<T> {
+    let json = nip44::decrypt(receiver.secret_key(), &event.pubkey, &event.content)
+        .context("Failed to decrypt NIP-46 message")?;
+    serde_json::from_str(&json).context("Failed to parse NIP-46 message")
+}
+
+/// Client side of a NIP-46 remote signer
+///
+/// A mirror bunker derives keys per Twitter user: besides the standard methods,
+/// `get_public_key` and `sign_event` take the Twitter user ID as an extra
+/// trailing parameter so the bunker can pick the derived key to use. An account
+/// signer holds a single user's own key and gets standard requests.
+pub struct RemoteSigner {
+    client: Client,
+    app_keys: Keys,
+    remote_public_key: PublicKey,
+    /// Whether requests carry the Twitter user ID for per-user derivation
+    per_user: bool,
+    /// Twitter user ID -> pubkey answers, persisted so mentions resolve offline
+    public_keys: Mutex<BTreeMap<String, PublicKey>>,
+    cache_path: PathBuf,
+}
+
+impl RemoteSigner {
+    /// Connects to a mirror bunker that derives a key per Twitter user
+    pub async fn connect(uri: &BunkerUri, data_dir: &Path) -> Result<Self> {
+        Self::connect_with_mode(uri, data_dir, true).await
+    }
+
+    /// Connects to a standard NIP-46 signer holding one Twitter user's own key
+    pub async fn connect_account(uri: &BunkerUri, data_dir: &Path) -> Result<Self> {
+        Self::connect_with_mode(uri, data_dir, false).await
+    }
+
+    /// Connects to the bunker relays and performs the NIP-46 `connect` handshake
+    async fn connect_with_mode(uri: &BunkerUri, data_dir: &Path, per_user: bool) -> Result<Self> {
+        let app_keys = Keys::generate();
+        let client = Client::new(app_keys.clone());
+        for relay in &uri.relays {
+            client
+                .add_relay(relay)
+                .await
+                .with_context(|| format!("Failed to add bunker relay: {relay}"))?;
+        }
+        client.connect().await;
+
+        let filter = Filter::new()
+            .kind(Kind::NostrConnect)
+            .author(uri.remote_public_key)
+            .pubkey(app_keys.public_key())
+            .since(Timestamp::now());
+        client
+            .subscribe(filter, None)
+            .await
+            .context("Failed to subscribe to remote signer responses")?;
+
+        let cache_path = public_key_cache_path(data_dir, &uri.remote_public_key);
+        let public_keys = load_public_key_cache(&cache_path);
+
+        let signer = Self {
+            client,
+            app_keys,
+            remote_public_key: uri.remote_public_key,
+            per_user,
+            public_keys: Mutex::new(public_keys),
+            cache_path,
+        };
+
+        let result = signer
+            .request(
+                "connect",
+                vec![
+                    uri.remote_public_key.to_hex(),
+                    uri.secret.clone().unwrap_or_default(),
+                ],
+            )
+            .await
+            .context("Remote signer rejected the connection")?;
+        ensure!(
+            result == "ack" || uri.secret.as_deref() == Some(result.as_str()),
+            "Unexpected connect response from remote signer: {result}"
+        );
+
+        debug!(
+            "Connected to remote signer {pubkey}",
+            pubkey = uri.remote_public_key
+        );
+
+        Ok(signer)
+    }
+
+    /// Sends a request and waits for the matching response
+    async fn request(&self, method: &str, params: Vec<String>) -> Result<String> {
+        let request = Nip46Request {
+            id: uuid::Uuid::new_v4().to_string(),
+            method: method.to_string(),
+            params,
+        };
+        let event = build_nip46_event(&self.app_keys, &self.remote_public_key, &request)?;
+
+        // Subscribe to notifications before sending so the response can't be missed
+        let mut notifications = self.client.notifications();
+        self.client
+            .send_event(&event)
+            .await
+            .with_context(|| format!("Failed to send {method} request to remote signer"))?;
+
+        let response = timeout(REQUEST_TIMEOUT, async {
+            loop {
+                let notification = match notifications.recv().await {
+                    Ok(notification) => notification,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Missed {skipped} relay notifications while waiting for {method}");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                let RelayPoolNotification::Event { event, .. } = notification else {
+                    continue;
+                };
+                if event.kind != Kind::NostrConnect || event.pubkey != self.remote_public_key {
+                    continue;
+                }
+                match decrypt_nip46_event::<Nip46Response>(&self.app_keys, &event) {
+                    Ok(response) if response.id == request.id => return Some(response),
+                    Ok(_) => continue,
+                    Err(e) => {
+                        warn!("Ignoring undecryptable remote signer message: {e}");
+                        continue;
+                    }
+                }
+            }
+        })
+        .await
+        .with_context(|| format!("Timed out waiting for remote signer to answer {method}"))?
+        .with_context(|| format!("Remote signer connection closed during {method}"))?;
+
+        if let Some(error) = response.error {
+            bail!("Remote signer returned an error for {method}: {error}");
+        }
+        response
+            .result
+            .with_context(|| format!("Remote signer returned no result for {method}"))
+    }
+
+    /// Appends the Twitter user ID for per-user bunkers
+    fn params(&self, twitter_user_id: Option<&str>, mut params: Vec<String>) -> Vec<String> {
+        if self.per_user
+            && let Some(twitter_user_id) = twitter_user_id
+        {
+            params.push(twitter_user_id.to_string());
+        }
+        params
+    }
+
+    /// The bunker's own pubkey, which is its root identity
+    pub fn remote_public_key(&self) -> PublicKey {
+        self.remote_public_key
+    }
+
+    /// Returns the pubkey the bunker derives for a Twitter user
+    pub async fn get_public_key(&self, twitter_user_id: &str) -> Result<PublicKey> {
+        if let Some(public_key) = self.cached_public_key(twitter_user_id) {
+            return Ok(public_key);
+        }
+
+        let result = self
+            .request(
+                "get_public_key",
+                self.params(Some(twitter_user_id), Vec::new()),
+            )
+            .await?;
+        let public_key = PublicKey::parse(&result)
+            .with_context(|| format!("Remote signer returned an invalid pubkey: {result}"))?;
+
+        self.remember_public_key(twitter_user_id, public_key);
+        Ok(public_key)
+    }
+
+    /// Returns a previously fetched pubkey without contacting the bunker
+    pub fn cached_public_key(&self, twitter_user_id: &str) -> Option<PublicKey> {
+        match self.public_keys.lock() {
+            Ok(public_keys) => public_keys.get(twitter_user_id).copied(),
+            Err(e) => {
+                warn!("Remote signer pubkey cache is poisoned: {e}");
+                None
+            }
+        }
+    }
+
+    fn remember_public_key(&self, twitter_user_id: &str, public_key: PublicKey) {
+        let snapshot = match self.public_keys.lock() {
+            Ok(mut public_keys) => {
+                public_keys.insert(twitter_user_id.to_string(), public_key);
+                public_keys.clone()
+            }
+            Err(e) => {
+                warn!("Remote signer pubkey cache is poisoned: {e}");
+                return;
+            }
+        };
+        if let Err(e) = save_public_key_cache(&self.cache_path, &snapshot) {
+            warn!("Failed to persist remote signer pubkey cache: {e}");
+        }
+    }
+
+    /// Asks the bunker to sign an event with the key derived for a Twitter user,
+    /// or with its own key without one
+    pub async fn sign_event(
+        &self,
+        twitter_user_id: Option<&str>,
+        unsigned: UnsignedEvent,
+    ) -> Result<Event> {
+        let expected_pubkey = unsigned.pubkey;
+        let result = self
+            .request(
+                "sign_event",
+                self.params(twitter_user_id, vec![unsigned.as_json()]),
+            )
+            .await?;
+
+        let event = Event::from_json(&result).context("Remote signer returned an invalid event")?;
+        event
+            .verify()
+            .context("Remote signer returned an event with an invalid signature")?;
+        ensure!(
+            event.pubkey == expected_pubkey,
+            "Remote signer signed with {actual} instead of {expected_pubkey}",
+            actual = event.pubkey
+        );
+
+        Ok(event)
+    }
+}
+
+fn public_key_cache_path(data_dir: &Path, remote_public_key: &PublicKey) -> PathBuf {
+    data_dir.join("remote_signer").join(format!(
+        "{pubkey}.json",
+        pubkey = remote_public_key.to_hex()
+    ))
+}
+
+/// Looks up a pubkey a remote signer returned earlier, without connecting to it
+pub fn cached_public_key_in(
+    data_dir: &Path,
+    remote_public_key: &PublicKey,
+    twitter_user_id: &str,
+) -> Option<PublicKey> {
+    load_public_key_cache(&public_key_cache_path(data_dir, remote_public_key))
+        .get(twitter_user_id)
+        .copied()
+}
+
+fn load_public_key_cache(path: &Path) -> BTreeMap<String, PublicKey> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return BTreeMap::new();
+    };
+    match serde_json::from_str::<BTreeMap<String, String>>(&content) {
+        Ok(entries) => entries
+            .into_iter()
+            .filter_map(|(user_id, hex)| match PublicKey::from_hex(&hex) {
+                Ok(public_key) => Some((user_id, public_key)),
+                Err(e) => {
+                    warn!("Ignoring invalid cached pubkey for Twitter user {user_id}: {e}");
+                    None
+                }
+            })
+            .collect(),
+        Err(e) => {
+            warn!(
+                "Ignoring unreadable remote signer cache {path}: {e}",
+                path = path.display()
+            );
+            BTreeMap::new()
+        }
+    }
+}
+
+fn save_public_key_cache(path: &Path, public_keys: &BTreeMap<String, PublicKey>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create remote signer directory")?;
+    }
+    let entries: BTreeMap<&String, String> = public_keys
+        .iter()
+        .map(|(user_id, public_key)| (user_id, public_key.to_hex()))
+        .collect();
+    let json = serde_json::to_string_pretty(&entries)
+        .context("Failed to serialize remote signer pubkey cache")?;
+    std::fs::write(path, json).with_context(|| {
+        format!(
+            "Failed to write remote signer cache to {path}",
+            path = path.display()
+        )
+    })
+}
+
+/// Bunker side of the protocol: holds the mnemonic and answers signing requests
+pub struct Bunker {
+    keys: Keys,
+    source: KeySource,
+    secret: String,
+    authorized_clients: HashSet<PublicKey>,
+}
+
+impl Bunker {
+    /// Creates a bunker whose own identity is the root key of the mnemonic
+    /// ([`keys::ROOT_DERIVATION_PATH`])
+    ///
+    /// Clients must present the connection secret, a random one is generated when
+    /// none is given. Anyone knowing it can sign as every mirrored user.
+    pub fn new(source: KeySource, secret: Option<String>) -> Result<Self> {
+        let secret = secret.unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+        ensure!(
+            !secret.is_empty(),
+            "Bunker connection secret must not be empty"
+        );
+        Ok(Self {
+            keys: source.root_keys()?,
+            source,
+            secret,
+            authorized_clients: HashSet::new(),
+        })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.keys.public_key()
+    }
+
+    /// Keys the bunker uses on the relay connection
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    pub fn bunker_uri(&self, relays: &[String]) -> BunkerUri {
+        BunkerUri {
+            remote_public_key: self.public_key(),
+            relays: relays.to_vec(),
+            secret: Some(self.secret.clone()),
+        }
+    }
+
+    /// Decrypts a request event and returns the encrypted response event, if any
+    pub fn handle_event(&mut self, event: &Event) -> Result<Option<Event>> {
+        if event.kind != Kind::NostrConnect {
+            return Ok(None);
+        }
+        let request: Nip46Request = decrypt_nip46_event(&self.keys, event)?;
+        debug!(
+            "Bunker request {method} from {client}",
+            method = request.method,
+            client = event.pubkey
+        );
+        let response = self.handle_request(&event.pubkey, &request);
+        build_nip46_event(&self.keys, &event.pubkey, &response).map(Some)
+    }
+
+    fn handle_request(&mut self, client: &PublicKey, request: &Nip46Request) -> Nip46Response {
+        let id = request.id.as_str();
+
+        if request.method == "connect" {
+            let provided = request.params.get(1).map(String::as_str).unwrap_or("");
+            if provided != self.secret {
+                return Nip46Response::err(id, "Invalid connection secret".to_string());
+            }
+            self.authorized_clients.insert(*client);
+            return Nip46Response::ok(id, "ack".to_string());
+        }
+
+        if request.method == "ping" {
+            return Nip46Response::ok(id, "pong".to_string());
+        }
+
+        if !self.authorized_clients.contains(client) {
+            return Nip46Response::err(id, "Client is not connected".to_string());
+        }
+
+        let result = match request.method.as_str() {
+            "get_public_key" => self.get_public_key(&request.params),
+            "sign_event" => self.sign_event(&request.params),
+            method => Err(anyhow::anyhow!("Unsupported method: {method}")),
+        };
+
+        match result {
+            Ok(result) => Nip46Response::ok(id, result),
+            Err(e) => Nip46Response::err(id, e.to_string()),
+        }
+    }
+
+    /// Keys for the Twitter user in `param`, or the bunker's own keys without one
+    fn keys_for(&self, twitter_user_id: Option<&String>) -> Result<Keys> {
+        match twitter_user_id {
+            Some(user_id) => keys::get_keys_for_tweet(user_id, &self.source),
+            None => Ok(self.keys.clone()),
+        }
+    }
+
+    fn get_public_key(&self, params: &[String]) -> Result<String> {
+        Ok(self.keys_for(params.first())?.public_key().to_hex())
+    }
+
+    fn sign_event(&self, params: &[String]) -> Result<String> {
+        let unsigned_json = params
+            .first()
+            .context("sign_event requires an event parameter")?;
+        let user_keys = self.keys_for(params.get(1))?;
+
+        let unsigned = UnsignedEvent::from_json(unsigned_json).context("Invalid unsigned event")?;
+        ensure!(
+            SIGNABLE_KINDS.contains(&unsigned.kind.as_u16()),
+            "Refusing to sign kind {kind} events",
+            kind = unsigned.kind.as_u16()
+        );
+        ensure!(
+            unsigned.pubkey == user_keys.public_key(),
+            "Event pubkey {pubkey} does not match the requested Twitter user",
+            pubkey = unsigned.pubkey
+        );
+
+        let event = unsigned
+            .sign_with_keys(&user_keys)
+            .context("Failed to sign event")?;
+        Ok(event.as_json())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn test_key_source() -> KeySource {
+        KeySource::new(Some(TEST_MNEMONIC.to_string()), Default::default())
+    }
+
+    fn send(
+        bunker: &mut Bunker,
+        app_keys: &Keys,
+        method: &str,
+        params: Vec<String>,
+    ) -> Result<Nip46Response> {
+        let request = Nip46Request {
+            id: method.to_string(),
+            method: method.to_string(),
+            params,
+        };
+        let event = build_nip46_event(app_keys, &bunker.public_key(), &request)?;
+        let response_event = bunker
+            .handle_event(&event)?
+            .context("Bunker did not answer")?;
+        assert_eq!(response_event.pubkey, bunker.public_key());
+        decrypt_nip46_event(app_keys, &response_event)
+    }
+
+    #[test]
+    fn test_bunker_uri_roundtrip() -> Result<()> {
+        let keys = Keys::generate();
+        let uri = BunkerUri {
+            remote_public_key: keys.public_key(),
+            relays: vec!["wss://relay.example.com".to_string()],
+            secret: Some("s3cret".to_string()),
+        };
+
+        let parsed = BunkerUri::parse(&uri.to_string())?;
+        assert_eq!(parsed, uri);
+
+        assert!(BunkerUri::parse("nostrconnect://abc?relay=wss://x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bunker_requires_connect_secret() -> Result<()> {
+        let mut bunker = Bunker::new(test_key_source(), Some("s3cret".to_string()))?;
+        let app_keys = Keys::generate();
+
+        // Requests before connecting are refused
+        let response = send(&mut bunker, &app_keys, "get_public_key", vec![])?;
+        assert!(response.error.is_some());
+
+        // A wrong secret is refused
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "connect",
+            vec![bunker.public_key().to_hex(), "wrong".to_string()],
+        )?;
+        assert!(response.error.is_some());
+
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "connect",
+            vec![bunker.public_key().to_hex(), "s3cret".to_string()],
+        )?;
+        assert_eq!(response.result.as_deref(), Some("ack"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bunker_signs_with_derived_keys() -> Result<()> {
+        let mut bunker = Bunker::new(test_key_source(), Some("s3cret".to_string()))?;
+        let app_keys = Keys::generate();
+        send(
+            &mut bunker,
+            &app_keys,
+            "connect",
+            vec![bunker.public_key().to_hex(), "s3cret".to_string()],
+        )?;
+
+        // Without a Twitter user the bunker answers with its root identity
+        let response = send(&mut bunker, &app_keys, "get_public_key", vec![])?;
+        assert_eq!(
+            response.result,
+            Some(test_key_source().root_keys()?.public_key().to_hex())
+        );
+
+        let expected = test_key_source().derive_keys("123456")?;
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "get_public_key",
+            vec!["123456".to_string()],
+        )?;
+        assert_eq!(response.result, Some(expected.public_key().to_hex()));
+
+        let unsigned = EventBuilder::new(Kind::TextNote, "hello").build(expected.public_key());
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "sign_event",
+            vec![unsigned.as_json(), "123456".to_string()],
+        )?;
+        let event = Event::from_json(response.result.context("No signed event")?)?;
+        event.verify()?;
+        assert_eq!(event.pubkey, expected.public_key());
+        assert_eq!(event.content, "hello");
+
+        // Signing for a different user than the event pubkey is refused
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "sign_event",
+            vec![unsigned.as_json(), "789012".to_string()],
+        )?;
+        assert!(response.error.is_some());
+
+        // Kinds nostrweet never publishes are refused
+        let direct_message =
+            EventBuilder::new(Kind::EncryptedDirectMessage, "hi").build(expected.public_key());
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "sign_event",
+            vec![direct_message.as_json(), "123456".to_string()],
+        )?;
+        assert!(response.error.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bunker_generates_secret() -> Result<()> {
+        let mut bunker = Bunker::new(test_key_source(), None)?;
+        let app_keys = Keys::generate();
+
+        // Connecting without a secret is refused
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "connect",
+            vec![bunker.public_key().to_hex()],
+        )?;
+        assert!(response.error.is_some());
+
+        // The generated secret is part of the advertised URI
+        let uri = bunker.bunker_uri(&["wss://relay.example.com".to_string()]);
+        let secret = uri.secret.context("Bunker URI has no secret")?;
+        assert_eq!(secret.len(), 32);
+        let response = send(
+            &mut bunker,
+            &app_keys,
+            "connect",
+            vec![bunker.public_key().to_hex(), secret],
+        )?;
+        assert_eq!(response.result.as_deref(), Some("ack"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use nostr_sdk::{EventBuilder, Kind, Tag, Timestamp};
+
+use crate::datetime_utils::parse_rfc3339;
+use crate::nostr::{self, NostrEventInfo};
+use crate::twitter::{ReferencedTweet, Tweet};
+
+/// The retweeted tweet, if `tweet` is a plain retweet without text of its own
+pub fn plain_retweet_of(tweet: &Tweet) -> Option<&ReferencedTweet> {
+    let (is_simple_retweet, _) = nostr::analyze_retweet(tweet);
+    if !is_simple_retweet {
+        return None;
+    }
+    tweet
+        .referenced_tweets
+        .as_ref()?
+        .iter()
+        .find(|rt| rt.type_field == "retweeted")
+}
+
+/// Builds the NIP-18 repost of a mirrored original, dated like the retweet
+///
+/// Text notes get a kind 6 repost, anything else (e.g. a kind 20 picture) a
+/// kind 16 generic repost with a `k` tag naming the original's kind.
+pub fn repost_builder(retweet: &Tweet, original: &NostrEventInfo) -> Result<EventBuilder> {
+    let created_at = parse_rfc3339(&retweet.created_at)
+        .with_context(|| format!("Retweet {id} has an invalid date", id = retweet.id))?
+        .timestamp() as u64;
+    let relay = original.relays.first().map(String::as_str).unwrap_or("");
+    let retweet_url = nostr::build_twitter_status_url(&retweet.id);
+
+    // NIP-18 lets reposts embed the original, so clients can show it without fetching
+    let content = match &original.event_json {
+        Some(json) => {
+            let event: serde_json::Value =
+                serde_json::from_str(json).context("Failed to parse mirrored event JSON")?;
+            event.to_string()
+        }
+        None => String::new(),
+    };
+
+    let mut tags = vec![
+        Tag::parse(vec!["e", original.event_id.as_str(), relay])?,
+        Tag::parse(vec!["p", original.pubkey.as_str()])?,
+        Tag::parse(vec!["r", retweet_url.as_str()])?,
+        nostr::tweet_proxy_tag(&retweet.id)?,
+        Tag::parse(vec!["client", "nostrweet"])?,
+    ];
+    let kind = if original.kind() == 1 {
+        Kind::Repost
+    } else {
+        tags.push(Tag::parse(vec!["k", original.kind().to_string().as_str()])?);
+        Kind::GenericRepost
+    };
+
+    Ok(EventBuilder::new(kind, content)
+        .custom_created_at(Timestamp::from(created_at))
+        .tags(tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    fn retweet(text: &str) -> Result<Tweet> {
+        test_tweet(
+            "2000",
+            "retweeter",
+            serde_json::json!({
+                "text": text,
+                "referenced_tweets": [{ "type": "retweeted", "id": "1000" }]
+            }),
+        )
+    }
+
+    fn mirrored_original(event_json: &str) -> NostrEventInfo {
+        NostrEventInfo {
+            tweet_id: "1000".to_string(),
+            event_id: "01".repeat(32),
+            pubkey: "ab".repeat(32),
+            created_at: 1705764000,
+            media_urls: Vec::new(),
+            relays: vec!["wss://relay.example.com".to_string()],
+            event_json: Some(event_json.to_string()),
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_plain_retweet_of() -> Result<()> {
+        let plain = retweet("RT @original: Hello Nostr")?;
+        assert_eq!(
+            plain_retweet_of(&plain).map(|rt| rt.id.as_str()),
+            Some("1000")
+        );
+
+        // Retweets with their own text keep the copied format
+        let commented = retweet("Worth reading\nRT @original: Hello Nostr")?;
+        assert!(plain_retweet_of(&commented).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repost_builder() -> Result<()> {
+        let keys = nostr_sdk::Keys::generate();
+        let original = mirrored_original(r#"{ "id": "0101", "kind": 1 }"#);
+
+        let event = repost_builder(&retweet("RT @original: Hello Nostr")?, &original)?
+            .sign_with_keys(&keys)?;
+        assert_eq!(event.kind, Kind::Repost);
+        assert_eq!(event.created_at.as_u64(), 1705764600);
+        assert_eq!(event.content, r#"{"id":"0101","kind":1}"#);
+
+        let tags: Vec<Vec<String>> = event.tags.iter().map(|t| t.clone().to_vec()).collect();
+        assert!(tags.contains(&vec![
+            "e".to_string(),
+            "01".repeat(32),
+            "wss://relay.example.com".to_string()
+        ]));
+        assert!(tags.contains(&vec!["p".to_string(), "ab".repeat(32)]));
+        assert!(tags.contains(&vec![
+            "proxy".to_string(),
+            "https://x.com/i/status/2000".to_string(),
+            "web".to_string()
+        ]));
+        assert!(!tags.iter().any(|tag| tag[0] == "k"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repost_builder_generic_repost() -> Result<()> {
+        let keys = nostr_sdk::Keys::generate();
+        let original = mirrored_original(r#"{ "id": "0101", "kind": 20 }"#);
+
+        let event = repost_builder(&retweet("RT @original: Hello Nostr")?, &original)?
+            .sign_with_keys(&keys)?;
+        assert_eq!(event.kind, Kind::GenericRepost);
+
+        let tags: Vec<Vec<String>> = event.tags.iter().map(|t| t.clone().to_vec()).collect();
+        assert!(tags.contains(&vec!["k".to_string(), "20".to_string()]));
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result, bail};
+use nostr_sdk::{Event, EventBuilder, Keys, PublicKey};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::key_overrides::{AccountOverride, KeyOverride, KeyOverrides};
+use crate::keys::KeySource;
+use crate::remote_signer::{BunkerUri, RemoteSigner};
+use crate::twitter::User;
+
+/// Where the keys of mirrored users come from, built once in `main`
+#[derive(Clone, Default)]
+pub struct Signers {
+    keys: KeySource,
+    /// Remote signer all signing goes through when `--bunker` is set
+    remote: Option<Arc<RemoteSigner>>,
+    /// Accounts with their own Nostr identity, from `key_overrides.json`
+    overrides: Option<Arc<KeyOverrides>>,
+    /// Connections to the personal bunkers of overridden accounts, keyed by bunker URI
+    account_signers: Arc<Mutex<HashMap<String, Arc<RemoteSigner>>>>,
+}
+
+impl Signers {
+    pub fn new(keys: KeySource) -> Self {
+        Self {
+            keys,
+            ..Self::default()
+        }
+    }
+
+    /// Routes all signing through a NIP-46 remote signer instead of the local mnemonic
+    pub fn with_remote_signer(mut self, remote: RemoteSigner) -> Self {
+        self.remote = Some(Arc::new(remote));
+        self
+    }
+
+    /// Uses the accounts' own Nostr identities instead of derived keys
+    pub fn with_key_overrides(mut self, overrides: KeyOverrides) -> Self {
+        self.overrides = Some(Arc::new(overrides));
+        self
+    }
+
+    pub fn keys(&self) -> &KeySource {
+        &self.keys
+    }
+
+    /// Whether signing goes through a remote signer, secret keys are not available then
+    pub fn is_remote(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Override configured for a Twitter account, by user ID or username
+    pub fn override_for_account(
+        &self,
+        twitter_user_id: &str,
+        username: &str,
+    ) -> Option<&AccountOverride> {
+        self.overrides
+            .as_ref()?
+            .for_account(twitter_user_id, username)
+    }
+
+    /// Pubkey of the override configured for a Twitter username, if any and known
+    pub fn override_public_key_for_username(&self, username: &str) -> Result<Option<PublicKey>> {
+        let Some(overrides) = &self.overrides else {
+            return Ok(None);
+        };
+        match overrides.for_username(username) {
+            Some(account) => overrides.public_key(account),
+            None => Ok(None),
+        }
+    }
+
+    /// Pubkey of an override without network access, see [`KeyOverrides::public_key`]
+    pub fn override_public_key(&self, account: &AccountOverride) -> Result<Option<PublicKey>> {
+        match &self.overrides {
+            Some(overrides) => overrides.public_key(account),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the connection to an account's own bunker, connecting on first use
+    async fn account_signer(&self, uri: &str) -> Result<Arc<RemoteSigner>> {
+        let mut account_signers = self.account_signers.lock().await;
+        if let Some(remote) = account_signers.get(uri) {
+            return Ok(remote.clone());
+        }
+
+        let data_dir = self
+            .overrides
+            .as_ref()
+            .map(|overrides| overrides.data_dir())
+            .context("Key overrides are not loaded")?;
+        let bunker_uri = BunkerUri::parse(uri)?;
+        let remote = Arc::new(
+            RemoteSigner::connect_account(&bunker_uri, data_dir)
+                .await
+                .context("Failed to connect to account bunker")?,
+        );
+        account_signers.insert(uri.to_string(), remote.clone());
+        Ok(remote)
+    }
+
+    /// Signer of the mnemonic's root identity, the bunker's own key with a remote signer
+    pub fn root_signer(&self) -> Result<UserSigner> {
+        match &self.remote {
+            Some(remote) => Ok(UserSigner::Remote {
+                remote: remote.clone(),
+                twitter_user_id: None,
+                public_key: remote.remote_public_key(),
+            }),
+            None => Ok(UserSigner::Local(self.keys.root_keys()?)),
+        }
+    }
+}
+
+/// Signs events on behalf of one mirrored Twitter user
+#[derive(Clone)]
+pub enum UserSigner {
+    /// Keys derived in-process from the mnemonic
+    Local(Keys),
+    /// Keys held by a NIP-46 remote signer
+    Remote {
+        remote: Arc<RemoteSigner>,
+        /// Twitter user the key is derived for, `None` for the bunker's own key
+        twitter_user_id: Option<String>,
+        public_key: PublicKey,
+    },
+    /// Pubkey-only key override: mentions resolve to it but nothing is signed
+    ReadOnly(PublicKey),
+}
+
+impl UserSigner {
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            UserSigner::Local(keys) => keys.public_key(),
+            UserSigner::Remote { public_key, .. } => *public_key,
+            UserSigner::ReadOnly(public_key) => *public_key,
+        }
+    }
+
+    /// Whether events can be signed for this user (false for pubkey-only overrides)
+    pub fn can_sign(&self) -> bool {
+        !matches!(self, UserSigner::ReadOnly(_))
+    }
+
+    /// Local keys, when the secret is held in-process
+    pub fn keys(&self) -> Option<&Keys> {
+        match self {
+            UserSigner::Local(keys) => Some(keys),
+            UserSigner::Remote { .. } | UserSigner::ReadOnly(_) => None,
+        }
+    }
+
+    /// Builds and signs an event with this user's key
+    pub async fn sign_event(&self, builder: EventBuilder) -> Result<Event> {
+        match self {
+            UserSigner::Local(keys) => builder.sign(keys).await.context("Failed to sign event"),
+            UserSigner::Remote {
+                remote,
+                twitter_user_id,
+                public_key,
+            } => {
+                let unsigned = builder.build(*public_key);
+                remote
+                    .sign_event(twitter_user_id.as_deref(), unsigned)
+                    .await
+            }
+            UserSigner::ReadOnly(public_key) => {
+                bail!("Cannot sign for {public_key}, it is a pubkey-only key override")
+            }
+        }
+    }
+}
+
+/// Returns the signer for a Twitter account: its key override if any, then
+/// the remote signer when one is configured, otherwise keys derived from the
+/// mnemonic
+///
+/// Overrides are matched by user ID or username, overrides keyed by username
+/// only know the user ID once the user's profile is cached.
+pub async fn signer_for_account(
+    twitter_user_id: &str,
+    username: &str,
+    signers: &Signers,
+) -> Result<UserSigner> {
+    let account = signers.override_for_account(twitter_user_id, username);
+    signer_with_override(twitter_user_id, account, signers).await
+}
+
+/// Returns the signer for a Twitter user, see [`signer_for_account`]
+pub async fn signer_for_user(user: &User, signers: &Signers) -> Result<UserSigner> {
+    signer_for_account(&user.id, &user.username, signers).await
+}
+
+async fn signer_with_override(
+    twitter_user_id: &str,
+    account: Option<&AccountOverride>,
+    signers: &Signers,
+) -> Result<UserSigner> {
+    if let Some(account) = account {
+        debug!(
+            "Using {kind} key override for Twitter user {twitter_user_id}",
+            kind = account.key.kind()
+        );
+        return match &account.key {
+            KeyOverride::Nsec(nsec) => {
+                Ok(UserSigner::Local(Keys::parse(nsec).with_context(|| {
+                    format!("Invalid nsec in key override for Twitter user {twitter_user_id}")
+                })?))
+            }
+            KeyOverride::Pubkey(pubkey) => Ok(UserSigner::ReadOnly(
+                PublicKey::parse(pubkey).context("Invalid pubkey in key override")?,
+            )),
+            KeyOverride::Bunker(uri) => {
+                let remote = signers.account_signer(uri).await?;
+                let public_key = remote.get_public_key(twitter_user_id).await?;
+                Ok(UserSigner::Remote {
+                    remote,
+                    twitter_user_id: Some(twitter_user_id.to_string()),
+                    public_key,
+                })
+            }
+        };
+    }
+
+    if let Some(remote) = &signers.remote {
+        let public_key = remote
+            .get_public_key(twitter_user_id)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get pubkey for Twitter user {twitter_user_id} from remote signer"
+                )
+            })?;
+        debug!("Using remote signer for Twitter user {twitter_user_id}");
+        return Ok(UserSigner::Remote {
+            remote: remote.clone(),
+            twitter_user_id: Some(twitter_user_id.to_string()),
+            public_key,
+        });
+    }
+
+    debug!("Deriving key for Twitter user {twitter_user_id}");
+    Ok(UserSigner::Local(
+        signers.keys().derive_keys(twitter_user_id)?,
+    ))
+}
+
+/// Returns the pubkey of a Twitter account without network access
+///
+/// With a remote signer this only knows users the signer was already asked
+/// about, and returns `None` for the rest.
+pub fn known_public_key(
+    twitter_user_id: &str,
+    username: &str,
+    signers: &Signers,
+) -> Result<Option<PublicKey>> {
+    if let Some(account) = signers.override_for_account(twitter_user_id, username) {
+        return signers.override_public_key(account);
+    }
+
+    if let Some(remote) = &signers.remote {
+        return Ok(remote.cached_public_key(twitter_user_id));
+    }
+
+    Ok(Some(
+        signers.keys().derive_keys(twitter_user_id)?.public_key(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_signers;
+    use nostr_sdk::ToBech32;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_username_override_applies_without_cached_profile() -> Result<()> {
+        let keys = Keys::generate();
+        let entries = BTreeMap::from([(
+            "@Jack".to_string(),
+            KeyOverride::Nsec(keys.secret_key().to_bech32()?),
+        )]);
+        let temp_dir = tempfile::tempdir()?;
+        let signers = test_signers()
+            .with_key_overrides(KeyOverrides::from_entries(temp_dir.path(), entries)?);
+
+        assert_eq!(
+            known_public_key("12", "jack", &signers)?,
+            Some(keys.public_key())
+        );
+        // Another account still derives its key
+        assert_ne!(
+            known_public_key("34", "someone_else", &signers)?,
+            Some(keys.public_key())
+        );
+        Ok(())
+    }
+}
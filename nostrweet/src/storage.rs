@@ -8,11 +8,12 @@ use crate::filename_utils::{
 };
 #[cfg(test)]
 use crate::twitter::{NoteTweet, ReferencedTweet};
-use crate::twitter::{Tweet, User};
+use crate::twitter::{Tweet, TwitterApi, User};
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// Find an existing tweet JSON file in the output directory
 pub fn find_existing_tweet_json(tweet_id: &str, data_dir: &Path) -> Option<PathBuf> {
@@ -59,6 +60,8 @@ pub fn save_tweet(tweet: &Tweet, data_dir: &Path) -> Result<PathBuf> {
 
     info!("Saved tweet data to {path}", path = file_path.display());
 
+    crate::edits::record_version(tweet, data_dir)?;
+
     Ok(file_path)
 }
 
@@ -168,6 +171,53 @@ pub fn find_latest_user_profile(username: &str, data_dir: &Path) -> Result<Optio
     Ok(latest_file.map(|(_, path)| path))
 }
 
+/// Load the latest cached profile of every user in the data directory, sorted by username
+pub fn list_cached_user_profiles(data_dir: &Path) -> Result<Vec<User>> {
+    let data_dir_str = data_dir
+        .to_str()
+        .context("Data directory path contains invalid UTF-8")?;
+    let glob_pattern = format!("{data_dir_str}/??????????????_*_*.json");
+
+    // Keep only the newest profile per user ID
+    let mut latest: HashMap<String, (chrono::NaiveDateTime, User)> = HashMap::new();
+
+    for path in glob::glob(&glob_pattern)?.flatten() {
+        let Some(timestamp) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|filename| filename.split('_').next())
+            .and_then(|timestamp_str| parse_compact_datetime(timestamp_str).ok())
+        else {
+            continue;
+        };
+
+        let user = match load_user_from_file(&path) {
+            Ok(user) => user,
+            Err(e) => {
+                warn!(
+                    "Skipping unreadable profile {path}: {e}",
+                    path = path.display()
+                );
+                continue;
+            }
+        };
+
+        if latest
+            .get(&user.id)
+            .is_none_or(|(latest_timestamp, _)| timestamp > *latest_timestamp)
+        {
+            latest.insert(user.id.clone(), (timestamp, user));
+        }
+    }
+
+    let mut users: Vec<User> = latest.into_values().map(|(_, user)| user).collect();
+    users.sort_by(|a, b| a.username.cmp(&b.username));
+
+    debug!("Found {count} cached user profiles", count = users.len());
+
+    Ok(users)
+}
+
 /// Saves user profile data to a JSON file in the specified directory
 pub fn save_user_profile(user: &User, data_dir: &Path) -> Result<PathBuf> {
     let user_id = &user.id;
@@ -246,7 +296,7 @@ pub fn mark_tweet_as_not_found(tweet_id: &str, data_dir: &Path) -> Result<()> {
 pub async fn load_or_fetch_tweet(
     tweet_id: &str,
     data_dir: &Path,
-    bearer_token: Option<&str>,
+    twitter_api: Option<&TwitterApi>,
 ) -> Result<Tweet> {
     // Step 1: Check if we have the tweet in cache
     if let Some(existing_path) = find_existing_tweet_json(tweet_id, data_dir) {
@@ -258,7 +308,7 @@ pub async fn load_or_fetch_tweet(
             .with_context(|| format!("Failed to load existing tweet data for {tweet_id}"))?;
 
         // Step 2: Ensure referenced tweets are enriched (may need API call)
-        ensure_tweet_enriched(&mut tweet, data_dir, bearer_token)
+        ensure_tweet_enriched(&mut tweet, data_dir, twitter_api)
             .await
             .with_context(|| format!("Failed to enrich referenced tweets for {tweet_id}"))?;
 
@@ -268,10 +318,11 @@ pub async fn load_or_fetch_tweet(
     // Step 3: Not in cache - need to fetch from Twitter API
     debug!("Tweet {tweet_id} not found locally, downloading from Twitter API");
 
-    let bearer =
-        bearer_token.ok_or_else(|| anyhow::anyhow!("Bearer token required to download tweet"))?;
+    let twitter_api =
+        twitter_api.ok_or_else(|| anyhow::anyhow!("Bearer token required to download tweet"))?;
 
-    let client = crate::twitter::TwitterClient::new(data_dir, bearer)
+    let client = twitter_api
+        .client(data_dir)
         .context("Failed to initialize Twitter client")?;
 
     let mut tweet = client
@@ -306,7 +357,7 @@ pub async fn load_or_fetch_tweet(
 pub async fn ensure_tweet_enriched(
     tweet: &mut Tweet,
     data_dir: &Path,
-    bearer_token: Option<&str>,
+    twitter_api: Option<&TwitterApi>,
 ) -> Result<()> {
     // Check if we have referenced tweets that need enrichment
     let has_unenriched_refs = tweet
@@ -315,10 +366,10 @@ pub async fn ensure_tweet_enriched(
         .map(|refs| refs.iter().any(|r| r.data.is_none()))
         .unwrap_or(false);
 
-    if has_unenriched_refs && bearer_token.is_some() {
+    if has_unenriched_refs && let Some(twitter_api) = twitter_api {
         debug!("Some referenced tweets need enrichment, fetching from Twitter API");
-        let bearer = bearer_token.unwrap();
-        let client = crate::twitter::TwitterClient::new(data_dir, bearer)
+        let client = twitter_api
+            .client(data_dir)
             .context("Failed to initialize Twitter client for enriching referenced tweets")?;
 
         // This will fetch full tweet data including note_tweet for long tweets
@@ -360,6 +411,13 @@ mod tests {
             includes: None,
             author_id: Some("987654321".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         }
     }
 
@@ -498,6 +556,46 @@ mod tests {
         assert!(latest.is_none());
     }
 
+    #[test]
+    fn test_list_cached_user_profiles() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut user = create_test_user();
+
+        // Two profiles for the same user, the newer one has a different name
+        let older = temp_dir
+            .path()
+            .join("20230101120000_testuser_987654321.json");
+        fs::write(&older, serde_json::to_string(&user)?)?;
+        user.name = Some("Renamed User".to_string());
+        let newer = temp_dir
+            .path()
+            .join("20230102120000_testuser_987654321.json");
+        fs::write(&newer, serde_json::to_string(&user)?)?;
+
+        // A second user plus a tweet file that must be ignored
+        let other = User {
+            id: "111".to_string(),
+            username: "alice".to_string(),
+            ..Default::default()
+        };
+        fs::write(
+            temp_dir.path().join("20230101120000_alice_111.json"),
+            serde_json::to_string(&other)?,
+        )?;
+        fs::write(
+            temp_dir.path().join("20230101_120000_alice_2000.json"),
+            "{}",
+        )?;
+
+        let users = list_cached_user_profiles(temp_dir.path())?;
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[0].username, "alice");
+        assert_eq!(users[1].username, "testuser");
+        assert_eq!(users[1].name.as_deref(), Some("Renamed User"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_find_latest_tweet_id_for_user() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -560,6 +658,13 @@ mod tests {
             includes: None,
             author_id: Some("987654321".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         let temp_dir = TempDir::new().unwrap();
@@ -601,6 +706,13 @@ mod tests {
             includes: None,
             author_id: Some("987654321".to_string()),
             note_tweet: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            edit_history_tweet_ids: None,
+            edit_controls: None,
+            lang: None,
+            possibly_sensitive: None,
+            geo: None,
         };
 
         // Check that the tweet needs enrichment
@@ -641,6 +753,13 @@ mod tests {
                         note_tweet: Some(NoteTweet {
                             text: "This is the extended full text of the tweet that would be longer than 280 characters and might have been cut off with ellipsis in the truncated version...".to_string(),
                         }),
+                        conversation_id: None,
+                        in_reply_to_user_id: None,
+                        edit_history_tweet_ids: None,
+                        edit_controls: None,
+                        lang: None,
+                        possibly_sensitive: None,
+                        geo: None,
                     }));
                 }
             }
@@ -0,0 +1,34 @@
+//! Fixtures shared by unit tests
+
+use anyhow::{Context, Result};
+
+use crate::keys::{DerivationScheme, KeySource};
+use crate::signer::Signers;
+use crate::twitter::Tweet;
+
+/// BIP39 test vector mnemonic
+pub const TEST_MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// Signers deriving every key locally from [`TEST_MNEMONIC`]
+pub fn test_signers() -> Signers {
+    Signers::new(KeySource::new(
+        Some(TEST_MNEMONIC.to_string()),
+        DerivationScheme::default(),
+    ))
+}
+
+/// Tweet `id` by @`username` (user ID 42), with the API fields in `extra` added or replaced
+pub fn test_tweet(id: &str, username: &str, extra: serde_json::Value) -> Result<Tweet> {
+    let mut value = serde_json::json!({
+        "id": id,
+        "text": format!("tweet {id}"),
+        "created_at": "2024-01-20T15:30:00.000Z",
+        "author": { "id": "42", "username": username },
+        "author_id": "42"
+    });
+    if let (Some(object), Some(extra)) = (value.as_object_mut(), extra.as_object()) {
+        object.extend(extra.clone());
+    }
+    serde_json::from_value(value).with_context(|| format!("Invalid test tweet {id}"))
+}
@@ -0,0 +1,360 @@
+use anyhow::Result;
+use nostr_sdk::Tag;
+use std::path::Path;
+use tracing::{debug, warn};
+
+use crate::nostr::{self, NostrEventInfo};
+use crate::storage;
+use crate::twitter::{ReferencedTweet, Tweet, TwitterApi};
+
+/// Stop walking up a thread after this many tweets (guards against cycles in bad data)
+const MAX_THREAD_DEPTH: usize = 200;
+
+fn author_id(tweet: &Tweet) -> Option<&str> {
+    if tweet.author.id.is_empty() {
+        tweet.author_id.as_deref()
+    } else {
+        Some(tweet.author.id.as_str())
+    }
+}
+
+fn referenced(tweet: &Tweet, type_field: &str) -> Option<&ReferencedTweet> {
+    tweet
+        .referenced_tweets
+        .as_ref()?
+        .iter()
+        .find(|rt| rt.type_field == type_field)
+}
+
+/// ID of the tweet this one replies to
+pub fn reply_parent(tweet: &Tweet) -> Option<&str> {
+    referenced(tweet, "replied_to").map(|rt| rt.id.as_str())
+}
+
+/// ID of the tweet this one quotes
+pub fn quoted_tweet(tweet: &Tweet) -> Option<&str> {
+    referenced(tweet, "quoted").map(|rt| rt.id.as_str())
+}
+
+/// ID of the tweet this one continues, if it replies to its own author
+pub fn self_reply_parent(tweet: &Tweet) -> Option<&str> {
+    let parent = referenced(tweet, "replied_to")?;
+    let author = author_id(tweet)?;
+
+    let replies_to_self = match (&tweet.in_reply_to_user_id, &parent.data) {
+        (Some(reply_to_user), _) => reply_to_user == author,
+        (None, Some(data)) => author_id(data) == Some(author),
+        (None, None) => false,
+    };
+    replies_to_self.then_some(parent.id.as_str())
+}
+
+/// Loads the earlier parts of a self-thread, root first
+///
+/// Parts missing from the cache are fetched when the Twitter API is given. The
+/// walk stops at the first part that can't be loaded, so the result may start
+/// in the middle of the thread.
+pub async fn load_thread_ancestors(
+    tweet: &Tweet,
+    data_dir: &Path,
+    twitter_api: Option<&TwitterApi>,
+) -> Result<Vec<Tweet>> {
+    let mut ancestors: Vec<Tweet> = Vec::new();
+    let mut parent_id = self_reply_parent(tweet).map(str::to_string);
+
+    while let Some(id) = parent_id.take() {
+        if ancestors.len() >= MAX_THREAD_DEPTH {
+            warn!(
+                "Thread of tweet {tweet_id} is longer than {MAX_THREAD_DEPTH} tweets, stopping",
+                tweet_id = tweet.id
+            );
+            break;
+        }
+        if storage::is_tweet_not_found(&id, data_dir) {
+            debug!("Thread part {id} is marked as not found");
+            break;
+        }
+
+        match storage::load_or_fetch_tweet(&id, data_dir, twitter_api).await {
+            Ok(parent) => {
+                parent_id = self_reply_parent(&parent).map(str::to_string);
+                ancestors.push(parent);
+            }
+            Err(e) => {
+                warn!("Failed to load thread part {id}: {e:#}");
+                break;
+            }
+        }
+    }
+
+    ancestors.reverse();
+    Ok(ancestors)
+}
+
+/// Finds the ID of the first tweet of a conversation, walking the cached replies
+fn conversation_root_id(tweet: &Tweet, data_dir: &Path) -> Result<Option<String>> {
+    if let Some(conversation_id) = &tweet.conversation_id {
+        return Ok(Some(conversation_id.clone()));
+    }
+
+    let Some(mut root_id) = reply_parent(tweet).map(str::to_string) else {
+        return Ok(None);
+    };
+    for _ in 0..MAX_THREAD_DEPTH {
+        let Some(path) = storage::find_existing_tweet_json(&root_id, data_dir) else {
+            break;
+        };
+        let parent = storage::load_tweet_from_file(&path)?;
+        match reply_parent(&parent) {
+            Some(id) => root_id = id.to_string(),
+            None => break,
+        }
+    }
+    Ok(Some(root_id))
+}
+
+/// The event marked as the thread's NIP-10 root
+///
+/// That is the conversation's first tweet when it was mirrored. Otherwise the
+/// reply chain is walked through the cache, and the earliest mirrored tweet on
+/// it becomes the root.
+async fn root_event(tweet: &Tweet, data_dir: &Path) -> Result<Option<NostrEventInfo>> {
+    if let Some(root_id) = conversation_root_id(tweet, data_dir)?
+        && let Some(root) = nostr::check_existing_nostr_event(data_dir, &root_id).await?
+    {
+        return Ok(Some(root));
+    }
+
+    let mut earliest = None;
+    let mut parent_id = reply_parent(tweet).map(str::to_string);
+    for _ in 0..MAX_THREAD_DEPTH {
+        let Some(id) = parent_id.take() else {
+            break;
+        };
+        if let Some(info) = nostr::check_existing_nostr_event(data_dir, &id).await? {
+            earliest = Some(info);
+        }
+        let Some(path) = storage::find_existing_tweet_json(&id, data_dir) else {
+            break;
+        };
+        let parent = storage::load_tweet_from_file(&path)?;
+        parent_id = reply_parent(&parent).map(str::to_string);
+    }
+    Ok(earliest)
+}
+
+fn event_tag(info: &NostrEventInfo, marker: &str) -> Result<Tag> {
+    let relay = info.relays.first().map(String::as_str).unwrap_or("");
+    Ok(Tag::parse(vec![
+        "e",
+        info.event_id.as_str(),
+        relay,
+        marker,
+        info.pubkey.as_str(),
+    ])?)
+}
+
+fn quote_tag(info: &NostrEventInfo) -> Result<Tag> {
+    let relay = info.relays.first().map(String::as_str).unwrap_or("");
+    Ok(Tag::parse(vec![
+        "q",
+        info.event_id.as_str(),
+        relay,
+        info.pubkey.as_str(),
+    ])?)
+}
+
+/// NIP-10 `e` tags for replies and the NIP-18 `q` tag for quotes of mirrored tweets
+///
+/// The events are looked up in the `nostr/<tweet_id>.json` records, so the
+/// referenced tweets have to be posted first. References that weren't
+/// mirrored get no tags; their content is quoted inline instead. The `p` tags
+/// of the referenced authors come from the formatted content.
+pub async fn reference_event_tags(tweet: &Tweet, data_dir: &Path) -> Result<Vec<Tag>> {
+    let mut tags = Vec::new();
+
+    if let Some(parent_id) = reply_parent(tweet) {
+        match nostr::check_existing_nostr_event(data_dir, parent_id).await? {
+            Some(parent) => match root_event(tweet, data_dir).await? {
+                Some(root) if root.event_id != parent.event_id => {
+                    tags.push(event_tag(&root, "root")?);
+                    tags.push(event_tag(&parent, "reply")?);
+                }
+                // A direct reply to the root only carries the root marker
+                _ => tags.push(event_tag(&parent, "root")?),
+            },
+            None => debug!(
+                "Parent tweet {parent_id} of {tweet_id} was not mirrored, not linking it",
+                tweet_id = tweet.id
+            ),
+        }
+    }
+
+    if let Some(quoted_id) = quoted_tweet(tweet) {
+        match nostr::check_existing_nostr_event(data_dir, quoted_id).await? {
+            Some(quoted) => tags.push(quote_tag(&quoted)?),
+            None => debug!(
+                "Quoted tweet {quoted_id} of {tweet_id} was not mirrored, not linking it",
+                tweet_id = tweet.id
+            ),
+        }
+    }
+
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    fn thread_tweet(id: &str, parent: Option<&str>) -> Result<Tweet> {
+        let mut tweet = test_tweet(
+            id,
+            "threader",
+            serde_json::json!({ "text": format!("part {id}"), "conversation_id": "100" }),
+        )?;
+        if let Some(parent) = parent {
+            tweet.in_reply_to_user_id = Some("42".to_string());
+            tweet.referenced_tweets = Some(vec![ReferencedTweet {
+                id: parent.to_string(),
+                type_field: "replied_to".to_string(),
+                data: None,
+            }]);
+        }
+        Ok(tweet)
+    }
+
+    async fn record_event(data_dir: &Path, tweet_id: &str, event_id: &str) -> Result<()> {
+        let info = NostrEventInfo {
+            tweet_id: tweet_id.to_string(),
+            event_id: event_id.to_string(),
+            pubkey: "ab".repeat(32),
+            created_at: 1705764600,
+            media_urls: Vec::new(),
+            relays: vec!["wss://relay.example.com".to_string()],
+            event_json: None,
+            media_event_ids: Vec::new(),
+            media_event_kinds: Vec::new(),
+        };
+        nostr::save_nostr_event_info(&info, data_dir).await?;
+        Ok(())
+    }
+
+    fn e_tags(tags: &[Tag]) -> Vec<Vec<String>> {
+        tags.iter()
+            .map(|tag| tag.clone().to_vec())
+            .filter(|tag| tag[0] == "e")
+            .collect()
+    }
+
+    #[test]
+    fn test_self_reply_parent() -> Result<()> {
+        let mut reply = thread_tweet("101", Some("100"))?;
+        assert_eq!(self_reply_parent(&reply), Some("100"));
+
+        reply.in_reply_to_user_id = Some("7".to_string());
+        assert_eq!(self_reply_parent(&reply), None);
+
+        assert_eq!(self_reply_parent(&thread_tweet("100", None)?), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reference_event_tags() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root_event = "01".repeat(32);
+        let second_event = "02".repeat(32);
+        record_event(temp_dir.path(), "100", &root_event).await?;
+        record_event(temp_dir.path(), "101", &second_event).await?;
+
+        // Direct reply to the root
+        let tags =
+            reference_event_tags(&thread_tweet("101", Some("100"))?, temp_dir.path()).await?;
+        assert_eq!(
+            e_tags(&tags),
+            vec![vec![
+                "e".to_string(),
+                root_event.clone(),
+                "wss://relay.example.com".to_string(),
+                "root".to_string(),
+                "ab".repeat(32),
+            ]]
+        );
+
+        // Deeper parts point at both the root and their parent
+        let tags =
+            reference_event_tags(&thread_tweet("102", Some("101"))?, temp_dir.path()).await?;
+        let markers: Vec<(String, String)> = e_tags(&tags)
+            .into_iter()
+            .map(|tag| (tag[1].clone(), tag[3].clone()))
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                (root_event.clone(), "root".to_string()),
+                (second_event, "reply".to_string())
+            ]
+        );
+
+        // Nothing to link to when the parent wasn't posted
+        let tags =
+            reference_event_tags(&thread_tweet("104", Some("103"))?, temp_dir.path()).await?;
+        assert!(tags.is_empty());
+
+        // Quotes of mirrored tweets get a q tag
+        let mut quote = thread_tweet("200", None)?;
+        quote.referenced_tweets = Some(vec![ReferencedTweet {
+            id: "100".to_string(),
+            type_field: "quoted".to_string(),
+            data: None,
+        }]);
+        let tags = reference_event_tags(&quote, temp_dir.path()).await?;
+        assert_eq!(
+            tags.into_iter().map(Tag::to_vec).collect::<Vec<_>>(),
+            vec![vec![
+                "q".to_string(),
+                root_event,
+                "wss://relay.example.com".to_string(),
+                "ab".repeat(32),
+            ]]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_earliest_mirrored_part_is_root() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        // The first tweet of the thread (100) was never mirrored
+        for (id, parent) in [("101", "100"), ("102", "101")] {
+            storage::save_tweet(&thread_tweet(id, Some(parent))?, temp_dir.path())?;
+        }
+        let first_event = "01".repeat(32);
+        let second_event = "02".repeat(32);
+        record_event(temp_dir.path(), "101", &first_event).await?;
+        record_event(temp_dir.path(), "102", &second_event).await?;
+
+        let markers = |tags: Vec<Tag>| -> Vec<(String, String)> {
+            e_tags(&tags)
+                .into_iter()
+                .map(|tag| (tag[1].clone(), tag[3].clone()))
+                .collect()
+        };
+
+        let tags =
+            reference_event_tags(&thread_tweet("103", Some("102"))?, temp_dir.path()).await?;
+        assert_eq!(
+            markers(tags),
+            vec![
+                (first_event.clone(), "root".to_string()),
+                (second_event, "reply".to_string())
+            ]
+        );
+
+        // Replying to the earliest mirrored part only marks the root
+        let tags =
+            reference_event_tags(&thread_tweet("102", Some("101"))?, temp_dir.path()).await?;
+        assert_eq!(markers(tags), vec![(first_event, "root".to_string())]);
+        Ok(())
+    }
+}
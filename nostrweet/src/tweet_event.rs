@@ -0,0 +1,242 @@
+use anyhow::{Context, Result, ensure};
+use nostr_sdk::{Event, EventBuilder, Kind, PublicKey, Tag, Timestamp};
+use std::path::Path;
+use tracing::{debug, info};
+
+use crate::datetime_utils::parse_rfc3339;
+use crate::edits;
+use crate::media_events;
+use crate::mirror_options::MirrorOptions;
+use crate::nostr::{self, NostrEventInfo, UploadedMedia};
+use crate::nostr_linking::NostrLinkResolver;
+use crate::repost;
+use crate::signer::{self, Signers, UserSigner};
+use crate::thread;
+use crate::tweet_metadata;
+use crate::twitter::{ReferencedTweet, Tweet};
+
+/// The signed events mirroring one tweet, ready to be published
+pub struct TweetEvents {
+    /// The text note first when there is one, then the picture or video event
+    pub events: Vec<Event>,
+    /// The mirrored version this tweet is an edit of
+    pub superseded: Option<NostrEventInfo>,
+}
+
+impl TweetEvents {
+    /// The event the tweet is recorded under
+    pub fn event(&self) -> Result<&Event> {
+        self.events
+            .first()
+            .context("No event was built for the tweet")
+    }
+
+    /// IDs of the picture and video events published for the tweet
+    pub fn media_event_ids(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .filter(|e| e.kind != Kind::TextNote)
+            .map(|e| e.id.to_hex())
+            .collect()
+    }
+
+    /// Kinds of the picture and video events, in the order of `media_event_ids`
+    pub fn media_event_kinds(&self) -> Vec<u16> {
+        self.events
+            .iter()
+            .filter(|e| e.kind != Kind::TextNote)
+            .map(|e| e.kind.as_u16())
+            .collect()
+    }
+}
+
+/// Creates tags for a Nostr event including original and uploaded media URLs and mentions
+///
+/// Each described media file gets a NIP-92 `imeta` tag.
+pub fn create_nostr_event_tags(
+    tweet_id: &str,
+    orig_urls: &[String],
+    attachments: &[UploadedMedia],
+    mentioned_pubkeys: &[PublicKey],
+) -> Result<Vec<Tag>> {
+    let mut tags = Vec::new();
+
+    // reference original tweet
+    let twitter_url = nostr::build_twitter_status_url(tweet_id);
+    tags.push(Tag::parse(vec!["r", twitter_url.as_str()])?);
+    tags.push(nostr::tweet_proxy_tag(tweet_id)?);
+
+    // Add p-tags for mentioned users
+    for pubkey in mentioned_pubkeys {
+        tags.push(Tag::parse(vec!["p", &pubkey.to_hex()])?);
+    }
+
+    // media tagging: tag original URLs as media unless a described file covers them
+    for orig in orig_urls {
+        if !attachments
+            .iter()
+            .any(|a| a.url == *orig || a.source.as_ref() == Some(orig))
+        {
+            tags.push(Tag::parse(vec!["media", orig.as_str()])?);
+        }
+    }
+
+    // tag the source of uploaded files, their URL and their full description
+    for attachment in attachments {
+        if let Some(source) = &attachment.source
+            && *source != attachment.url
+        {
+            tags.push(Tag::parse(vec!["source", source.as_str()])?);
+        }
+        tags.push(Tag::parse(vec!["media", attachment.url.as_str()])?);
+        tags.push(attachment.imeta_tag()?);
+    }
+
+    // Add client identifier
+    tags.push(Tag::parse(vec!["client", "nostrweet"])?);
+
+    Ok(tags)
+}
+
+/// Builds and signs the events mirroring a tweet
+///
+/// The text note links the tweet's thread, quotes and superseded version.
+/// Media-only tweets can also, or instead, be published as a picture or
+/// video event, depending on the media event policy.
+#[allow(clippy::too_many_arguments)]
+pub async fn build_tweet_event(
+    tweet: &Tweet,
+    tweet_media_urls: &[String],
+    uploaded_urls: &[String],
+    described_media: &[UploadedMedia],
+    signer: &UserSigner,
+    data_dir: &Path,
+    signers: &Signers,
+    mirror_options: &MirrorOptions,
+) -> Result<TweetEvents> {
+    let tweet_id = &tweet.id;
+    ensure!(
+        !tweet.created_at.is_empty(),
+        "No creation date found in tweet {tweet_id} - cannot create Nostr event without a valid timestamp"
+    );
+    let timestamp = Timestamp::from(parse_rfc3339(&tweet.created_at)?.timestamp() as u64);
+    debug!("Using tweet creation timestamp: {timestamp}");
+
+    // Link uploaded media in the content, falling back to the Twitter URLs
+    let media_urls = if uploaded_urls.is_empty() {
+        tweet_media_urls
+    } else {
+        uploaded_urls
+    };
+
+    // Create a resolver for Twitter username to Nostr pubkey mapping
+    let mut resolver = NostrLinkResolver::new(
+        Some(data_dir.to_string_lossy().to_string()),
+        signers.clone(),
+    );
+    let (mut content, mentioned_pubkeys) = nostr::format_tweet_as_nostr_content_with_mentions(
+        tweet,
+        media_urls,
+        &mut resolver,
+        &mirror_options.content_templates,
+    )?;
+
+    // A new version of an edited tweet links the version mirrored before it
+    let superseded = edits::superseded_event(tweet, data_dir, mirror_options.edit_mode).await?;
+    if let Some(previous) = &superseded {
+        info!(
+            "Tweet {tweet_id} is an edit of mirrored tweet {previous_id}",
+            previous_id = previous.tweet_id
+        );
+        edits::add_correction_note(&mut content, previous, mirror_options.edit_mode)?;
+    }
+
+    // Thread, quote and edit links, carried by the text note and the media event alike
+    let mut links = thread::reference_event_tags(tweet, data_dir).await?;
+    if let Some(previous) = &superseded {
+        links.push(edits::superseded_tag(previous)?);
+    }
+
+    let mut tags = create_nostr_event_tags(
+        tweet_id,
+        tweet_media_urls,
+        described_media,
+        &mentioned_pubkeys,
+    )?;
+    tags.extend(links.iter().cloned());
+    tags.extend(tweet_metadata::metadata_tags(tweet)?);
+
+    let media_builder = media_events::media_event_builder(
+        tweet,
+        described_media,
+        &links,
+        &mut resolver,
+        mirror_options.media_event_policy,
+        &mirror_options.content_templates,
+    )?;
+    let mut events = Vec::new();
+    if media_builder.is_none() || !mirror_options.media_event_policy.replaces_text_note() {
+        let builder = EventBuilder::new(Kind::TextNote, content)
+            .custom_created_at(timestamp)
+            .tags(tags);
+        events.push(signer.sign_event(builder).await?);
+    }
+    if let Some(builder) = media_builder {
+        events.push(signer.sign_event(builder).await?);
+    }
+
+    Ok(TweetEvents { events, superseded })
+}
+
+/// The retweeted tweet to mirror before reposting it, if it isn't on Nostr yet
+///
+/// Only originals whose author can be signed for are mirrored.
+pub async fn original_to_mirror<'a>(
+    original: &'a ReferencedTweet,
+    data_dir: &Path,
+    signers: &Signers,
+) -> Result<Option<&'a Tweet>> {
+    if nostr::check_existing_nostr_event(data_dir, &original.id)
+        .await?
+        .is_some()
+    {
+        return Ok(None);
+    }
+    let Some(data) = original.data.as_deref() else {
+        return Ok(None);
+    };
+    if data.author.id.is_empty()
+        || !signer::signer_for_user(&data.author, signers)
+            .await?
+            .can_sign()
+    {
+        return Ok(None);
+    }
+    Ok(Some(data))
+}
+
+/// Signs the NIP-18 repost of a plain retweet
+///
+/// Returns `None` when the original isn't on Nostr, so the retweet is
+/// posted as a copied text note instead.
+pub async fn build_repost_event(
+    tweet: &Tweet,
+    original: &ReferencedTweet,
+    signer: &UserSigner,
+    data_dir: &Path,
+) -> Result<Option<Event>> {
+    let Some(original_info) = nostr::check_existing_nostr_event(data_dir, &original.id).await?
+    else {
+        info!(
+            "Retweeted tweet {orig_id} is not on Nostr, posting retweet {tweet_id} as a text note",
+            orig_id = original.id,
+            tweet_id = tweet.id
+        );
+        return Ok(None);
+    };
+
+    let event = signer
+        .sign_event(repost::repost_builder(tweet, &original_info)?)
+        .await?;
+    Ok(Some(event))
+}
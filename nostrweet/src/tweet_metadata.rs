@@ -0,0 +1,221 @@
+use anyhow::Result;
+use nostr_sdk::Tag;
+use std::collections::BTreeSet;
+
+use crate::twitter::Tweet;
+
+/// NIP-32 label namespace for the language of a note
+const LANGUAGE_NAMESPACE: &str = "ISO-639-1";
+
+/// Reason given in the NIP-36 `content-warning` tag
+const SENSITIVE_REASON: &str = "Marked as possibly sensitive on Twitter";
+
+/// Geohash precision for exact coordinates, a few meters
+const POINT_GEOHASH_PRECISION: usize = 9;
+
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Tags describing a tweet beyond its text: topics, language, location and sensitivity
+///
+/// - `t` for each hashtag and cashtag, lowercased (NIP-24)
+/// - `L`/`l` with the tweet language (NIP-32)
+/// - `g` with the geohash of the tweet's coordinates or tagged place
+/// - `content-warning` when Twitter flags the tweet (NIP-36)
+pub fn metadata_tags(tweet: &Tweet) -> Result<Vec<Tag>> {
+    let mut tags = Vec::new();
+
+    for topic in topics(tweet) {
+        tags.push(Tag::parse(vec!["t".to_string(), topic])?);
+    }
+
+    if let Some(language) = tweet.lang.as_deref().and_then(iso_639_1) {
+        tags.push(Tag::parse(vec!["L", LANGUAGE_NAMESPACE])?);
+        tags.push(Tag::parse(vec!["l", language, LANGUAGE_NAMESPACE])?);
+    }
+
+    if let Some(geohash) = geohash(tweet) {
+        tags.push(Tag::parse(vec!["g".to_string(), geohash])?);
+    }
+
+    if tweet.possibly_sensitive == Some(true) {
+        tags.push(Tag::parse(vec!["content-warning", SENSITIVE_REASON])?);
+    }
+
+    Ok(tags)
+}
+
+/// Hashtags and cashtags of a tweet, lowercased and without duplicates
+fn topics(tweet: &Tweet) -> BTreeSet<String> {
+    let Some(entities) = &tweet.entities else {
+        return BTreeSet::new();
+    };
+    let hashtags = entities.hashtags.iter().flatten().map(|h| h.tag.as_str());
+    let cashtags = entities.cashtags.iter().flatten().map(|c| c.tag.as_str());
+    hashtags
+        .chain(cashtags)
+        .map(|tag| tag.trim_start_matches(['#', '$']).to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// The two-letter code of a Twitter language, if it has one
+///
+/// Twitter uses `und` for undetermined text, `q*` codes for text without a
+/// language (hashtags only, links only…) and still reports Indonesian and
+/// Hebrew under their withdrawn codes.
+fn iso_639_1(lang: &str) -> Option<&str> {
+    let primary = lang.split('-').next()?;
+    match primary {
+        "in" => Some("id"),
+        "iw" => Some("he"),
+        code if code.len() == 2 && code.bytes().all(|b| b.is_ascii_lowercase()) => Some(code),
+        _ => None,
+    }
+}
+
+/// Geohash of the exact location of a tweet, or of the area of its place
+fn geohash(tweet: &Tweet) -> Option<String> {
+    let geo = tweet.geo.as_ref()?;
+    if let Some(point) = &geo.coordinates {
+        let [longitude, latitude] = point.coordinates;
+        return Some(encode_geohash(latitude, longitude, POINT_GEOHASH_PRECISION));
+    }
+
+    let place_id = geo.place_id.as_ref()?;
+    let place = tweet
+        .includes
+        .as_ref()?
+        .places
+        .as_ref()?
+        .iter()
+        .find(|place| &place.id == place_id)?;
+    let [west, south, east, north] =
+        <[f64; 4]>::try_from(place.geo.as_ref()?.bbox.as_deref()?).ok()?;
+
+    // The cell covering the whole area is the prefix its corners share
+    let south_west = encode_geohash(south, west, POINT_GEOHASH_PRECISION);
+    let north_east = encode_geohash(north, east, POINT_GEOHASH_PRECISION);
+    let shared = south_west
+        .chars()
+        .zip(north_east.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    // Areas straddling a top-level cell boundary have no common cell
+    (shared > 0).then(|| south_west[..shared].to_string())
+}
+
+/// Encodes a position as a geohash of `precision` characters
+fn encode_geohash(latitude: f64, longitude: f64, precision: usize) -> String {
+    let mut latitude_range = (-90.0, 90.0);
+    let mut longitude_range = (-180.0, 180.0);
+    let mut geohash = String::with_capacity(precision);
+    // Bits alternate between longitude and latitude, starting with longitude
+    let mut even_bit = true;
+    let mut index = 0;
+    let mut bits = 0;
+
+    while geohash.len() < precision {
+        let (range, value) = if even_bit {
+            (&mut longitude_range, longitude)
+        } else {
+            (&mut latitude_range, latitude)
+        };
+        let middle = (range.0 + range.1) / 2.0;
+        index <<= 1;
+        if value >= middle {
+            index |= 1;
+            range.0 = middle;
+        } else {
+            range.1 = middle;
+        }
+        even_bit = !even_bit;
+
+        bits += 1;
+        if bits == 5 {
+            geohash.push(char::from(GEOHASH_ALPHABET[index]));
+            index = 0;
+            bits = 0;
+        }
+    }
+    geohash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_tweet;
+
+    fn tag_values(tags: &[Tag]) -> Vec<Vec<String>> {
+        tags.iter().map(|tag| tag.clone().to_vec()).collect()
+    }
+
+    fn tweet(mut extra: serde_json::Value) -> Result<Tweet> {
+        extra["text"] = "Shipping #Rust 1.0 while $BTC moons #rust".into();
+        test_tweet("5000", "traveler", extra)
+    }
+
+    #[test]
+    fn test_encode_geohash() {
+        assert_eq!(encode_geohash(57.64911, 10.40744, 11), "u4pruydqqvj");
+        assert_eq!(encode_geohash(-25.382708, -49.265506, 5), "6gkzw");
+    }
+
+    #[test]
+    fn test_iso_639_1() {
+        assert_eq!(iso_639_1("en"), Some("en"));
+        assert_eq!(iso_639_1("zh-cn"), Some("zh"));
+        assert_eq!(iso_639_1("in"), Some("id"));
+        assert_eq!(iso_639_1("und"), None);
+        assert_eq!(iso_639_1("qme"), None);
+        assert_eq!(iso_639_1("zxx"), None);
+    }
+
+    #[test]
+    fn test_metadata_tags() -> Result<()> {
+        let tweet = tweet(serde_json::json!({
+            "entities": {
+                "hashtags": [{ "tag": "Rust" }, { "tag": "rust" }],
+                "cashtags": [{ "tag": "BTC" }]
+            },
+            "lang": "en",
+            "possibly_sensitive": true,
+            "geo": { "coordinates": { "type": "Point", "coordinates": [10.40744, 57.64911] } }
+        }))?;
+
+        assert_eq!(
+            tag_values(&metadata_tags(&tweet)?),
+            vec![
+                vec!["t", "btc"],
+                vec!["t", "rust"],
+                vec!["L", "ISO-639-1"],
+                vec!["l", "en", "ISO-639-1"],
+                vec!["g", "u4pruydqq"],
+                vec!["content-warning", SENSITIVE_REASON],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_place_geohash() -> Result<()> {
+        let tweet = tweet(serde_json::json!({
+            "geo": { "place_id": "01a9a39529b27f36" },
+            "includes": {
+                "places": [{
+                    "id": "01a9a39529b27f36",
+                    "full_name": "Manhattan, NY",
+                    "geo": { "type": "Feature", "bbox": [-74.026675, 40.683935, -73.910408, 40.877483] }
+                }]
+            }
+        }))?;
+        assert_eq!(geohash(&tweet).as_deref(), Some("dr"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_tweet_has_no_metadata_tags() -> Result<()> {
+        let tweet = tweet(serde_json::json!({ "lang": "und", "possibly_sensitive": false }))?;
+        assert!(metadata_tags(&tweet)?.is_empty());
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error_utils::{create_http_client_with_context, parse_http_response_json};
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, bail, ensure};
 use backoff::{ExponentialBackoffBuilder, backoff::Backoff};
 use regex::Regex;
 use reqwest::{Client, StatusCode};
@@ -31,13 +31,47 @@ pub enum TwitterError {
     Other(#[from] anyhow::Error),
 }
 
-const TWITTER_API_BASE: &str = "https://api.twitter.com/2";
+/// Twitter API v2 base URL used unless another one is configured
+pub const DEFAULT_TWITTER_API_BASE: &str = "https://api.twitter.com/2";
+
+/// Credentials and base URL of the Twitter API, built once in `main`
+#[derive(Debug, Clone)]
+pub struct TwitterApi {
+    bearer_token: String,
+    /// API base URL, without trailing slash
+    api_base: String,
+}
+
+impl TwitterApi {
+    pub fn new(bearer_token: &str) -> Self {
+        Self {
+            bearer_token: bearer_token.to_string(),
+            api_base: DEFAULT_TWITTER_API_BASE.to_string(),
+        }
+    }
+
+    /// Sends requests to another API base URL, e.g. a fake Twitter server for offline testing
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn bearer_token(&self) -> &str {
+        &self.bearer_token
+    }
+
+    /// Creates a client for this API, saving downloads to `data_dir`
+    pub fn client(&self, data_dir: &Path) -> Result<TwitterClient> {
+        Ok(TwitterClient::new(data_dir, &self.bearer_token)?.with_api_base(&self.api_base))
+    }
+}
 
 // Common URL parameters for API requests
 const COMMON_MEDIA_FIELDS: &str = "url,preview_image_url,alt_text,variants,media_key,type";
-const COMMON_TWEET_FIELDS: &str = "created_at,entities,referenced_tweets,author_id,note_tweet";
+const COMMON_PLACE_FIELDS: &str = "full_name,geo";
+const COMMON_TWEET_FIELDS: &str = "created_at,entities,referenced_tweets,author_id,note_tweet,conversation_id,in_reply_to_user_id,edit_history_tweet_ids,edit_controls,lang,possibly_sensitive,geo";
 const COMMON_USER_FIELDS: &str = "name,username,profile_image_url,description,url,entities";
-const COMMON_EXPANSIONS: &str = "attachments.media_keys,referenced_tweets.id,referenced_tweets.id.attachments.media_keys,author_id";
+const COMMON_EXPANSIONS: &str = "attachments.media_keys,referenced_tweets.id,referenced_tweets.id.attachments.media_keys,author_id,geo.place_id";
 
 /// Twitter API rate limit information extracted from response headers
 #[derive(Debug, Clone, Default)]
@@ -83,6 +117,55 @@ pub struct Tweet {
     /// For tweets that exceed the standard character limit (X Premium long tweets).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub note_tweet: Option<NoteTweet>,
+
+    /// ID of the first tweet of the conversation (the thread root)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+
+    /// Author of the tweet this one replies to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_reply_to_user_id: Option<String>,
+
+    /// IDs of every version of the tweet, oldest first, this one included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit_history_tweet_ids: Option<Vec<String>>,
+
+    /// Whether and until when the tweet can still be edited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit_controls: Option<EditControls>,
+
+    /// Language detected by Twitter (BCP 47, `und` when undetermined)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+
+    /// Whether the tweet's links or media may be sensitive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub possibly_sensitive: Option<bool>,
+
+    /// Place tagged by the author and, if shared, their exact location
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo: Option<TweetGeo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EditControls {
+    pub edits_remaining: Option<u32>,
+    pub is_edit_eligible: Option<bool>,
+    pub editable_until: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TweetGeo {
+    /// Expanded into `includes.places`
+    pub place_id: Option<String>,
+    pub coordinates: Option<GeoPoint>,
+}
+
+/// A GeoJSON point
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeoPoint {
+    /// Longitude, then latitude
+    pub coordinates: [f64; 2],
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -132,6 +215,8 @@ pub struct Entities {
     pub urls: Option<Vec<UrlEntity>>,
     pub mentions: Option<Vec<Mention>>,
     pub hashtags: Option<Vec<Hashtag>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cashtags: Option<Vec<Cashtag>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -151,11 +236,32 @@ pub struct Hashtag {
     pub tag: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cashtag {
+    pub tag: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Includes {
     pub media: Option<Vec<Media>>,
     pub users: Option<Vec<User>>,
     pub tweets: Option<Vec<Tweet>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub places: Option<Vec<Place>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Place {
+    pub id: String,
+    pub full_name: Option<String>,
+    pub geo: Option<PlaceGeo>,
+}
+
+/// A GeoJSON feature describing the area of a place
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaceGeo {
+    /// West longitude, south latitude, east longitude, north latitude
+    pub bbox: Option<Vec<f64>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -180,6 +286,8 @@ pub struct MediaVariant {
 pub struct TwitterClient {
     client: Client,
     bearer_token: String,
+    /// API base URL, without trailing slash
+    api_base: String,
     /// Data directory for tweets
     data_dir: Option<std::path::PathBuf>,
 }
@@ -191,6 +299,39 @@ pub struct TimelineResponse {
     pub includes: Option<Includes>,
 }
 
+/// Most tweet IDs the lookup endpoint accepts per request
+pub const TWEET_LOOKUP_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct TweetLookupResponse {
+    data: Option<Vec<TweetLookupData>>,
+    errors: Option<Vec<ApiProblem>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TweetLookupData {
+    id: String,
+}
+
+/// Entry of the `errors` array the API returns next to partial results
+#[derive(Debug, Deserialize)]
+struct ApiProblem {
+    value: Option<String>,
+    resource_id: Option<String>,
+    title: Option<String>,
+    #[serde(rename = "type")]
+    type_field: Option<String>,
+}
+
+/// Result of checking which tweets still exist
+#[derive(Debug, Default)]
+pub struct TweetLookup {
+    /// Tweets the API returned
+    pub found: Vec<String>,
+    /// Tweets the API reported as not found
+    pub missing: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TimelineMeta {
     pub result_count: u32,
@@ -391,7 +532,7 @@ impl TwitterClient {
                     reset = rate_limits.reset
                 );
             } else {
-                let status_code = response.status().as_u16();
+                let status = response.status();
                 let error_message = format!(
                     "limit: {limit:?}, remaining: {remaining:?}, reset: {reset:?}",
                     limit = rate_limits.limit,
@@ -399,7 +540,7 @@ impl TwitterClient {
                     reset = rate_limits.reset
                 );
 
-                return Err(match response.status() {
+                return Err(match status {
                     StatusCode::NOT_FOUND => TwitterError::UserNotFound {
                         username: resource_id.to_string(),
                     },
@@ -407,10 +548,16 @@ impl TwitterClient {
                         reset_time: rate_limits.reset,
                         remaining: rate_limits.remaining,
                     },
-                    _ => TwitterError::ApiError {
-                        status: status_code,
-                        message: error_message,
-                    },
+                    _ => {
+                        // Keep the body, callers tell errors apart by Twitter's details
+                        let body = response.text().await.with_context(|| {
+                            format!("Failed to read Twitter API error for {resource_id}")
+                        })?;
+                        TwitterError::ApiError {
+                            status: status.as_u16(),
+                            message: format!("{body} ({error_message})"),
+                        }
+                    }
                 }
                 .into());
             }
@@ -553,10 +700,17 @@ impl TwitterClient {
         Ok(Self {
             client,
             bearer_token: bearer_token.to_string(),
+            api_base: DEFAULT_TWITTER_API_BASE.to_string(),
             data_dir: Some(data_dir.to_path_buf()),
         })
     }
 
+    /// Sends requests to `api_base` instead of the default API base URL
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
+    }
+
     /// Retrieves a tweet by its ID, including all media and referenced tweets
     pub async fn get_tweet(&self, tweet_id: &str) -> Result<Tweet> {
         // Use a new function that returns a pinned box to handle recursive async calls
@@ -863,30 +1017,8 @@ impl TwitterClient {
         }
 
         // Process includes data to populate tweet metadata
-        if let Some(includes) = timeline_response.includes {
-            // Add author information to each tweet
-            if let Some(users) = &includes.users {
-                use std::collections::HashMap;
-                if !users.is_empty() {
-                    // Build a lookup map id -> user for fast access
-                    let user_map: HashMap<String, crate::twitter::User> =
-                        users.iter().cloned().map(|u| (u.id.clone(), u)).collect();
-                    for tweet in &mut tweets {
-                        if let Some(author_id) = &tweet.author_id
-                            && let Some(user) = user_map.get(author_id)
-                        {
-                            tweet.author = user.clone();
-                        }
-                    }
-                }
-            }
-
-            // Add media information to each tweet
-            if let Some(ref media) = includes.media {
-                for tweet in &mut tweets {
-                    self.add_media_to_tweet(tweet, media);
-                }
-            }
+        if let Some(includes) = &timeline_response.includes {
+            self.apply_timeline_includes(&mut tweets, includes);
         }
 
         // Save all newly fetched tweets to cache
@@ -962,6 +1094,83 @@ impl TwitterClient {
         }
     }
 
+    /// Fills in authors and media of timeline tweets from the response includes
+    fn apply_timeline_includes(&self, tweets: &mut [Tweet], includes: &Includes) {
+        // Add author information to each tweet
+        if let Some(users) = &includes.users {
+            use std::collections::HashMap;
+            if !users.is_empty() {
+                // Build a lookup map id -> user for fast access
+                let user_map: HashMap<String, crate::twitter::User> =
+                    users.iter().cloned().map(|u| (u.id.clone(), u)).collect();
+                for tweet in tweets.iter_mut() {
+                    if let Some(author_id) = &tweet.author_id
+                        && let Some(user) = user_map.get(author_id)
+                    {
+                        tweet.author = user.clone();
+                    }
+                }
+            }
+        }
+
+        // Add media information to each tweet
+        if let Some(ref media) = includes.media {
+            for tweet in tweets.iter_mut() {
+                self.add_media_to_tweet(tweet, media);
+            }
+        }
+
+        // Add the place each tweet is tagged with
+        if let Some(places) = &includes.places {
+            for tweet in tweets.iter_mut() {
+                let Some(place) = tweet
+                    .geo
+                    .as_ref()
+                    .and_then(|geo| geo.place_id.as_ref())
+                    .and_then(|place_id| places.iter().find(|place| &place.id == place_id))
+                else {
+                    continue;
+                };
+                if let Some(tweet_includes) = &mut tweet.includes {
+                    tweet_includes.places = Some(vec![place.clone()]);
+                } else {
+                    tweet.includes = Some(Includes {
+                        media: None,
+                        users: None,
+                        tweets: None,
+                        places: Some(vec![place.clone()]),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Fetches a single page of a user's timeline, walking backward from `until_id`
+    /// or continuing from `pagination_token`
+    ///
+    /// Unlike `get_user_timeline`, nothing is read from or written to the cache.
+    pub async fn get_user_timeline_page(
+        &self,
+        user_id: &str,
+        max_results: u32,
+        pagination_token: Option<&str>,
+        until_id: Option<&str>,
+    ) -> Result<TimelineResponse> {
+        // The API accepts 5 to 100 results per page
+        let max_results = max_results.clamp(5, 100);
+        let url =
+            self.build_user_timeline_url(user_id, max_results, pagination_token, None, until_id);
+
+        let response = self.api_request(user_id, &url).await?;
+        let mut page: TimelineResponse =
+            parse_http_response_json(response, "Twitter API timeline").await?;
+
+        if let (Some(tweets), Some(includes)) = (&mut page.data, &page.includes) {
+            self.apply_timeline_includes(tweets, includes);
+        }
+        Ok(page)
+    }
+
     /// Adds media items to a tweet if they match the tweet's media keys
     fn add_media_to_tweet(&self, tweet: &mut Tweet, media_items: &[Media]) {
         // Create includes for this tweet if it doesn't exist
@@ -970,6 +1179,7 @@ impl TwitterClient {
                 media: None,
                 users: None,
                 tweets: None,
+                places: None,
             });
         }
 
@@ -1053,6 +1263,7 @@ impl TwitterClient {
                 current_page_size,
                 next_token.as_deref(),
                 since_id,
+                None,
             );
 
             let response = self.api_request(user_id, &url).await?;
@@ -1096,10 +1307,12 @@ impl TwitterClient {
     /// Builds a Twitter API URL for fetching a specific tweet with all necessary fields
     fn build_tweet_url(&self, tweet_id: &str) -> String {
         format!(
-            "{TWITTER_API_BASE}/tweets/{tweet_id}?expansions={COMMON_EXPANSIONS}\
+            "{api_base}/tweets/{tweet_id}?expansions={COMMON_EXPANSIONS}\
             &media.fields={COMMON_MEDIA_FIELDS}\
-            &tweet.fields={COMMON_TWEET_FIELDS},attachments,public_metrics,conversation_id,context_annotations\
-            &user.fields={COMMON_USER_FIELDS}"
+            &tweet.fields={COMMON_TWEET_FIELDS},attachments,public_metrics,context_annotations\
+            &user.fields={COMMON_USER_FIELDS}\
+            &place.fields={COMMON_PLACE_FIELDS}",
+            api_base = self.api_base
         )
     }
 
@@ -1110,12 +1323,16 @@ impl TwitterClient {
         max_results: u32,
         pagination_token: Option<&str>,
         since_id: Option<&str>,
+        until_id: Option<&str>,
     ) -> String {
-        let base = format!("{TWITTER_API_BASE}/users/{user_id}/tweets?max_results={max_results}");
+        let base = format!(
+            "{api_base}/users/{user_id}/tweets?max_results={max_results}",
+            api_base = self.api_base
+        );
 
         // Build URL with common parameters
         let params = format!(
-            "&expansions={COMMON_EXPANSIONS}&media.fields={COMMON_MEDIA_FIELDS}&tweet.fields={COMMON_TWEET_FIELDS}&user.fields={COMMON_USER_FIELDS}"
+            "&expansions={COMMON_EXPANSIONS}&media.fields={COMMON_MEDIA_FIELDS}&tweet.fields={COMMON_TWEET_FIELDS}&user.fields={COMMON_USER_FIELDS}&place.fields={COMMON_PLACE_FIELDS}"
         );
 
         // Add pagination token if provided
@@ -1125,13 +1342,17 @@ impl TwitterClient {
         // Add since_id if provided (for efficient pagination)
         let since_param = since_id.map_or(String::new(), |id| format!("&since_id={id}"));
 
-        format!("{base}{params}{token_param}{since_param}")
+        // Add until_id if provided (for walking backward through older tweets)
+        let until_param = until_id.map_or(String::new(), |id| format!("&until_id={id}"));
+
+        format!("{base}{params}{token_param}{since_param}{until_param}")
     }
 
     /// Get a user's profile from their username
     pub async fn get_user_by_username(&self, username: &str) -> Result<User> {
         let url = format!(
-            "{TWITTER_API_BASE}/users/by/username/{username}?user.fields={COMMON_USER_FIELDS}"
+            "{api_base}/users/by/username/{username}?user.fields={COMMON_USER_FIELDS}",
+            api_base = self.api_base
         );
 
         let response = self
@@ -1237,8 +1458,11 @@ impl TwitterClient {
     }
 
     /// Get a user's ID from their username
-    async fn get_user_id(&self, username: &str) -> Result<String> {
-        let url = format!("{TWITTER_API_BASE}/users/by/username/{username}");
+    pub async fn get_user_id(&self, username: &str) -> Result<String> {
+        let url = format!(
+            "{api_base}/users/by/username/{username}",
+            api_base = self.api_base
+        );
 
         let response = self.api_request(username, &url).await?;
 
@@ -1251,10 +1475,57 @@ impl TwitterClient {
         Ok(user_id.to_string())
     }
 
+    /// Check which of up to 100 tweets still exist, in one request
+    ///
+    /// Only tweets the API explicitly reports as not found end up in `missing`.
+    /// Tweets withheld for other reasons (protected or suspended accounts) are
+    /// in neither list, so callers can't mistake them for deletions.
+    pub async fn lookup_tweets(&self, tweet_ids: &[String]) -> Result<TweetLookup> {
+        ensure!(
+            tweet_ids.len() <= TWEET_LOOKUP_LIMIT,
+            "Can't look up more than {TWEET_LOOKUP_LIMIT} tweets at once"
+        );
+        if tweet_ids.is_empty() {
+            return Ok(TweetLookup::default());
+        }
+
+        let url = format!(
+            "{api_base}/tweets?ids={ids}&tweet.fields=author_id",
+            api_base = self.api_base,
+            ids = tweet_ids.join(",")
+        );
+        let response = self
+            .api_request(&format!("tweets:{count}", count = tweet_ids.len()), &url)
+            .await
+            .context("API request for tweet lookup failed")?;
+        let body: TweetLookupResponse = parse_http_response_json(response, "tweet lookup").await?;
+
+        let found = body
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tweet| tweet.id)
+            .collect();
+        let missing = body
+            .errors
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|problem| {
+                problem.type_field.as_deref()
+                    == Some("https://api.twitter.com/2/problems/resource-not-found")
+                    || problem.title.as_deref() == Some("Not Found Error")
+            })
+            .filter_map(|problem| problem.resource_id.or(problem.value))
+            .collect();
+
+        Ok(TweetLookup { found, missing })
+    }
+
     /// Fetch a tweet with extended media information to get video URLs
     pub async fn get_tweet_with_media(&self, tweet_id: &str) -> Result<Tweet> {
         let url = format!(
-            "{TWITTER_API_BASE}/tweets/{tweet_id}?expansions=attachments.media_keys&media.fields=url,preview_image_url,alt_text,variants,media_key,type&tweet.fields=created_at,entities,referenced_tweets,author_id,note_tweet"
+            "{api_base}/tweets/{tweet_id}?expansions=attachments.media_keys&media.fields=url,preview_image_url,alt_text,variants,media_key,type&tweet.fields=created_at,entities,referenced_tweets,author_id,note_tweet",
+            api_base = self.api_base
         );
 
         debug!("Fetching tweet with media: {url}");
@@ -1533,4 +1804,29 @@ mod tests {
         let variants = media.variants.unwrap();
         assert_eq!(variants[0].content_type, "video/mp4");
     }
+
+    #[tokio::test]
+    async fn test_client_uses_configured_api_base() -> Result<()> {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/2/users/by/username/testuser")
+            .match_query(mockito::Matcher::Any)
+            .match_header("authorization", "Bearer test-token")
+            .with_header("content-type", "application/json")
+            .with_header("x-rate-limit-remaining", "899")
+            .with_body(r#"{"data":{"id":"42","name":"Test User","username":"testuser"}}"#)
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir()?;
+        let client = TwitterApi::new("test-token")
+            .with_api_base(&format!("{url}/2/", url = server.url()))
+            .client(temp_dir.path())?;
+        let user = client.get_user_by_username("testuser").await?;
+
+        assert_eq!(user.id, "42");
+        assert_eq!(user.name.as_deref(), Some("Test User"));
+        mock.assert_async().await;
+        Ok(())
+    }
 }